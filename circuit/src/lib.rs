@@ -78,19 +78,11 @@ impl TestCircuitHandle {
     ) -> anyhow::Result<NamedTempFile> {
         let formatted_input_str = serde_json::to_string(&input_signals.to_json_value())?;
         witness_gen(
-            self.witness_gen_js_path().to_str().unwrap(),
             self.witness_gen_wasm_path().to_str().unwrap(),
             &formatted_input_str,
         )
     }
 
-    fn witness_gen_js_path(&self) -> PathBuf {
-        self.dir
-            .path()
-            .to_owned()
-            .join("circuit_js/generate_witness.js")
-    }
-
     fn witness_gen_wasm_path(&self) -> PathBuf {
         self.dir.path().to_owned().join("circuit_js/circuit.wasm")
     }
@@ -64,6 +64,12 @@ trusted_setup() {
         echo
         echo "Exporting verification key..."
         snarkjs zkey export verificationkey $outdir/prover_key.zkey $outdir/verification_key.json
+
+        # Lets the prover service check its padding config against the circuit's max lengths.
+        cp main.circom $outdir/main.circom
+
+        # The prover service computes witnesses with this unless configured to use a subprocess.
+        cp main_js/main.wasm $outdir/main.wasm
     }
     popd
 
@@ -0,0 +1,211 @@
+// Copyright © Aptos Foundation
+
+use crate::read_json;
+use anyhow::{anyhow, bail, Context, Result};
+use aptos_crypto::{
+    ed25519::{Ed25519PrivateKey, Ed25519PublicKey},
+    Uniform, ValidCryptoMaterialStringExt,
+};
+use clap::Subcommand;
+use prover_service::{groth16_vk::Groth16VerificationKey, prover_key::OnChainKeylessConfiguration};
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+#[derive(Subcommand)]
+pub enum KeysCommand {
+    /// Generates a training wheels key pair.
+    GenerateTwKey {
+        /// Writes the private key to this file, in the format the prover reads it in, instead of
+        /// printing it.
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Prints the on-chain keyless configuration for a training wheels key.
+    TwConfig {
+        #[arg(long)]
+        private_key_file: PathBuf,
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Converts a snarkjs `verification_key.json`, or a VK in arkworks canonical bytes, to its
+    /// on-chain form.
+    ConvertVk {
+        #[arg(long)]
+        vk: PathBuf,
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Compares a snarkjs `verification_key.json` with an on-chain
+    /// `0x1::keyless_account::Groth16VerificationKey` resource.
+    CompareVk {
+        #[arg(long)]
+        vk: PathBuf,
+        /// The resource as returned by a fullnode's API.
+        #[arg(long)]
+        on_chain: PathBuf,
+    },
+    /// Compares a training wheels key with the one in an on-chain
+    /// `0x1::keyless_account::Configuration` resource.
+    CompareTwKey {
+        #[arg(long)]
+        private_key_file: PathBuf,
+        /// The resource as returned by a fullnode's API.
+        #[arg(long)]
+        on_chain: PathBuf,
+    },
+}
+
+pub fn run(command: KeysCommand) -> Result<()> {
+    match command {
+        KeysCommand::GenerateTwKey { output } => {
+            let private_key = Ed25519PrivateKey::generate(&mut rand::thread_rng());
+            let public_key = Ed25519PublicKey::from(&private_key);
+            let private_key_encoded = private_key.to_encoded_string()?;
+            match output {
+                Some(path) => write(&path, &private_key_encoded)?,
+                None => println!("private key: {}", private_key_encoded),
+            }
+            println!("public key: {}", public_key.to_encoded_string()?);
+        }
+        KeysCommand::TwConfig {
+            private_key_file,
+            output,
+        } => {
+            let config = tw_config(&private_key_file)?;
+            print_or_write(&config, output.as_deref())?;
+        }
+        KeysCommand::ConvertVk { vk, output } => {
+            let on_chain_vk = Groth16VerificationKey::from_file(&vk)?.try_as_onchain_repr()?;
+            print_or_write(&on_chain_vk, output.as_deref())?;
+        }
+        KeysCommand::CompareVk { vk, on_chain } => {
+            let local = serde_json::to_value(
+                Groth16VerificationKey::from_file(&vk)?.try_as_onchain_repr()?,
+            )?;
+            compare(&local["data"], &read_json(&on_chain)?["data"], "data")?;
+        }
+        KeysCommand::CompareTwKey {
+            private_key_file,
+            on_chain,
+        } => {
+            let local = serde_json::to_value(tw_config(&private_key_file)?)?;
+            compare(
+                &local["data"]["training_wheels_pubkey"],
+                &read_json(&on_chain)?["data"]["training_wheels_pubkey"],
+                "data.training_wheels_pubkey",
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn tw_config(private_key_file: &Path) -> Result<OnChainKeylessConfiguration> {
+    let private_key_encoded = fs::read_to_string(private_key_file)
+        .with_context(|| format!("Couldn't read {}", private_key_file.display()))?;
+    let private_key = Ed25519PrivateKey::from_encoded_string(private_key_encoded.trim())
+        .map_err(|e| anyhow!("Invalid training wheels private key: {}", e))?;
+    Ok(OnChainKeylessConfiguration::from_tw_pk(Some(
+        Ed25519PublicKey::from(&private_key),
+    )))
+}
+
+fn write(path: &Path, contents: &str) -> Result<()> {
+    fs::write(path, contents).with_context(|| format!("Couldn't write {}", path.display()))
+}
+
+fn print_or_write(value: &impl Serialize, output: Option<&Path>) -> Result<()> {
+    let json = serde_json::to_string_pretty(value)?;
+    match output {
+        Some(path) => write(path, &json),
+        None => {
+            println!("{}", json);
+            Ok(())
+        }
+    }
+}
+
+fn compare(local: &Value, on_chain: &Value, path: &str) -> Result<()> {
+    let differences = diff(local, on_chain, path);
+    if differences.is_empty() {
+        println!("The local and on-chain keys match");
+        return Ok(());
+    }
+
+    for difference in &differences {
+        println!("{}", difference);
+    }
+    bail!("The local and on-chain keys differ");
+}
+
+/// Lists the paths at which `local` and `on_chain` differ, with both values.
+fn diff(local: &Value, on_chain: &Value, path: &str) -> Vec<String> {
+    match (local, on_chain) {
+        (Value::Object(local_fields), Value::Object(on_chain_fields)) => {
+            let mut differences = vec![];
+            for (key, local_value) in local_fields {
+                let on_chain_value = on_chain_fields.get(key).unwrap_or(&Value::Null);
+                differences.extend(diff(local_value, on_chain_value, &format!("{path}.{key}")));
+            }
+            for (key, on_chain_value) in on_chain_fields {
+                if !local_fields.contains_key(key) {
+                    differences.extend(diff(
+                        &Value::Null,
+                        on_chain_value,
+                        &format!("{path}.{key}"),
+                    ));
+                }
+            }
+            differences
+        }
+        (Value::Array(local_elements), Value::Array(on_chain_elements))
+            if local_elements.len() == on_chain_elements.len() =>
+        {
+            local_elements
+                .iter()
+                .zip(on_chain_elements)
+                .enumerate()
+                .flat_map(|(i, (local, on_chain))| diff(local, on_chain, &format!("{path}[{i}]")))
+                .collect()
+        }
+        _ if local == on_chain => vec![],
+        _ => vec![format!(
+            "{path}:\n  local:    {local}\n  on-chain: {on_chain}"
+        )],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::diff;
+    use serde_json::json;
+
+    #[test]
+    fn test_diff() {
+        let local = json!({ "alpha_g1": "0x01", "gamma_abc_g1": ["0x02", "0x03"] });
+
+        assert!(diff(&local, &local, "data").is_empty());
+        assert_eq!(
+            diff(
+                &local,
+                &json!({ "alpha_g1": "0x01", "gamma_abc_g1": ["0x02", "0x04"], "extra": 1 }),
+                "data"
+            ),
+            vec![
+                "data.gamma_abc_g1[1]:\n  local:    \"0x03\"\n  on-chain: \"0x04\"",
+                "data.extra:\n  local:    null\n  on-chain: 1",
+            ]
+        );
+        assert_eq!(
+            diff(
+                &local,
+                &json!({ "alpha_g1": "0x01", "gamma_abc_g1": [] }),
+                "data"
+            ),
+            vec!["data.gamma_abc_g1:\n  local:    [\"0x02\",\"0x03\"]\n  on-chain: []"]
+        );
+    }
+}
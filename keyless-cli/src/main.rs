@@ -0,0 +1,302 @@
+// Copyright © Aptos Foundation
+
+use anyhow::{anyhow, bail, Context, Result};
+use aptos_crypto::ed25519::Ed25519PublicKey;
+use aptos_keyless_common::{
+    input_processing::{
+        config::CircuitPaddingConfig,
+        encoding::{AsFr, FromB64, JwtHeader, JwtParts},
+        signal_schema::SignalSchema,
+    },
+    secret::Secret,
+    EphemeralPublicKeyBlinder,
+};
+use aptos_types::{
+    jwks::rsa::RSA_JWK, keyless::Pepper, transaction::authenticator::EphemeralPublicKey,
+};
+use ark_ff::{BigInteger, PrimeField};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use prover_service::{
+    api::RequestInput,
+    config::{EmailVerifiedPolicy, UidKeyConfig},
+    input_processing::{
+        derive_circuit_input_signals,
+        preprocess::decode_with_jwk,
+        public_inputs_hash::{compute_idc_hash, compute_public_inputs_hash},
+        types::Input,
+    },
+    jwk_fetching::parse_jwks,
+    training_wheels::{check_nonce_consistency, verification_logic::compute_nonce},
+};
+use serde_json::Value;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+mod keys;
+mod verify;
+
+/// Computes what the keyless prover computes from a request, without a prover and offline, and
+/// manages the keys the prover and the chain share.
+#[derive(Parser)]
+#[command(name = "keyless-cli")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Prints the nonce that the JWT must carry for an ephemeral public key.
+    Nonce(NonceArgs),
+    /// Prints the public inputs hash of a request.
+    PublicInputsHash(RequestArgs),
+    /// Prints the IdCommitment of a request.
+    Idc(RequestArgs),
+    /// Prints the padded circuit input signals of a request, as passed to the witness generator.
+    CircuitInput {
+        #[command(flatten)]
+        request: RequestArgs,
+        /// Writes the signals to this file instead of printing them.
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Checks a prover response's proof and training wheels signature.
+    Verify(verify::VerifyArgs),
+    /// Manages the training wheels key and the Groth16 verification key and their on-chain forms.
+    #[command(subcommand)]
+    Keys(keys::KeysCommand),
+}
+
+#[derive(Args)]
+struct NonceArgs {
+    /// The circuit's padding config: a `conversion_config.yml`, or the `main.circom` of the
+    /// circuit.
+    #[arg(long)]
+    circuit_config: PathBuf,
+    /// The ephemeral public key in hex, either as the 32 bytes of an Ed25519 public key or as a
+    /// BCS-serialized `EphemeralPublicKey`.
+    #[arg(long)]
+    epk: String,
+    /// The EPK blinder in hex.
+    #[arg(long)]
+    blinder: String,
+    #[arg(long)]
+    exp_date_secs: u64,
+}
+
+#[derive(Args)]
+struct RequestArgs {
+    #[command(flatten)]
+    nonce: NonceArgs,
+    /// The JWT, or `@<path>` to read it from a file.
+    #[arg(long)]
+    jwt: String,
+    /// A JWK, or a JWK set in which case the key matching the JWT's kid is used.
+    #[arg(long)]
+    jwk_file: PathBuf,
+    /// The pepper in hex.
+    #[arg(long)]
+    pepper: String,
+    #[arg(long, default_value = "sub")]
+    uid_key: String,
+    #[arg(long)]
+    exp_horizon_secs: u64,
+    #[arg(long)]
+    extra_field: Option<String>,
+    #[arg(long)]
+    idc_aud: Option<String>,
+    #[arg(long)]
+    aud_index: Option<usize>,
+    /// Defaults to what the prover defaults to: required for the "email" uid key, not required
+    /// for any other.
+    #[arg(long, value_enum)]
+    email_verified: Option<EmailVerified>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum EmailVerified {
+    Required,
+    NotRequired,
+}
+
+impl From<EmailVerified> for EmailVerifiedPolicy {
+    fn from(email_verified: EmailVerified) -> Self {
+        match email_verified {
+            EmailVerified::Required => EmailVerifiedPolicy::Required,
+            EmailVerified::NotRequired => EmailVerifiedPolicy::NotRequired,
+        }
+    }
+}
+
+fn main() -> Result<()> {
+    match Cli::parse().command {
+        Command::Nonce(args) => {
+            let circuit_config = read_circuit_config(&args.circuit_config)?;
+            let nonce = compute_nonce(
+                args.exp_date_secs,
+                &parse_epk(&args.epk)?,
+                parse_blinder(&args.blinder)?.as_fr(),
+                &circuit_config,
+            )?;
+            println!("{}", nonce);
+        }
+        Command::PublicInputsHash(args) => {
+            let (input, circuit_config) = args.into_input()?;
+            println!("{}", compute_public_inputs_hash(&input, &circuit_config)?);
+        }
+        Command::Idc(args) => {
+            let (input, circuit_config) = args.into_input()?;
+            let idc = compute_idc_hash(
+                &input,
+                &circuit_config,
+                *input.pepper_fr.expose_secret(),
+                &input.jwt_parts.expose_secret().payload_decoded()?,
+            )?;
+            println!("{}", idc);
+            println!("0x{}", hex::encode(idc.into_bigint().to_bytes_le()));
+        }
+        Command::CircuitInput { request, output } => {
+            let (input, circuit_config) = request.into_input()?;
+            let (circuit_input_signals, _) = derive_circuit_input_signals(input, &circuit_config)?;
+            let json = serde_json::to_string_pretty(&circuit_input_signals.to_json_value())?;
+            match output {
+                Some(path) => fs::write(&path, json)
+                    .with_context(|| format!("Couldn't write {}", path.display()))?,
+                None => println!("{}", json),
+            }
+        }
+        Command::Verify(args) => verify::run(args)?,
+        Command::Keys(command) => keys::run(command)?,
+    }
+    Ok(())
+}
+
+impl RequestArgs {
+    fn into_input(self) -> Result<(Input, CircuitPaddingConfig)> {
+        let circuit_config = read_circuit_config(&self.nonce.circuit_config)?;
+        let jwt = match self.jwt.strip_prefix('@') {
+            Some(path) => fs::read_to_string(path)
+                .with_context(|| format!("Couldn't read {}", path))?
+                .trim()
+                .to_owned(),
+            None => self.jwt,
+        };
+        let jwk = read_jwk(&self.jwk_file, &jwt)?;
+        let email_verified_policy = self
+            .email_verified
+            .map(EmailVerifiedPolicy::from)
+            .unwrap_or_else(|| UidKeyConfig::new(&self.uid_key).email_verified_policy());
+
+        let request = RequestInput {
+            jwt_b64: Secret::new(jwt),
+            epk: parse_epk(&self.nonce.epk)?,
+            epk_blinder: Secret::new(parse_blinder(&self.nonce.blinder)?),
+            exp_date_secs: self.nonce.exp_date_secs,
+            exp_horizon_secs: self.exp_horizon_secs,
+            pepper: Secret::new(parse_pepper(&self.pepper)?),
+            uid_key: self.uid_key,
+            extra_field: self.extra_field,
+            idc_aud: self.idc_aud,
+            aud_index: self.aud_index,
+            use_insecure_test_jwk: false,
+            proof_encodings: vec![],
+        };
+        let input = decode_with_jwk(request, Arc::new(jwk), email_verified_policy)?;
+
+        // Not an error, since finding out why the nonce doesn't match is one of the reasons to
+        // use this.
+        if let Err(e) = check_nonce_consistency(&input, &circuit_config) {
+            eprintln!("Warning: {}", e);
+        }
+        Ok((input, circuit_config))
+    }
+}
+
+fn read_circuit_config(path: &Path) -> Result<CircuitPaddingConfig> {
+    let contents =
+        fs::read_to_string(path).with_context(|| format!("Couldn't read {}", path.display()))?;
+
+    if path
+        .extension()
+        .is_some_and(|extension| extension == "circom")
+    {
+        CircuitPaddingConfig::from_main_circom(&contents)
+    } else {
+        let circuit_config: CircuitPaddingConfig = serde_yaml::from_str(&contents)?;
+        Ok(circuit_config.with_signal_schema(SignalSchema::keyless_main()))
+    }
+}
+
+fn read_json(path: &Path) -> Result<Value> {
+    let contents =
+        fs::read_to_string(path).with_context(|| format!("Couldn't read {}", path.display()))?;
+    Ok(serde_json::from_str(&contents)?)
+}
+
+fn read_jwk(path: &Path, jwt: &str) -> Result<RSA_JWK> {
+    select_jwk(&read_json(path)?, jwt)
+}
+
+fn select_jwk(jwk_json: &Value, jwt: &str) -> Result<RSA_JWK> {
+    if jwk_json.get("keys").is_none() {
+        return RSA_JWK::try_from(jwk_json).map_err(|e| anyhow!("Invalid JWK: {}", e));
+    }
+
+    let header: JwtHeader = serde_json::from_str(&JwtParts::from_b64(jwt)?.header_decoded()?)?;
+    let keys = parse_jwks(jwk_json)?;
+    let jwk = keys
+        .get(&header.kid)
+        .ok_or_else(|| anyhow!("The JWK set has no key with kid {}", header.kid))?;
+    Ok((**jwk).clone())
+}
+
+fn parse_epk(epk_hex: &str) -> Result<EphemeralPublicKey> {
+    let bytes = hex::decode(epk_hex.trim_start_matches("0x"))?;
+    if bytes.len() == 32 {
+        let public_key = Ed25519PublicKey::try_from(bytes.as_slice())
+            .map_err(|e| anyhow!("Invalid Ed25519 public key: {}", e))?;
+        Ok(EphemeralPublicKey::ed25519(public_key))
+    } else {
+        Ok(bcs::from_bytes(&bytes)?)
+    }
+}
+
+fn parse_blinder(blinder_hex: &str) -> Result<EphemeralPublicKeyBlinder> {
+    Ok(hex::decode(blinder_hex.trim_start_matches("0x"))?)
+}
+
+fn parse_pepper(pepper_hex: &str) -> Result<Pepper> {
+    let bytes = hex::decode(pepper_hex.trim_start_matches("0x"))?;
+    match <[u8; 31]>::try_from(bytes.as_slice()) {
+        Ok(bytes) => Ok(Pepper::new(bytes)),
+        Err(_) => bail!("A pepper is 31 bytes long, got {}", bytes.len()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::select_jwk;
+    use serde_json::json;
+
+    const JWT: &str = "eyJhbGciOiJSUzI1NiIsImtpZCI6InRlc3QtcnNhIiwidHlwIjoiSldUIn0.e30.c2ln";
+
+    #[test]
+    fn test_select_jwk() {
+        let jwk =
+            json!({ "kty": "RSA", "kid": "test-rsa", "alg": "RS256", "e": "AQAB", "n": "6S7a" });
+        let other_jwk =
+            json!({ "kty": "RSA", "kid": "other", "alg": "RS256", "e": "AQAB", "n": "3U9r" });
+
+        assert_eq!(select_jwk(&jwk, JWT).unwrap().kid, "test-rsa");
+        assert_eq!(
+            select_jwk(&json!({ "keys": [other_jwk, jwk] }), JWT)
+                .unwrap()
+                .n,
+            "6S7a"
+        );
+        assert!(select_jwk(&json!({ "keys": [other_jwk] }), JWT).is_err());
+    }
+}
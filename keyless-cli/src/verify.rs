@@ -0,0 +1,116 @@
+// Copyright © Aptos Foundation
+
+use crate::{read_circuit_config, read_json, read_jwk, EmailVerified};
+use anyhow::{anyhow, bail, Result};
+use aptos_crypto::{ed25519::Ed25519PublicKey, poseidon_bn254, ValidCryptoMaterialStringExt};
+use aptos_keyless_common::input_processing::encoding::AsFr;
+use clap::Args;
+use prover_service::{
+    api::{ProverServiceResponse, RequestInput},
+    config::{EmailVerifiedPolicy, UidKeyConfig},
+    groth16_vk::Groth16VerificationKey,
+    input_processing::{preprocess::decode_with_jwk, public_inputs_hash::compute_public_inputs},
+    training_wheels::{self, check_nonce_consistency},
+};
+use std::{path::PathBuf, sync::Arc};
+
+#[derive(Args)]
+pub struct VerifyArgs {
+    /// The prover's response.
+    #[arg(long)]
+    response: PathBuf,
+    /// The Groth16 verification key: a snarkjs `verification_key.json`, an on-chain
+    /// `0x1::keyless_account::Groth16VerificationKey` resource, or its arkworks canonical bytes.
+    #[arg(long)]
+    vk: PathBuf,
+    /// The training wheels public key in hex.
+    #[arg(long)]
+    tw_public_key: String,
+    /// The request the response is for, as sent to the prover. When given, the public inputs hash
+    /// is recomputed from it, and compared with the response's.
+    #[arg(long, requires_all = ["jwk_file", "circuit_config"])]
+    request: Option<PathBuf>,
+    /// A JWK, or a JWK set in which case the key matching the JWT's kid is used.
+    #[arg(long)]
+    jwk_file: Option<PathBuf>,
+    /// The circuit's padding config: a `conversion_config.yml`, or the `main.circom` of the
+    /// circuit.
+    #[arg(long)]
+    circuit_config: Option<PathBuf>,
+    /// The email_verified policy the prover applied to the request's uid key. Defaults to what
+    /// the prover defaults to: required for the "email" uid key, not required for any other.
+    #[arg(long, value_enum, requires = "request")]
+    email_verified: Option<EmailVerified>,
+}
+
+pub fn run(args: VerifyArgs) -> Result<()> {
+    let response: ProverServiceResponse = serde_json::from_value(read_json(&args.response)?)?;
+    let ProverServiceResponse::Success {
+        proof,
+        public_inputs_hash,
+        ..
+    } = &response
+    else {
+        bail!("The response is an error, there is nothing to verify");
+    };
+    let prepared_vk = Groth16VerificationKey::from_file(&args.vk)?.prepared_vk();
+    let tw_public_key = Ed25519PublicKey::from_encoded_string(&args.tw_public_key)
+        .map_err(|e| anyhow!("Invalid training wheels public key: {}", e))?;
+
+    let mut failed = false;
+    let public_inputs_hash_fr = public_inputs_hash.as_fr();
+    println!("Public inputs hash: {}", public_inputs_hash_fr);
+
+    match proof.verify_proof(public_inputs_hash_fr, &prepared_vk) {
+        Ok(()) => println!("Groth16 proof: valid"),
+        Err(e) => {
+            println!("Groth16 proof: INVALID: {:#}", e);
+            failed = true;
+        }
+    }
+    match training_wheels::verify(&response, &tw_public_key) {
+        Ok(()) => println!("Training wheels signature: valid"),
+        Err(e) => {
+            println!("Training wheels signature: INVALID: {:#}", e);
+            failed = true;
+        }
+    }
+
+    if let (Some(request), Some(jwk_file), Some(circuit_config)) =
+        (&args.request, &args.jwk_file, &args.circuit_config)
+    {
+        let request: RequestInput = serde_json::from_value(read_json(request)?)?;
+        let jwk = read_jwk(jwk_file, request.jwt_b64.expose_secret())?;
+        let circuit_config = read_circuit_config(circuit_config)?;
+        let email_verified_policy = args
+            .email_verified
+            .map(EmailVerifiedPolicy::from)
+            .unwrap_or_else(|| UidKeyConfig::new(&request.uid_key).email_verified_policy());
+        let input = decode_with_jwk(request, Arc::new(jwk), email_verified_policy)?;
+        if let Err(e) = check_nonce_consistency(&input, &circuit_config) {
+            println!("Warning: {}", e);
+        }
+
+        let public_inputs = compute_public_inputs(&input, &circuit_config)?;
+        let recomputed =
+            poseidon_bn254::hash_scalars(public_inputs.iter().map(|(_, fr)| *fr).collect())?;
+        if recomputed == public_inputs_hash_fr {
+            println!("Public inputs hash: matches the request");
+        } else {
+            println!(
+                "Public inputs hash: DIFFERS from the one recomputed from the request, {}",
+                recomputed
+            );
+            println!("It is the hash of these inputs, recomputed from the request:");
+            for (name, fr) in &public_inputs {
+                println!("  {:<20}{}", name, fr);
+            }
+            failed = true;
+        }
+    }
+
+    if failed {
+        bail!("Verification failed");
+    }
+    Ok(())
+}
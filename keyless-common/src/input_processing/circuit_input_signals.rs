@@ -16,6 +16,18 @@ pub enum CircuitInputSignal {
     Bytes(Vec<u8>),
 }
 
+impl CircuitInputSignal {
+    pub fn kind_name(&self) -> &'static str {
+        match self {
+            CircuitInputSignal::U64(_) => "a u64",
+            CircuitInputSignal::Fr(_) => "a field element",
+            CircuitInputSignal::Frs(_) => "an array of field elements",
+            CircuitInputSignal::Limbs(_) => "an array of limbs",
+            CircuitInputSignal::Bytes(_) => "an array of bytes",
+        }
+    }
+}
+
 pub struct Unpadded;
 
 #[derive(Debug)]
@@ -142,12 +154,14 @@ impl CircuitInputSignals<Unpadded> {
     }
 
     pub fn pad(self, config: &CircuitPaddingConfig) -> Result<CircuitInputSignals<Padded>> {
+        if let Some(schema) = &config.signal_schema {
+            schema.check(&self.signals)?;
+        }
+
         let padded_signals_vec: Result<Vec<(String, CircuitInputSignal)>> = self
             .signals
             .into_iter()
-            .map(|(k, v)| {
-                anyhow::Ok((String::from(&k), pad_if_needed(&k, v, &config.max_lengths)?))
-            })
+            .map(|(k, v)| anyhow::Ok((String::from(&k), pad_if_needed(&k, v, config)?)))
             .collect();
 
         let padded_signals: BTreeMap<String, CircuitInputSignal> =
@@ -165,38 +179,39 @@ impl CircuitInputSignals<Unpadded> {
 fn pad_if_needed(
     k: &str,
     v: CircuitInputSignal,
-    global_input_max_lengths: &BTreeMap<String, usize>,
+    config: &CircuitPaddingConfig,
 ) -> Result<CircuitInputSignal, anyhow::Error> {
     Ok(match v {
         CircuitInputSignal::U64(x) => CircuitInputSignal::U64(x),
         CircuitInputSignal::Fr(x) => CircuitInputSignal::Fr(x),
-        CircuitInputSignal::Frs(x) => CircuitInputSignal::Frs(x),
-        CircuitInputSignal::Limbs(mut x) => {
-            let zeros_needed =
-                global_input_max_lengths.get(k).copied().unwrap_or(x.len()) - x.len();
-            x.extend(vec![0; zeros_needed]);
-            CircuitInputSignal::Limbs(x)
+        CircuitInputSignal::Frs(x) => {
+            let max_length = config.padded_length(k).unwrap_or(x.len());
+            CircuitInputSignal::Frs(pad_vec(k, x, max_length, Fr::from(0u64))?)
+        }
+        CircuitInputSignal::Limbs(x) => {
+            let max_length = config.padded_length(k).unwrap_or(x.len());
+            CircuitInputSignal::Limbs(pad_vec(k, x, max_length, 0)?)
         }
-
         CircuitInputSignal::Bytes(b) => {
-            CircuitInputSignal::Bytes(pad_bytes(&b, global_input_max_lengths[k])?)
+            let max_length = config
+                .padded_length(k)
+                .ok_or_else(|| anyhow!("No max length configured for signal \"{}\"", k))?;
+            CircuitInputSignal::Bytes(pad_vec(k, b, max_length, 0)?)
         }
     })
 }
 
-fn pad_bytes(unpadded_bytes: &[u8], max_size: usize) -> Result<Vec<u8>, anyhow::Error> {
-    let mut bytes = Vec::from(unpadded_bytes);
-
-    println!("size: {}", bytes.len());
-    println!("max size: {}", max_size);
-    println!("{:?}", String::from_utf8_lossy(&bytes));
-
-    if max_size < bytes.len() {
-        Err(anyhow!("max_size exceeded"))
-    } else {
-        bytes.extend([0].repeat(max_size - bytes.len()));
-        Ok(bytes)
+fn pad_vec<T: Clone>(k: &str, mut v: Vec<T>, max_length: usize, zero: T) -> Result<Vec<T>> {
+    if max_length < v.len() {
+        bail!(
+            "Signal \"{}\" has length {}, which exceeds its max length of {}",
+            k,
+            v.len(),
+            max_length
+        );
     }
+    v.resize(max_length, zero);
+    Ok(v)
 }
 
 /// Can only serialize a CircuitInputSignals struct if padding has been added
@@ -235,3 +250,96 @@ fn fr_to_string(fr: &Fr) -> String {
         s
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{CircuitInputSignals, Unpadded};
+    use crate::input_processing::{
+        config::CircuitPaddingConfig,
+        signal_schema::{SignalKind, SignalLength, SignalSchema},
+    };
+    use ark_bn254::Fr;
+
+    fn test_config() -> CircuitPaddingConfig {
+        CircuitPaddingConfig::new()
+            .max_length("b", 4)
+            .with_signal_schema(SignalSchema::new(&[
+                ("a", SignalKind::Scalar),
+                ("b", SignalKind::Bytes(SignalLength::Param("maxB"))),
+                ("c", SignalKind::Frs(SignalLength::Const(3))),
+            ]))
+    }
+
+    fn valid_signals() -> CircuitInputSignals<Unpadded> {
+        CircuitInputSignals::new()
+            .usize_input("a", 1)
+            .str_input("b", "xy")
+            .frs_input("c", &[Fr::from(7u64)])
+    }
+
+    #[test]
+    fn test_pad_with_schema() {
+        let padded = valid_signals().pad(&test_config()).unwrap();
+
+        assert_eq!(
+            padded.to_json_value(),
+            serde_json::json!({
+                "a": "1",
+                "b": ["120", "121", "0", "0"],
+                "c": ["7", "0", "0"],
+            })
+        );
+    }
+
+    #[test]
+    fn test_pad_rejects_missing_signal() {
+        let signals = CircuitInputSignals::new()
+            .usize_input("a", 1)
+            .frs_input("c", &[]);
+
+        let e = signals.pad(&test_config()).unwrap_err();
+        assert_eq!(e.to_string(), "Missing input signals: b");
+    }
+
+    #[test]
+    fn test_pad_rejects_unknown_signal() {
+        let signals = valid_signals().usize_input("d", 1);
+
+        let e = signals.pad(&test_config()).unwrap_err();
+        assert_eq!(e.to_string(), "Unknown input signals: d");
+    }
+
+    #[test]
+    fn test_pad_rejects_wrong_kind() {
+        let signals = valid_signals().usize_input("b", 1);
+
+        let e = signals.pad(&test_config()).unwrap_err();
+        assert!(e
+            .to_string()
+            .starts_with("Input signal \"b\" should be Bytes"));
+    }
+
+    #[test]
+    fn test_pad_rejects_oversized_signals() {
+        let signals = valid_signals().str_input("b", "abcde");
+        let e = signals.pad(&test_config()).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "Signal \"b\" has length 5, which exceeds its max length of 4"
+        );
+
+        let signals = valid_signals().frs_input("c", &[Fr::from(1u64); 4]);
+        let e = signals.pad(&test_config()).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "Signal \"c\" has length 4, which exceeds its max length of 3"
+        );
+    }
+
+    #[test]
+    fn test_pad_without_schema() {
+        let signals = CircuitInputSignals::new().limbs_input("l", &[1, 2]);
+
+        assert!(signals.pad(&CircuitPaddingConfig::new()).is_ok());
+    }
+}
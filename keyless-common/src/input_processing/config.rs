@@ -1,12 +1,17 @@
 // Copyright © Aptos Foundation
 // SPDX-License-Identifier: Apache-2.0
 
+use super::signal_schema::{SignalLength, SignalSchema, KEYLESS_MAIN_TEMPLATE_PARAMS};
+use anyhow::{anyhow, bail, Result};
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct CircuitPaddingConfig {
     pub max_lengths: BTreeMap<String, usize>,
+    /// If set, padding checks the signals against this schema.
+    #[serde(skip)]
+    pub signal_schema: Option<SignalSchema>,
 }
 
 impl CircuitPaddingConfig {
@@ -14,6 +19,7 @@ impl CircuitPaddingConfig {
     pub fn new() -> Self {
         Self {
             max_lengths: BTreeMap::new(),
+            signal_schema: None,
         }
     }
 
@@ -21,4 +27,313 @@ impl CircuitPaddingConfig {
         self.max_lengths.insert(String::from(signal), l);
         self
     }
+
+    pub fn with_signal_schema(mut self, signal_schema: SignalSchema) -> Self {
+        self.signal_schema = Some(signal_schema);
+        self
+    }
+
+    /// The length that the array signal `signal` should be padded to. Lengths fixed by the schema
+    /// take precedence over `max_lengths`.
+    pub fn padded_length(&self, signal: &str) -> Option<usize> {
+        let schema_length = self
+            .signal_schema
+            .as_ref()
+            .and_then(|schema| schema.get(signal))
+            .and_then(|kind| kind.length());
+
+        match schema_length {
+            Some(SignalLength::Const(n)) => Some(n),
+            _ => self.max_lengths.get(signal).copied(),
+        }
+    }
+}
+
+impl CircuitPaddingConfig {
+    /// Derives the config from the instantiation of the `identity` template in a `main.circom`,
+    /// e.g. `circuit/templates/main.circom`. Every array signal of the main template gets a max
+    /// length, and the keyless main signal schema is attached.
+    pub fn from_main_circom(main_circom: &str) -> Result<Self> {
+        let args = main_component_args(main_circom)?;
+        if args.len() != KEYLESS_MAIN_TEMPLATE_PARAMS.len() {
+            bail!(
+                "main component has {} template arguments, expected {}",
+                args.len(),
+                KEYLESS_MAIN_TEMPLATE_PARAMS.len()
+            );
+        }
+
+        let params: BTreeMap<&str, usize> = KEYLESS_MAIN_TEMPLATE_PARAMS
+            .iter()
+            .zip(args.iter())
+            .map(|(param, arg)| Ok((*param, eval_arithmetic(arg)?)))
+            .collect::<Result<_>>()?;
+
+        let schema = SignalSchema::keyless_main();
+        let mut config = Self::new();
+        for (signal, kind) in schema.signals() {
+            match kind.length() {
+                Some(SignalLength::Const(n)) => config = config.max_length(signal, n),
+                Some(SignalLength::Param(param)) => {
+                    config = config.max_length(signal, params[param])
+                }
+                None => {}
+            }
+        }
+
+        Ok(config.with_signal_schema(schema))
+    }
+
+    /// The total number of field elements the schema's input signals take up once padded.
+    pub fn input_signal_count(&self) -> Result<usize> {
+        let schema = self
+            .signal_schema
+            .as_ref()
+            .ok_or_else(|| anyhow!("Circuit config has no signal schema"))?;
+
+        schema
+            .signals()
+            .iter()
+            .map(|(signal, kind)| match kind.length() {
+                None => Ok(1),
+                Some(_) => self
+                    .padded_length(signal)
+                    .ok_or_else(|| anyhow!("No max length configured for signal \"{}\"", signal)),
+            })
+            .sum()
+    }
+
+    /// Fails with the list of differing max lengths if `self` and `other` disagree.
+    pub fn check_matches(&self, other: &CircuitPaddingConfig) -> Result<()> {
+        let mismatches: Vec<String> = self
+            .max_lengths
+            .keys()
+            .chain(other.max_lengths.keys())
+            .filter(|signal| self.max_lengths.get(*signal) != other.max_lengths.get(*signal))
+            .collect::<std::collections::BTreeSet<_>>()
+            .into_iter()
+            .map(|signal| {
+                format!(
+                    "{}: {:?} vs {:?}",
+                    signal,
+                    self.max_lengths.get(signal),
+                    other.max_lengths.get(signal)
+                )
+            })
+            .collect();
+
+        if mismatches.is_empty() {
+            Ok(())
+        } else {
+            bail!(
+                "Circuit configs disagree on max lengths: {}",
+                mismatches.join(", ")
+            )
+        }
+    }
+}
+
+/// Returns the template arguments of `component main ... = identity(...)`, with comments removed.
+fn main_component_args(main_circom: &str) -> Result<Vec<String>> {
+    let without_comments: String = main_circom
+        .lines()
+        .map(|line| line.split("//").next().unwrap_or(""))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let main_start = without_comments
+        .find("component main")
+        .ok_or_else(|| anyhow!("main.circom has no main component"))?;
+    let args_start = without_comments[main_start..]
+        .find("identity(")
+        .map(|i| main_start + i + "identity(".len())
+        .ok_or_else(|| anyhow!("main component is not an instance of identity"))?;
+
+    // Arguments can have parentheses of their own, so split on the commas outside of them.
+    let mut args = vec![];
+    let mut arg = String::new();
+    let mut depth = 0;
+    for c in without_comments[args_start..].chars() {
+        match c {
+            ')' if depth == 0 => {
+                args.push(arg.trim().to_string());
+                return Ok(args);
+            }
+            ',' if depth == 0 => {
+                args.push(arg.trim().to_string());
+                arg.clear();
+                continue;
+            }
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            _ => {}
+        }
+        arg.push(c);
+    }
+    bail!("Unterminated template arguments of main component")
+}
+
+/// Evaluates an integer expression made of numbers, `+`, `-`, `*`, `\\` and parentheses. In
+/// circom, `\\` is integer division, while `/` is division in the field, which is rejected.
+fn eval_arithmetic(expr: &str) -> Result<usize> {
+    let tokens: Vec<char> = expr.chars().filter(|c| !c.is_whitespace()).collect();
+    let mut pos = 0;
+    let result = eval_sum(&tokens, &mut pos)?;
+    if pos != tokens.len() {
+        bail!("Unexpected characters in template argument \"{}\"", expr);
+    }
+    usize::try_from(result).map_err(|_| anyhow!("Negative template argument \"{}\"", expr))
+}
+
+fn eval_sum(tokens: &[char], pos: &mut usize) -> Result<i64> {
+    let mut result = eval_product(tokens, pos)?;
+    while let Some(&op) = tokens.get(*pos) {
+        match op {
+            '+' | '-' => {
+                *pos += 1;
+                let rhs = eval_product(tokens, pos)?;
+                result = if op == '+' {
+                    result + rhs
+                } else {
+                    result - rhs
+                };
+            }
+            _ => break,
+        }
+    }
+    Ok(result)
+}
+
+fn eval_product(tokens: &[char], pos: &mut usize) -> Result<i64> {
+    let mut result = eval_atom(tokens, pos)?;
+    while let Some(&op) = tokens.get(*pos) {
+        match op {
+            '*' => {
+                *pos += 1;
+                result *= eval_atom(tokens, pos)?;
+            }
+            '/' => bail!(
+                "`/` is field division in circom, use `\\` for integer division in template \
+                 arguments"
+            ),
+            '\\' => {
+                *pos += 1;
+                let rhs = eval_atom(tokens, pos)?;
+                if rhs == 0 {
+                    bail!("Division by zero in template argument");
+                }
+                result /= rhs;
+            }
+            _ => break,
+        }
+    }
+    Ok(result)
+}
+
+fn eval_atom(tokens: &[char], pos: &mut usize) -> Result<i64> {
+    match tokens.get(*pos) {
+        Some('(') => {
+            *pos += 1;
+            let result = eval_sum(tokens, pos)?;
+            if tokens.get(*pos) != Some(&')') {
+                bail!("Unbalanced parentheses in template argument");
+            }
+            *pos += 1;
+            Ok(result)
+        }
+        Some(c) if c.is_ascii_digit() => {
+            let start = *pos;
+            while tokens.get(*pos).is_some_and(|c| c.is_ascii_digit()) {
+                *pos += 1;
+            }
+            let digits: String = tokens[start..*pos].iter().collect();
+            Ok(digits.parse()?)
+        }
+        _ => bail!("Expected a number in template argument"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{eval_arithmetic, CircuitPaddingConfig};
+    use std::fs;
+
+    const MAIN_CIRCOM_PATH: &str = "../circuit/templates/main.circom";
+
+    #[test]
+    fn test_eval_arithmetic() {
+        assert_eq!(eval_arithmetic("192*8").unwrap(), 1536);
+        assert_eq!(eval_arithmetic("192*8-64").unwrap(), 1472);
+        assert_eq!(eval_arithmetic("2 * (3 + 4) \\ 3").unwrap(), 4);
+        assert!(eval_arithmetic("maxJWTLen").is_err());
+        assert!(eval_arithmetic("(1").is_err());
+        assert!(eval_arithmetic("1-2").is_err());
+    }
+
+    #[test]
+    fn test_eval_arithmetic_integer_division() {
+        assert_eq!(eval_arithmetic("7 \\ 2").unwrap(), 3);
+        assert_eq!(eval_arithmetic("(192*8-64) \\ 2").unwrap(), 736);
+        assert!(eval_arithmetic("1 \\ 0").is_err());
+    }
+
+    #[test]
+    fn test_eval_arithmetic_rejects_field_division() {
+        let e = eval_arithmetic("(3 + 5) / 2").unwrap_err();
+        assert!(e.to_string().contains("field division"), "{}", e);
+    }
+
+    #[test]
+    fn test_from_main_circom_with_parenthesized_args() {
+        let main_circom = fs::read_to_string(MAIN_CIRCOM_PATH).unwrap().replacen(
+            "192*8-64",
+            "(192*8-64) \\ 2 * 2",
+            1,
+        );
+        let config = CircuitPaddingConfig::from_main_circom(&main_circom).unwrap();
+
+        assert_eq!(
+            config.max_lengths["jwt_payload_without_sha_padding"],
+            192 * 8 - 64
+        );
+        assert_eq!(config.max_lengths["uid_field_string_bodies"], 350);
+    }
+
+    #[test]
+    fn test_from_main_circom() {
+        let config =
+            CircuitPaddingConfig::from_main_circom(&fs::read_to_string(MAIN_CIRCOM_PATH).unwrap())
+                .unwrap();
+
+        assert_eq!(config.max_lengths["jwt"], 192 * 8);
+        assert_eq!(
+            config.max_lengths["jwt_payload_without_sha_padding"],
+            192 * 8 - 64
+        );
+        assert_eq!(config.max_lengths["uid_field_string_bodies"], 350);
+        assert_eq!(config.max_lengths["temp_pubkey"], 3);
+        assert!(config.signal_schema.is_some());
+        assert_eq!(config.input_signal_count().unwrap(), 7859);
+    }
+
+    #[test]
+    fn test_from_main_circom_rejects_wrong_arity() {
+        let main_circom = "component main { public [public_inputs_hash] } = identity(1, 2);";
+        assert!(CircuitPaddingConfig::from_main_circom(main_circom).is_err());
+    }
+
+    #[test]
+    fn test_check_matches() {
+        let a = CircuitPaddingConfig::new().max_length("x", 1);
+        let b = CircuitPaddingConfig::new()
+            .max_length("x", 2)
+            .max_length("y", 3);
+
+        assert!(a.check_matches(&a.clone()).is_ok());
+        let e = a.check_matches(&b).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "Circuit configs disagree on max lengths: x: Some(1) vs Some(2), y: None vs Some(3)"
+        );
+    }
 }
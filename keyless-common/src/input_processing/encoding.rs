@@ -10,6 +10,7 @@ use ark_bn254::Fr;
 use ark_ff::{BigInteger, PrimeField};
 use num_bigint::BigUint;
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
 
 pub type RsaSignature = BigUint;
 
@@ -118,7 +119,53 @@ pub struct JwtPayload {
     pub nonce: String,
     pub sub: Option<String>,
     pub email: Option<String>,
-    pub aud: Option<String>,
+    pub aud: Option<JwtAud>,
+}
+
+/// The `aud` claim, which OIDC allows to be either a single string or an array of strings.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum JwtAud {
+    Single(String),
+    Multiple(Vec<String>),
+}
+
+#[derive(Debug, PartialEq, Eq, Error)]
+pub enum AudSelectionError {
+    #[error("aud_index {index} was given, but the JWT's aud claim is a single string")]
+    IndexIntoSingleAud { index: usize },
+    #[error(
+        "The JWT's aud claim is an array of {len} values, so aud_index must select one of them"
+    )]
+    MissingIndex { len: usize },
+    #[error("aud_index {index} is out of bounds for the JWT's aud claim, which has {len} values")]
+    IndexOutOfBounds { index: usize, len: usize },
+    #[error("The JWT's aud claim is an array, which the keyless circuit does not support yet")]
+    ArrayNotSupportedByCircuit,
+}
+
+impl JwtAud {
+    /// Returns the aud value to prove against. `aud_index` must be set if and only if the claim
+    /// is an array.
+    pub fn select(&self, aud_index: Option<usize>) -> Result<&str, AudSelectionError> {
+        match (self, aud_index) {
+            (JwtAud::Single(aud), None) => Ok(aud),
+            (JwtAud::Single(_), Some(index)) => {
+                Err(AudSelectionError::IndexIntoSingleAud { index })
+            }
+            (JwtAud::Multiple(auds), None) => {
+                Err(AudSelectionError::MissingIndex { len: auds.len() })
+            }
+            (JwtAud::Multiple(auds), Some(index)) => {
+                auds.get(index)
+                    .map(String::as_str)
+                    .ok_or(AudSelectionError::IndexOutOfBounds {
+                        index,
+                        len: auds.len(),
+                    })
+            }
+        }
+    }
 }
 
 impl FromB64 for JwtParts {
@@ -220,3 +267,43 @@ impl As64BitLimbs for RsaSignature {
         self.to_u64_digits()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{AudSelectionError, JwtAud, JwtPayload};
+
+    #[test]
+    fn test_aud_single_and_array_deserialize() {
+        let single: JwtPayload =
+            serde_json::from_str(r#"{"iss":"i","iat":0,"nonce":"n","aud":"a"}"#).unwrap();
+        assert_eq!(single.aud, Some(JwtAud::Single(String::from("a"))));
+
+        let multiple: JwtPayload =
+            serde_json::from_str(r#"{"iss":"i","iat":0,"nonce":"n","aud":["a","b"]}"#).unwrap();
+        assert_eq!(
+            multiple.aud,
+            Some(JwtAud::Multiple(vec![String::from("a"), String::from("b")]))
+        );
+    }
+
+    #[test]
+    fn test_aud_select() {
+        let single = JwtAud::Single(String::from("a"));
+        assert_eq!(single.select(None), Ok("a"));
+        assert_eq!(
+            single.select(Some(0)),
+            Err(AudSelectionError::IndexIntoSingleAud { index: 0 })
+        );
+
+        let multiple = JwtAud::Multiple(vec![String::from("a"), String::from("b")]);
+        assert_eq!(multiple.select(Some(1)), Ok("b"));
+        assert_eq!(
+            multiple.select(None),
+            Err(AudSelectionError::MissingIndex { len: 2 })
+        );
+        assert_eq!(
+            multiple.select(Some(2)),
+            Err(AudSelectionError::IndexOutOfBounds { index: 2, len: 2 })
+        );
+    }
+}
@@ -6,4 +6,6 @@ pub mod circuit_input_signals;
 pub mod config;
 pub mod encoding;
 pub mod sha;
+pub mod signal_schema;
+pub mod witness_calculator;
 pub mod witness_gen;
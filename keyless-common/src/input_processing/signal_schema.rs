@@ -0,0 +1,322 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use super::circuit_input_signals::CircuitInputSignal;
+use anyhow::{bail, Result};
+use std::collections::BTreeMap;
+
+/// The parameters of the `identity` template in `circuit/templates/mainTemplate.circom`, in order.
+pub const KEYLESS_MAIN_TEMPLATE_PARAMS: [&str; 22] = [
+    "maxJWTLen",
+    "maxJWTHeaderLen",
+    "maxJWTPayloadLen",
+    "maxAudKVPairLen",
+    "maxAudNameLen",
+    "maxAudValueLen",
+    "maxIssKVPairLen",
+    "maxIssNameLen",
+    "maxIssValueLen",
+    "maxIatKVPairLen",
+    "maxIatNameLen",
+    "maxIatValueLen",
+    "maxNonceKVPairLen",
+    "maxNonceNameLen",
+    "maxNonceValueLen",
+    "maxEVKVPairLen",
+    "maxEVNameLen",
+    "maxEVValueLen",
+    "maxUIDKVPairLen",
+    "maxUIDNameLen",
+    "maxUIDValueLen",
+    "maxEFKVPairLen",
+];
+
+/// The public input signals of the `identity` template.
+pub const KEYLESS_MAIN_PUBLIC_SIGNALS: [&str; 1] = ["public_inputs_hash"];
+
+/// The length of an array signal: either fixed in the template, or given by one of the template's
+/// parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignalLength {
+    Const(usize),
+    Param(&'static str),
+}
+
+/// What kind of value an input signal holds, and for arrays, how long it is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignalKind {
+    /// A single field element, e.g. set with `usize_input`, `u64_input` or `fr_input`.
+    Scalar,
+    /// An array of bytes or bits.
+    Bytes(SignalLength),
+    /// An array of 64-bit limbs.
+    Limbs(SignalLength),
+    /// An array of field elements.
+    Frs(SignalLength),
+}
+
+impl SignalKind {
+    pub fn length(&self) -> Option<SignalLength> {
+        match self {
+            SignalKind::Scalar => None,
+            SignalKind::Bytes(l) | SignalKind::Limbs(l) | SignalKind::Frs(l) => Some(*l),
+        }
+    }
+
+    pub fn matches(&self, signal: &CircuitInputSignal) -> bool {
+        matches!(
+            (self, signal),
+            (
+                SignalKind::Scalar,
+                CircuitInputSignal::U64(_) | CircuitInputSignal::Fr(_)
+            ) | (SignalKind::Bytes(_), CircuitInputSignal::Bytes(_))
+                | (SignalKind::Limbs(_), CircuitInputSignal::Limbs(_))
+                | (SignalKind::Frs(_), CircuitInputSignal::Frs(_))
+        )
+    }
+}
+
+/// The input signals a circuit template declares. If a `CircuitPaddingConfig` carries one,
+/// `CircuitInputSignals::pad` rejects signals that are missing, unknown or of the wrong kind,
+/// instead of leaving that to the witness generator.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignalSchema {
+    signals: BTreeMap<&'static str, SignalKind>,
+}
+
+impl SignalSchema {
+    pub fn new(signals: &[(&'static str, SignalKind)]) -> Self {
+        Self {
+            signals: signals.iter().copied().collect(),
+        }
+    }
+
+    pub fn signals(&self) -> &BTreeMap<&'static str, SignalKind> {
+        &self.signals
+    }
+
+    pub fn get(&self, signal_name: &str) -> Option<SignalKind> {
+        self.signals.get(signal_name).copied()
+    }
+
+    /// Checks that `signals` has exactly the signals of this schema, each of the right kind.
+    /// Lengths are checked while padding.
+    pub fn check(&self, signals: &BTreeMap<String, CircuitInputSignal>) -> Result<()> {
+        let missing: Vec<&str> = self
+            .signals
+            .keys()
+            .filter(|name| !signals.contains_key(**name))
+            .copied()
+            .collect();
+        if !missing.is_empty() {
+            bail!("Missing input signals: {}", missing.join(", "));
+        }
+
+        let unknown: Vec<&str> = signals
+            .keys()
+            .filter(|name| !self.signals.contains_key(name.as_str()))
+            .map(String::as_str)
+            .collect();
+        if !unknown.is_empty() {
+            bail!("Unknown input signals: {}", unknown.join(", "));
+        }
+
+        for (name, signal) in signals {
+            let kind = self.signals[name.as_str()];
+            if !kind.matches(signal) {
+                bail!(
+                    "Input signal \"{}\" should be {:?}, but is {}",
+                    name,
+                    kind,
+                    signal.kind_name()
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The input signals of the `identity` template in `circuit/templates/mainTemplate.circom`.
+    pub fn keyless_main() -> Self {
+        use SignalKind::*;
+        use SignalLength::*;
+
+        Self::new(&[
+            ("jwt", Bytes(Param("maxJWTLen"))),
+            ("jwt_header_with_separator", Bytes(Param("maxJWTHeaderLen"))),
+            ("jwt_payload", Bytes(Param("maxJWTPayloadLen"))),
+            ("header_len_with_separator", Scalar),
+            ("b64_payload_len", Scalar),
+            ("jwt_num_sha2_blocks", Scalar),
+            ("jwt_len_bit_encoded", Bytes(Const(8))),
+            ("padding_without_len", Bytes(Const(64))),
+            ("signature", Limbs(Const(32))),
+            ("pubkey_modulus", Limbs(Const(32))),
+            (
+                "jwt_payload_without_sha_padding",
+                Bytes(Param("maxJWTPayloadLen")),
+            ),
+            // aud
+            ("aud_field", Bytes(Param("maxAudKVPairLen"))),
+            ("aud_field_string_bodies", Bytes(Param("maxAudKVPairLen"))),
+            ("aud_field_len", Scalar),
+            ("aud_index", Scalar),
+            ("aud_value_index", Scalar),
+            ("aud_colon_index", Scalar),
+            ("aud_name", Bytes(Param("maxAudNameLen"))),
+            ("use_aud_override", Scalar),
+            ("private_aud_value", Bytes(Param("maxAudValueLen"))),
+            ("override_aud_value", Bytes(Param("maxAudValueLen"))),
+            ("private_aud_value_len", Scalar),
+            ("override_aud_value_len", Scalar),
+            // uid
+            ("uid_field", Bytes(Param("maxUIDKVPairLen"))),
+            ("uid_field_string_bodies", Bytes(Param("maxUIDKVPairLen"))),
+            ("uid_field_len", Scalar),
+            ("uid_index", Scalar),
+            ("uid_name_len", Scalar),
+            ("uid_value_index", Scalar),
+            ("uid_value_len", Scalar),
+            ("uid_colon_index", Scalar),
+            ("uid_name", Bytes(Param("maxUIDNameLen"))),
+            ("uid_value", Bytes(Param("maxUIDValueLen"))),
+            // extra field
+            ("extra_field", Bytes(Param("maxEFKVPairLen"))),
+            ("extra_field_len", Scalar),
+            ("extra_index", Scalar),
+            ("use_extra_field", Scalar),
+            // email_verified
+            ("ev_field", Bytes(Param("maxEVKVPairLen"))),
+            ("ev_field_len", Scalar),
+            ("ev_index", Scalar),
+            ("ev_value_index", Scalar),
+            ("ev_value_len", Scalar),
+            ("ev_colon_index", Scalar),
+            ("ev_name", Bytes(Param("maxEVNameLen"))),
+            ("ev_value", Bytes(Param("maxEVValueLen"))),
+            // iss
+            ("iss_field", Bytes(Param("maxIssKVPairLen"))),
+            ("iss_field_string_bodies", Bytes(Param("maxIssKVPairLen"))),
+            ("iss_field_len", Scalar),
+            ("iss_index", Scalar),
+            ("iss_value_index", Scalar),
+            ("iss_value_len", Scalar),
+            ("iss_colon_index", Scalar),
+            ("iss_name", Bytes(Param("maxIssNameLen"))),
+            ("iss_value", Bytes(Param("maxIssValueLen"))),
+            // iat
+            ("iat_field", Bytes(Param("maxIatKVPairLen"))),
+            ("iat_field_len", Scalar),
+            ("iat_index", Scalar),
+            ("iat_value_index", Scalar),
+            ("iat_value_len", Scalar),
+            ("iat_colon_index", Scalar),
+            ("iat_name", Bytes(Param("maxIatNameLen"))),
+            ("iat_value", Bytes(Param("maxIatValueLen"))),
+            ("exp_date", Scalar),
+            ("exp_delta", Scalar),
+            // nonce
+            ("nonce_field", Bytes(Param("maxNonceKVPairLen"))),
+            (
+                "nonce_field_string_bodies",
+                Bytes(Param("maxNonceKVPairLen")),
+            ),
+            ("nonce_field_len", Scalar),
+            ("nonce_index", Scalar),
+            ("nonce_value_index", Scalar),
+            ("nonce_value_len", Scalar),
+            ("nonce_colon_index", Scalar),
+            ("nonce_name", Bytes(Param("maxNonceNameLen"))),
+            ("nonce_value", Bytes(Param("maxNonceValueLen"))),
+            ("temp_pubkey", Frs(Const(3))),
+            ("temp_pubkey_len", Scalar),
+            ("jwt_randomness", Scalar),
+            ("pepper", Scalar),
+            ("public_inputs_hash", Scalar),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SignalLength, SignalSchema, KEYLESS_MAIN_TEMPLATE_PARAMS};
+    use std::{collections::BTreeMap, fs};
+
+    const MAIN_TEMPLATE_PATH: &str = "../circuit/templates/mainTemplate.circom";
+
+    /// Extracts the input signals of the `identity` template along with their array lengths, which
+    /// are either numbers or template parameter names. Lengths given by a `var` in the template
+    /// body are resolved to its value.
+    fn parse_template_input_signals(template: &str) -> BTreeMap<String, Option<String>> {
+        let body = &template[template.find("template identity(").unwrap()..];
+        let mut vars = BTreeMap::new();
+        let mut signals = BTreeMap::new();
+
+        for line in body.lines().map(str::trim) {
+            if let Some(var) = line.strip_prefix("var ") {
+                if let Some((name, value)) = var.split_once('=') {
+                    let value = value.split(';').next().unwrap().trim();
+                    if value.parse::<usize>().is_ok() {
+                        vars.insert(name.trim().to_string(), value.to_string());
+                    }
+                }
+            } else if let Some(decl) = line.strip_prefix("signal input ") {
+                let decl = decl.split(';').next().unwrap().trim();
+                match decl.split_once('[') {
+                    Some((name, dim)) => {
+                        let dim = dim.trim_end_matches(']');
+                        let length = vars.get(dim).cloned().unwrap_or(dim.to_string());
+                        signals.insert(name.to_string(), Some(length));
+                    }
+                    None => {
+                        signals.insert(decl.to_string(), None);
+                    }
+                }
+            }
+        }
+
+        signals
+    }
+
+    #[test]
+    fn test_keyless_main_schema_matches_template() {
+        let template = fs::read_to_string(MAIN_TEMPLATE_PATH).unwrap();
+        let template_signals = parse_template_input_signals(&template);
+
+        let schema_signals: BTreeMap<String, Option<String>> = SignalSchema::keyless_main()
+            .signals()
+            .iter()
+            .map(|(name, kind)| {
+                let length = kind.length().map(|length| match length {
+                    SignalLength::Const(n) => n.to_string(),
+                    SignalLength::Param(param) => param.to_string(),
+                });
+                (name.to_string(), length)
+            })
+            .collect();
+
+        assert_eq!(schema_signals, template_signals);
+    }
+
+    #[test]
+    fn test_keyless_main_template_params() {
+        let template = fs::read_to_string(MAIN_TEMPLATE_PATH).unwrap();
+        let params_start =
+            template.find("template identity(").unwrap() + "template identity(".len();
+        let params_end = params_start + template[params_start..].find(')').unwrap();
+
+        let params: Vec<&str> = template[params_start..params_end]
+            .lines()
+            .map(|line| {
+                line.split("//")
+                    .next()
+                    .unwrap()
+                    .trim()
+                    .trim_end_matches(',')
+            })
+            .filter(|param| !param.is_empty())
+            .collect();
+
+        assert_eq!(params, KEYLESS_MAIN_TEMPLATE_PARAMS);
+    }
+}
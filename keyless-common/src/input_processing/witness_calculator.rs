@@ -0,0 +1,727 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::{anyhow, bail, Context, Result};
+use num_bigint::BigUint;
+use serde_json::Value;
+use std::{
+    fs,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+use tracing::debug;
+use wasmtime::{
+    Caller, Config, Engine, Extern, Linker, Memory, Module, ResourceLimiter, Store, TypedFunc,
+    UpdateDeadline,
+};
+
+/// How often a running calculation checks its timeout and whether it was cancelled.
+const EPOCH_TICK: Duration = Duration::from_millis(10);
+
+/// Bounds on a single witness calculation.
+#[derive(Debug, Clone, Default)]
+pub struct CalculationLimits {
+    pub timeout: Option<Duration>,
+    /// Bounds the circuit's linear memory.
+    pub max_memory_bytes: Option<usize>,
+    /// The calculation stops once this is set.
+    pub cancelled: Arc<AtomicBool>,
+}
+
+/// Why a calculation was stopped before it finished. Errors from `calculate_wtns_with_limits`
+/// can be downcast to this.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum Interruption {
+    #[error("Witness calculation timed out")]
+    Timeout,
+    #[error("Witness calculation was cancelled")]
+    Cancelled,
+    #[error("Witness calculation exceeded its memory limit")]
+    MemoryLimit,
+}
+
+/// Runs the wasm witness calculator that `circom --wasm` generates in-process, instead of
+/// going through `node generate_witness.js`. This mirrors what `witness_calculator.js` does.
+#[derive(Clone)]
+pub struct WitnessCalculator {
+    engine: Engine,
+    module: Module,
+}
+
+impl WitnessCalculator {
+    pub fn from_file(wasm_path: &str) -> Result<Self> {
+        let wasm = fs::read(wasm_path).with_context(|| format!("Couldn't read {}", wasm_path))?;
+        Self::from_bytes(&wasm).with_context(|| format!("Invalid witness calculator {}", wasm_path))
+    }
+
+    pub fn from_bytes(wasm: &[u8]) -> Result<Self> {
+        let mut config = Config::new();
+        config.epoch_interruption(true);
+        let engine = Engine::new(&config)?;
+        let module = Module::new(&engine, wasm)?;
+        Ok(Self { engine, module })
+    }
+
+    /// Computes the witness for `input`, which has the format of the `input.json` that
+    /// `generate_witness.js` reads, and returns it serialized as a `.wtns` file.
+    pub fn calculate_wtns(&self, input: &Value) -> Result<Vec<u8>> {
+        self.calculate_wtns_with_limits(input, &CalculationLimits::default())
+    }
+
+    /// Like `calculate_wtns`, but stops with an `Interruption` error once a limit is hit.
+    pub fn calculate_wtns_with_limits(
+        &self,
+        input: &Value,
+        limits: &CalculationLimits,
+    ) -> Result<Vec<u8>> {
+        let done = AtomicBool::new(false);
+
+        thread::scope(|scope| {
+            // Running wasm code checks its deadline whenever the engine's epoch advances.
+            scope.spawn(|| {
+                while !done.load(Ordering::Relaxed) {
+                    thread::sleep(EPOCH_TICK);
+                    self.engine.increment_epoch();
+                }
+            });
+
+            // A fresh instance per witness, so that concurrent requests don't share circuit state.
+            let result = CircuitInstance::new(&self.engine, &self.module, limits).and_then(
+                |mut instance| {
+                    instance.set_inputs(input)?;
+                    instance.wtns()
+                },
+            );
+            done.store(true, Ordering::Relaxed);
+            result
+        })
+    }
+
+    /// The number of field elements the circuit's input signals take up, public and private.
+    pub fn input_size(&self) -> Result<usize> {
+        let mut instance =
+            CircuitInstance::new(&self.engine, &self.module, &CalculationLimits::default())?;
+        Ok(instance.get_input_size.call(&mut instance.store, ())? as usize)
+    }
+}
+
+#[derive(Default)]
+struct RuntimeState {
+    /// Messages from failed circuit asserts, reported along with the exception.
+    errors: String,
+    /// Output of `log()` calls in the circuit, up to the next newline.
+    log_line: String,
+    max_memory_bytes: Option<usize>,
+}
+
+impl ResourceLimiter for RuntimeState {
+    fn memory_growing(
+        &mut self,
+        _current: usize,
+        desired: usize,
+        _maximum: Option<usize>,
+    ) -> Result<bool> {
+        match self.max_memory_bytes {
+            Some(max_memory_bytes) if desired > max_memory_bytes => {
+                Err(Interruption::MemoryLimit.into())
+            }
+            _ => Ok(true),
+        }
+    }
+
+    fn table_growing(
+        &mut self,
+        _current: u32,
+        _desired: u32,
+        _maximum: Option<u32>,
+    ) -> Result<bool> {
+        Ok(true)
+    }
+}
+
+struct CircuitInstance {
+    store: Store<RuntimeState>,
+    init: TypedFunc<i32, ()>,
+    get_input_size: TypedFunc<(), i32>,
+    get_input_signal_size: TypedFunc<(i32, i32), i32>,
+    set_input_signal: TypedFunc<(i32, i32, i32), ()>,
+    get_witness_size: TypedFunc<(), i32>,
+    get_witness: TypedFunc<i32, ()>,
+    read_shared_rw_memory: TypedFunc<i32, i32>,
+    write_shared_rw_memory: TypedFunc<(i32, i32), ()>,
+    n32: usize,
+    prime: BigUint,
+}
+
+impl CircuitInstance {
+    fn new(engine: &Engine, module: &Module, limits: &CalculationLimits) -> Result<Self> {
+        let mut store = Store::new(
+            engine,
+            RuntimeState {
+                max_memory_bytes: limits.max_memory_bytes,
+                ..RuntimeState::default()
+            },
+        );
+        store.limiter(|state| state);
+
+        let deadline = limits.timeout.map(|timeout| Instant::now() + timeout);
+        let cancelled = limits.cancelled.clone();
+        store.set_epoch_deadline(1);
+        store.epoch_deadline_callback(move |_| {
+            if cancelled.load(Ordering::Relaxed) {
+                Err(Interruption::Cancelled.into())
+            } else if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                Err(Interruption::Timeout.into())
+            } else {
+                Ok(UpdateDeadline::Continue(1))
+            }
+        });
+
+        let mut linker = Linker::new(engine);
+
+        for import in module.imports() {
+            if let Some(memory_type) = import.ty().memory() {
+                let memory = Memory::new(&mut store, memory_type.clone())?;
+                linker.define(&store, import.module(), import.name(), memory)?;
+            }
+        }
+        linker.func_wrap(
+            "runtime",
+            "exceptionHandler",
+            |mut caller: Caller<'_, RuntimeState>, code: i32| -> Result<()> {
+                let errors = std::mem::take(&mut caller.data_mut().errors);
+                bail!("{}\n{}", exception_message(code), errors)
+            },
+        )?;
+        linker.func_wrap(
+            "runtime",
+            "printErrorMessage",
+            |mut caller: Caller<'_, RuntimeState>| -> Result<()> {
+                let message = read_message(&mut caller)?;
+                let errors = &mut caller.data_mut().errors;
+                errors.push_str(&message);
+                errors.push('\n');
+                Ok(())
+            },
+        )?;
+        linker.func_wrap(
+            "runtime",
+            "writeBufferMessage",
+            |mut caller: Caller<'_, RuntimeState>| -> Result<()> {
+                let message = read_message(&mut caller)?;
+                // Every `log()` call ends with a newline, so that's when the line is complete.
+                if message == "\n" {
+                    let log_line = std::mem::take(&mut caller.data_mut().log_line);
+                    debug!("circuit log: {}", log_line);
+                } else {
+                    push_log_item(&mut caller.data_mut().log_line, &message);
+                }
+                Ok(())
+            },
+        )?;
+        linker.func_wrap(
+            "runtime",
+            "showSharedRWMemory",
+            |mut caller: Caller<'_, RuntimeState>| -> Result<()> {
+                let n32 = typed_export::<(), i32>(&mut caller, "getFieldNumLen32")?
+                    .call(&mut caller, ())? as usize;
+                let read_shared_rw_memory =
+                    typed_export::<i32, i32>(&mut caller, "readSharedRWMemory")?;
+                let limbs = (0..n32)
+                    .map(|j| read_shared_rw_memory.call(&mut caller, j as i32))
+                    .collect::<Result<Vec<i32>>>()?;
+                let value =
+                    BigUint::from_slice(&limbs.iter().map(|l| *l as u32).collect::<Vec<_>>());
+                push_log_item(&mut caller.data_mut().log_line, &value.to_string());
+                Ok(())
+            },
+        )?;
+
+        let instance = linker.instantiate(&mut store, module)?;
+
+        let version = instance
+            .get_typed_func::<(), i32>(&mut store, "getVersion")?
+            .call(&mut store, ())?;
+        if version < 2 {
+            bail!(
+                "Witness calculators from circom {} are not supported",
+                version
+            );
+        }
+
+        let n32 = instance
+            .get_typed_func::<(), i32>(&mut store, "getFieldNumLen32")?
+            .call(&mut store, ())? as usize;
+        let read_shared_rw_memory = instance.get_typed_func(&mut store, "readSharedRWMemory")?;
+        instance
+            .get_typed_func::<(), ()>(&mut store, "getRawPrime")?
+            .call(&mut store, ())?;
+        let prime = read_field_element(&mut store, &read_shared_rw_memory, n32)?;
+
+        Ok(Self {
+            init: instance.get_typed_func(&mut store, "init")?,
+            get_input_size: instance.get_typed_func(&mut store, "getInputSize")?,
+            get_input_signal_size: instance.get_typed_func(&mut store, "getInputSignalSize")?,
+            set_input_signal: instance.get_typed_func(&mut store, "setInputSignal")?,
+            get_witness_size: instance.get_typed_func(&mut store, "getWitnessSize")?,
+            get_witness: instance.get_typed_func(&mut store, "getWitness")?,
+            write_shared_rw_memory: instance.get_typed_func(&mut store, "writeSharedRWMemory")?,
+            read_shared_rw_memory,
+            store,
+            n32,
+            prime,
+        })
+    }
+
+    /// Sets every input signal. The witness is computed once the last one is set.
+    fn set_inputs(&mut self, input: &Value) -> Result<()> {
+        let signals = input
+            .as_object()
+            .ok_or_else(|| anyhow!("Witness calculator input should be a JSON object"))?;
+
+        // Same as `generate_witness.js`, which doesn't enable the sanity checks.
+        self.init.call(&mut self.store, 0)?;
+
+        let mut input_counter = 0;
+        for (signal_name, signal_value) in signals {
+            let (h_msb, h_lsb) = fnv_hash(signal_name);
+            let signal_size = self
+                .get_input_signal_size
+                .call(&mut self.store, (h_msb, h_lsb))?;
+            if signal_size < 0 {
+                bail!("Signal \"{}\" not found", signal_name);
+            }
+
+            let mut values = vec![];
+            flatten_signal_value(signal_value, &mut values)
+                .with_context(|| format!("Invalid value for signal \"{}\"", signal_name))?;
+            if values.len() != signal_size as usize {
+                bail!(
+                    "Signal \"{}\" has {} values, but the circuit expects {}",
+                    signal_name,
+                    values.len(),
+                    signal_size
+                );
+            }
+
+            for (i, value) in values.iter().enumerate() {
+                self.write_field_element(&value.reduce(&self.prime))?;
+                self.set_input_signal
+                    .call(&mut self.store, (h_msb, h_lsb, i as i32))
+                    .with_context(|| format!("Couldn't set signal \"{}[{}]\"", signal_name, i))?;
+                input_counter += 1;
+            }
+        }
+
+        let input_size = self.get_input_size.call(&mut self.store, ())? as usize;
+        if input_counter < input_size {
+            bail!(
+                "Not all inputs have been set. Only {} out of {}",
+                input_counter,
+                input_size
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Serializes the witness in the `.wtns` format that snarkjs and rapidsnark read.
+    fn wtns(&mut self) -> Result<Vec<u8>> {
+        let witness_size = self.get_witness_size.call(&mut self.store, ())? as usize;
+        let n8 = self.n32 * 4;
+
+        let mut wtns = Vec::with_capacity(12 + 12 + 8 + n8 + 12 + n8 * witness_size);
+        wtns.extend_from_slice(b"wtns");
+        wtns.extend_from_slice(&2u32.to_le_bytes()); // version
+        wtns.extend_from_slice(&2u32.to_le_bytes()); // number of sections
+
+        wtns.extend_from_slice(&1u32.to_le_bytes());
+        wtns.extend_from_slice(&((8 + n8) as u64).to_le_bytes());
+        wtns.extend_from_slice(&(n8 as u32).to_le_bytes());
+        for limb in field_element_limbs(&self.prime, self.n32) {
+            wtns.extend_from_slice(&limb.to_le_bytes());
+        }
+        wtns.extend_from_slice(&(witness_size as u32).to_le_bytes());
+
+        wtns.extend_from_slice(&2u32.to_le_bytes());
+        wtns.extend_from_slice(&((n8 * witness_size) as u64).to_le_bytes());
+        for i in 0..witness_size {
+            self.get_witness.call(&mut self.store, i as i32)?;
+            for j in 0..self.n32 {
+                let limb = self.read_shared_rw_memory.call(&mut self.store, j as i32)?;
+                wtns.extend_from_slice(&limb.to_le_bytes());
+            }
+        }
+
+        Ok(wtns)
+    }
+
+    fn write_field_element(&mut self, value: &BigUint) -> Result<()> {
+        for (j, limb) in field_element_limbs(value, self.n32).enumerate() {
+            self.write_shared_rw_memory
+                .call(&mut self.store, (j as i32, limb as i32))?;
+        }
+        Ok(())
+    }
+}
+
+/// A signal value from the input JSON, before it is reduced modulo the circuit's prime.
+enum SignalValue {
+    NonNegative(BigUint),
+    Negative(BigUint),
+}
+
+impl SignalValue {
+    fn reduce(&self, prime: &BigUint) -> BigUint {
+        match self {
+            SignalValue::NonNegative(x) => x % prime,
+            SignalValue::Negative(x) => (prime - x % prime) % prime,
+        }
+    }
+}
+
+/// Like `flatArray` in `witness_calculator.js`: multi-dimensional signals are given as nested
+/// arrays, and their values are set in row-major order.
+fn flatten_signal_value(value: &Value, values: &mut Vec<SignalValue>) -> Result<()> {
+    match value {
+        Value::Array(elements) => {
+            for element in elements {
+                flatten_signal_value(element, values)?;
+            }
+        }
+        Value::String(s) => values.push(parse_signal_value(s)?),
+        Value::Number(n) => values.push(parse_signal_value(&n.to_string())?),
+        Value::Bool(b) => values.push(SignalValue::NonNegative(BigUint::from(*b as u8))),
+        Value::Null | Value::Object(_) => bail!("Expected a number, got {}", value),
+    }
+    Ok(())
+}
+
+fn parse_signal_value(s: &str) -> Result<SignalValue> {
+    let parse_magnitude = |s: &str| match s.strip_prefix("0x") {
+        Some(hex) => BigUint::parse_bytes(hex.as_bytes(), 16),
+        None => BigUint::from_str(s).ok(),
+    };
+
+    match s.strip_prefix('-') {
+        Some(magnitude) => parse_magnitude(magnitude).map(SignalValue::Negative),
+        None => parse_magnitude(s).map(SignalValue::NonNegative),
+    }
+    .ok_or_else(|| anyhow!("\"{}\" is not an integer", s))
+}
+
+/// The 64-bit FNV-1a hash of a signal name, split into its most and least significant halves,
+/// which is how the circuit's wasm identifies input signals.
+fn fnv_hash(signal_name: &str) -> (i32, i32) {
+    let mut hash: u64 = 0xCBF29CE484222325;
+    // `witness_calculator.js` hashes UTF-16 code units.
+    for c in signal_name.encode_utf16() {
+        hash ^= c as u64;
+        hash = hash.wrapping_mul(0x100000001B3);
+    }
+    ((hash >> 32) as i32, hash as i32)
+}
+
+/// The little-endian 32-bit limbs of `value`, padded to `n32` limbs.
+fn field_element_limbs(value: &BigUint, n32: usize) -> impl Iterator<Item = u32> {
+    let mut limbs = value.to_u32_digits();
+    limbs.resize(n32, 0);
+    limbs.into_iter()
+}
+
+fn read_field_element(
+    store: &mut Store<RuntimeState>,
+    read_shared_rw_memory: &TypedFunc<i32, i32>,
+    n32: usize,
+) -> Result<BigUint> {
+    let limbs = (0..n32)
+        .map(|j| Ok(read_shared_rw_memory.call(&mut *store, j as i32)? as u32))
+        .collect::<Result<Vec<u32>>>()?;
+    Ok(BigUint::from_slice(&limbs))
+}
+
+fn typed_export<Params, Results>(
+    caller: &mut Caller<'_, RuntimeState>,
+    name: &str,
+) -> Result<TypedFunc<Params, Results>>
+where
+    Params: wasmtime::WasmParams,
+    Results: wasmtime::WasmResults,
+{
+    caller
+        .get_export(name)
+        .and_then(Extern::into_func)
+        .ok_or_else(|| anyhow!("Witness calculator doesn't export {}", name))?
+        .typed(&*caller)
+}
+
+/// Reads the message the circuit's wasm has prepared, one character at a time.
+fn read_message(caller: &mut Caller<'_, RuntimeState>) -> Result<String> {
+    let get_message_char = typed_export::<(), i32>(caller, "getMessageChar")?;
+    let mut message = String::new();
+    loop {
+        let c = get_message_char.call(&mut *caller, ())?;
+        if c == 0 {
+            break;
+        }
+        message.push(char::from(c as u8));
+    }
+    Ok(message)
+}
+
+fn push_log_item(log_line: &mut String, item: &str) {
+    if !log_line.is_empty() {
+        log_line.push(' ');
+    }
+    log_line.push_str(item);
+}
+
+fn exception_message(code: i32) -> &'static str {
+    match code {
+        1 => "Signal not found.",
+        2 => "Too many signals set.",
+        3 => "Signal already set.",
+        4 => "Assert Failed.",
+        5 => "Not enough memory.",
+        6 => "Input signal array access exceeds the size.",
+        _ => "Unknown error.",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fnv_hash, CalculationLimits, Interruption, WitnessCalculator};
+    use serde_json::json;
+    use std::{
+        sync::{atomic::AtomicBool, Arc},
+        time::{Duration, Instant},
+    };
+
+    /// A stand-in for a circom-generated witness calculator, for the circuit
+    /// `signal input in[2]; assert(in[1] != 0);`, whose witness is `[1, in[0], in[1]]`, except
+    /// that it never finishes if `in[0]` is 42. The shared RW memory is at address 0 and the
+    /// witness at address 64.
+    const TEST_CIRCUIT_WAT: &str = r#"
+        (module
+          (import "env" "memory" (memory 1))
+          (import "runtime" "exceptionHandler" (func $exception_handler (param i32)))
+          (import "runtime" "printErrorMessage" (func $print_error_message))
+          (import "runtime" "writeBufferMessage" (func $write_buffer_message))
+          (import "runtime" "showSharedRWMemory" (func $show_shared_rw_memory))
+          (data (i32.const 1024) "in[1] must not be 0\00")
+          (global $message_pos (mut i32) (i32.const 0))
+          (func (export "getVersion") (result i32) (i32.const 2))
+          (func (export "getFieldNumLen32") (result i32) (i32.const 8))
+          (func (export "getInputSize") (result i32) (i32.const 2))
+          (func (export "getWitnessSize") (result i32) (i32.const 3))
+          (func (export "readSharedRWMemory") (param $j i32) (result i32)
+            (i32.load (i32.shl (local.get $j) (i32.const 2))))
+          (func (export "writeSharedRWMemory") (param $j i32) (param $v i32)
+            (i32.store (i32.shl (local.get $j) (i32.const 2)) (local.get $v)))
+          (func (export "getRawPrime")
+            (i32.store (i32.const 0) (i32.const -268435455))
+            (i32.store (i32.const 4) (i32.const 1138881939))
+            (i32.store (i32.const 8) (i32.const 2042196113))
+            (i32.store (i32.const 12) (i32.const 674490440))
+            (i32.store (i32.const 16) (i32.const -2122229667))
+            (i32.store (i32.const 20) (i32.const -1202698826))
+            (i32.store (i32.const 24) (i32.const -516841431))
+            (i32.store (i32.const 28) (i32.const 811880050)))
+          (func (export "init") (param $sanity_check i32)
+            (memory.fill (i32.const 64) (i32.const 0) (i32.const 96))
+            (i32.store (i32.const 64) (i32.const 1)))
+          (func (export "getInputSignalSize") (param $h_msb i32) (param $h_lsb i32) (result i32)
+            (if (result i32)
+              (i32.and
+                (i32.eq (local.get $h_msb) (i32.const 146225159))
+                (i32.eq (local.get $h_lsb) (i32.const -1252242498)))
+              (then (i32.const 2))
+              (else (i32.const -1))))
+          (func (export "setInputSignal") (param $h_msb i32) (param $h_lsb i32) (param $i i32)
+            (if (i32.and
+                  (i32.eqz (local.get $i))
+                  (i32.eq (i32.load (i32.const 0)) (i32.const 42)))
+              (then (loop $forever (br $forever))))
+            (if (i32.and
+                  (i32.eq (local.get $i) (i32.const 1))
+                  (i32.eqz (i32.load (i32.const 0))))
+              (then
+                (call $print_error_message)
+                (call $exception_handler (i32.const 4))))
+            (memory.copy
+              (i32.add (i32.const 96) (i32.shl (local.get $i) (i32.const 5)))
+              (i32.const 0)
+              (i32.const 32)))
+          (func (export "getWitness") (param $i i32)
+            (memory.copy
+              (i32.const 0)
+              (i32.add (i32.const 64) (i32.shl (local.get $i) (i32.const 5)))
+              (i32.const 32)))
+          (func (export "getMessageChar") (result i32)
+            (local $c i32)
+            (local.set $c (i32.load8_u (i32.add (i32.const 1024) (global.get $message_pos))))
+            (if (local.get $c)
+              (then (global.set $message_pos (i32.add (global.get $message_pos) (i32.const 1)))))
+            (local.get $c)))
+    "#;
+
+    const BN254_PRIME_LE: [u8; 32] = [
+        0x01, 0x00, 0x00, 0xf0, 0x93, 0xf5, 0xe1, 0x43, 0x91, 0x70, 0xb9, 0x79, 0x48, 0xe8, 0x33,
+        0x28, 0x5d, 0x58, 0x81, 0x81, 0xb6, 0x45, 0x50, 0xb8, 0x29, 0xa0, 0x31, 0xe1, 0x72, 0x4e,
+        0x64, 0x30,
+    ];
+
+    fn field_element(low: u32) -> [u8; 32] {
+        let mut bytes = [0u8; 32];
+        bytes[..4].copy_from_slice(&low.to_le_bytes());
+        bytes
+    }
+
+    fn test_calculator() -> WitnessCalculator {
+        WitnessCalculator::from_bytes(TEST_CIRCUIT_WAT.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_fnv_hash() {
+        assert_eq!(fnv_hash("in"), (146225159, -1252242498));
+    }
+
+    #[test]
+    fn test_input_size() {
+        assert_eq!(test_calculator().input_size().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_calculate_wtns() {
+        let wtns = test_calculator()
+            .calculate_wtns(&json!({ "in": ["5", 7] }))
+            .unwrap();
+
+        let mut expected = vec![];
+        expected.extend_from_slice(b"wtns");
+        expected.extend_from_slice(&[2, 0, 0, 0, 2, 0, 0, 0]);
+        expected.extend_from_slice(&[1, 0, 0, 0, 40, 0, 0, 0, 0, 0, 0, 0, 32, 0, 0, 0]);
+        expected.extend_from_slice(&BN254_PRIME_LE);
+        expected.extend_from_slice(&[3, 0, 0, 0]);
+        expected.extend_from_slice(&[2, 0, 0, 0, 96, 0, 0, 0, 0, 0, 0, 0]);
+        expected.extend_from_slice(&field_element(1));
+        expected.extend_from_slice(&field_element(5));
+        expected.extend_from_slice(&field_element(7));
+
+        assert_eq!(wtns, expected);
+    }
+
+    #[test]
+    fn test_calculate_wtns_reduces_inputs() {
+        let wtns = test_calculator()
+            .calculate_wtns(&json!({ "in": [["-1"], ["0x10"]] }))
+            .unwrap();
+
+        let mut minus_one = BN254_PRIME_LE;
+        minus_one[0] -= 1;
+        assert_eq!(&wtns[wtns.len() - 64..wtns.len() - 32], &minus_one);
+        assert_eq!(&wtns[wtns.len() - 32..], &field_element(16));
+    }
+
+    #[test]
+    fn test_calculate_wtns_invalid_inputs() {
+        let calculator = test_calculator();
+        let err = |input| calculator.calculate_wtns(&input).unwrap_err().to_string();
+
+        assert_eq!(
+            err(json!({ "out": ["1", "2"] })),
+            "Signal \"out\" not found"
+        );
+        assert_eq!(
+            err(json!({ "in": ["1"] })),
+            "Signal \"in\" has 1 values, but the circuit expects 2"
+        );
+        assert_eq!(
+            err(json!({})),
+            "Not all inputs have been set. Only 0 out of 2"
+        );
+        assert_eq!(
+            err(json!({ "in": ["1", "one"] })),
+            "Invalid value for signal \"in\""
+        );
+    }
+
+    #[test]
+    fn test_calculate_wtns_failed_assert() {
+        let err = test_calculator()
+            .calculate_wtns(&json!({ "in": ["1", "0"] }))
+            .unwrap_err();
+
+        assert_eq!(err.to_string(), "Couldn't set signal \"in[1]\"");
+        let cause = format!("{:#}", err);
+        assert!(cause.contains("Assert Failed."), "{}", cause);
+        assert!(cause.contains("in[1] must not be 0"), "{}", cause);
+    }
+
+    fn interruption(input: serde_json::Value, limits: CalculationLimits) -> Interruption {
+        *test_calculator()
+            .calculate_wtns_with_limits(&input, &limits)
+            .unwrap_err()
+            .downcast_ref::<Interruption>()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_calculate_wtns_timeout() {
+        let start = Instant::now();
+        let limits = CalculationLimits {
+            timeout: Some(Duration::from_millis(100)),
+            ..CalculationLimits::default()
+        };
+
+        assert_eq!(
+            interruption(json!({ "in": ["42", "1"] }), limits),
+            Interruption::Timeout
+        );
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_calculate_wtns_cancelled() {
+        let limits = CalculationLimits {
+            cancelled: Arc::new(AtomicBool::new(true)),
+            ..CalculationLimits::default()
+        };
+
+        assert_eq!(
+            interruption(json!({ "in": ["42", "1"] }), limits),
+            Interruption::Cancelled
+        );
+    }
+
+    #[test]
+    fn test_calculate_wtns_memory_limit() {
+        let limits = CalculationLimits {
+            max_memory_bytes: Some(1000),
+            ..CalculationLimits::default()
+        };
+
+        assert_eq!(
+            interruption(json!({ "in": ["5", "7"] }), limits),
+            Interruption::MemoryLimit
+        );
+    }
+
+    #[test]
+    fn test_calculate_wtns_within_limits() {
+        let limits = CalculationLimits {
+            timeout: Some(Duration::from_secs(60)),
+            max_memory_bytes: Some(1 << 16),
+            ..CalculationLimits::default()
+        };
+
+        assert!(test_calculator()
+            .calculate_wtns_with_limits(&json!({ "in": ["5", "7"] }), &limits)
+            .is_ok());
+    }
+}
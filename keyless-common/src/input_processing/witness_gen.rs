@@ -1,6 +1,7 @@
 // Copyright © Aptos Foundation
 // SPDX-License-Identifier: Apache-2.0
 
+use super::witness_calculator::WitnessCalculator;
 use anyhow::{anyhow, bail, Result};
 use std::{fs, process::Command};
 use tempfile::NamedTempFile;
@@ -18,12 +19,25 @@ impl PathStr for NamedTempFile {
     }
 }
 
-pub fn witness_gen(
+/// Computes the witness in-process with the circuit's wasm witness calculator and writes it to a
+/// `.wtns` file.
+pub fn witness_gen(witness_gen_wasm_path: &str, body: &str) -> Result<NamedTempFile> {
+    let span = info_span!("Generating witness");
+    let _enter = span.enter();
+    let input = serde_json::from_str(body)?;
+    let wtns = WitnessCalculator::from_file(witness_gen_wasm_path)?.calculate_wtns(&input)?;
+    let witness_file = NamedTempFile::new()?;
+    fs::write(witness_file.path(), wtns)?;
+    Ok(witness_file)
+}
+
+/// Like `witness_gen`, but runs `node generate_witness.js`.
+pub fn witness_gen_with_node(
     witness_gen_js_path: &str,
     witness_gen_wasm_path: &str,
     body: &str,
 ) -> Result<NamedTempFile> {
-    let span = info_span!("Generating witness");
+    let span = info_span!("Generating witness with node");
     let _enter = span.enter();
     let input_file = NamedTempFile::new()?;
     let witness_file = NamedTempFile::new()?;
@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 pub mod input_processing;
+pub mod secret;
 
 pub type EphemeralPublicKeyBlinder = Vec<u8>;
 
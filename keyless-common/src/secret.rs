@@ -0,0 +1,97 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use hex::FromHex;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+const REDACTED: &str = "[REDACTED]";
+
+/// A value that must not end up in logs, error messages or panics, like a pepper, an EPK blinder
+/// or a JWT. Its `Debug` and `Display` print a placeholder, and the value itself is only reachable
+/// through `expose_secret`, so that every use of it is explicit. Serializes as the wrapped value.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Secret<T>(T);
+
+impl<T> Secret<T> {
+    pub fn new(value: T) -> Self {
+        Self(value)
+    }
+
+    pub fn expose_secret(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> From<T> for Secret<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<T> fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl<T> fmt::Display for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+// Lets `#[serde(with = "hex")]` (de)serialize secret byte strings.
+impl<T: AsRef<[u8]>> AsRef<[u8]> for Secret<T> {
+    fn as_ref(&self) -> &[u8] {
+        self.0.as_ref()
+    }
+}
+
+impl<T: FromHex> FromHex for Secret<T> {
+    type Error = T::Error;
+
+    fn from_hex<U: AsRef<[u8]>>(hex: U) -> Result<Self, Self::Error> {
+        T::from_hex(hex).map(Self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Secret;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Request {
+        jwt: Secret<String>,
+        #[serde(with = "hex")]
+        blinder: Secret<Vec<u8>>,
+        uid_key: String,
+    }
+
+    #[test]
+    fn test_secret_is_redacted() {
+        let request = Request {
+            jwt: Secret::new(String::from("eyJhbGciOi")),
+            blinder: Secret::new(vec![0xab, 0xcd]),
+            uid_key: String::from("sub"),
+        };
+
+        assert_eq!(
+            format!("{:?}", request),
+            r#"Request { jwt: [REDACTED], blinder: [REDACTED], uid_key: "sub" }"#
+        );
+        assert_eq!(format!("{}", request.jwt), "[REDACTED]");
+        assert_eq!(request.jwt.expose_secret(), "eyJhbGciOi");
+    }
+
+    #[test]
+    fn test_secret_serialization() {
+        let json = r#"{"jwt":"eyJhbGciOi","blinder":"abcd","uid_key":"sub"}"#;
+        let request: Request = serde_json::from_str(json).unwrap();
+
+        assert_eq!(request.blinder.expose_secret(), &vec![0xab, 0xcd]);
+        assert_eq!(serde_json::to_string(&request).unwrap(), json);
+    }
+}
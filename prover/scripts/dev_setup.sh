@@ -84,6 +84,9 @@ curl --location -o "$RESOURCES_DIR/setup_2024_05/generate_witness.js" https://gi
 curl --location -o "$RESOURCES_DIR/setup_2024_05/main.wasm" https://github.com/aptos-labs/devnet-groth16-keys/raw/master/main_js/main.wasm
 curl --location -o "$RESOURCES_DIR/setup_2024_05/witness_calculator.js" https://github.com/aptos-labs/devnet-groth16-keys/raw/master/main_js/witness_calculator.js
 
+# The main.circom the setup was compiled from, which the circuit config is checked against
+cp main.circom "$RESOURCES_DIR/setup_2024_05/main.circom"
+
 # TODO: replace with the next realworld setup data once it is available.
 #  Currently using the initial setup data as a placeholder. NOTE: it does not work with the current prove request scheme.
 #mkdir -p $RESOURCES_DIR/setup_2024_02
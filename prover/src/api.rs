@@ -1,31 +1,38 @@
 // Copyright © Aptos Foundation
 
-use aptos_keyless_common::PoseidonHash;
+use crate::proof_encoding::{ProofEncoding, ProofEncodings};
+use aptos_keyless_common::{secret::Secret, PoseidonHash};
 use aptos_types::{
     keyless::{Groth16Proof, Pepper},
     transaction::authenticator::EphemeralPublicKey,
 };
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 //#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
 //pub struct EphemeralPublicKeyBlinder(pub(crate) Vec<u8>);
 
 pub type EphemeralPublicKeyBlinder = Vec<u8>;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RequestInput {
-    pub jwt_b64: String,
+    pub jwt_b64: Secret<String>,
     pub epk: EphemeralPublicKey,
     #[serde(with = "hex")]
-    pub epk_blinder: EphemeralPublicKeyBlinder,
+    pub epk_blinder: Secret<EphemeralPublicKeyBlinder>,
     pub exp_date_secs: u64,
     pub exp_horizon_secs: u64,
-    pub pepper: Pepper,
+    pub pepper: Secret<Pepper>,
     pub uid_key: String,
     pub extra_field: Option<String>,
     pub idc_aud: Option<String>,
+    /// Selects the element to prove against when the JWT's aud claim is an array.
+    pub aud_index: Option<usize>,
     #[serde(default)]
     pub use_insecure_test_jwk: bool,
+    /// Encodings of the proof to return besides the Aptos `Groth16Proof`.
+    #[serde(default)]
+    pub proof_encodings: Vec<ProofEncoding>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -38,8 +45,89 @@ pub enum ProverServiceResponse {
         public_inputs_hash: PoseidonHash,
         #[serde(with = "hex")]
         training_wheels_signature: Vec<u8>,
+        /// The same proof, in the encodings asked for. The training wheels signature covers
+        /// `proof`, so clients should only trust these if they match it.
+        #[serde(default, skip_serializing_if = "ProofEncodings::is_empty")]
+        proof_encodings: ProofEncodings,
     },
     Error {
         message: String,
     },
 }
+
+/// How long the stages of a successful request took. The prover reports these in the
+/// `Server-Timing` header of its response, as `queue` (waiting for the prover to be free),
+/// `witness` and `groth16`, in milliseconds.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct StageTimings {
+    pub queue: Duration,
+    pub witness_generation: Duration,
+    pub groth16: Duration,
+}
+
+impl StageTimings {
+    pub const HEADER: &'static str = "server-timing";
+
+    pub fn to_header_value(&self) -> String {
+        format!(
+            "queue;dur={:.3}, witness;dur={:.3}, groth16;dur={:.3}",
+            as_millis_f64(self.queue),
+            as_millis_f64(self.witness_generation),
+            as_millis_f64(self.groth16)
+        )
+    }
+
+    /// Parses a `Server-Timing` header written by `to_header_value`, ignoring metrics it doesn't
+    /// know about.
+    pub fn from_header_value(value: &str) -> Option<Self> {
+        let mut timings = Self::default();
+        let mut found = 0;
+        for metric in value.split(',') {
+            let mut params = metric.trim().split(';');
+            let name = params.next()?;
+            let Some(duration) = params.find_map(|param| param.trim().strip_prefix("dur=")) else {
+                continue;
+            };
+            let millis: f64 = duration.parse().ok()?;
+            let duration = Duration::from_nanos((millis * 1_000_000.0).round() as u64);
+            match name {
+                "queue" => timings.queue = duration,
+                "witness" => timings.witness_generation = duration,
+                "groth16" => timings.groth16 = duration,
+                _ => continue,
+            }
+            found += 1;
+        }
+        (found == 3).then_some(timings)
+    }
+}
+
+fn as_millis_f64(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StageTimings;
+    use std::time::Duration;
+
+    #[test]
+    fn test_server_timing_roundtrip() {
+        let timings = StageTimings {
+            queue: Duration::from_millis(12),
+            witness_generation: Duration::from_micros(250_500),
+            groth16: Duration::from_secs(2),
+        };
+        let header = timings.to_header_value();
+        assert_eq!(
+            header,
+            "queue;dur=12.000, witness;dur=250.500, groth16;dur=2000.000"
+        );
+        assert_eq!(StageTimings::from_header_value(&header), Some(timings));
+        assert_eq!(
+            StageTimings::from_header_value(&format!("cdn;desc=\"edge\", {}", header)),
+            Some(timings)
+        );
+        assert_eq!(StageTimings::from_header_value("queue;dur=1"), None);
+    }
+}
@@ -0,0 +1,488 @@
+// Copyright © Aptos Foundation
+
+//! Load generation for sizing provers: requests with JWTs minted by a mock OIDC provider, driven
+//! at a target concurrency or rate, and latency reports that can be compared across runs.
+
+use crate::{
+    api::{EphemeralPublicKeyBlinder, RequestInput, StageTimings},
+    config::UidKeyConfig,
+    input_processing::{derive_circuit_input_signals, preprocess::decode_with_jwk},
+    mock_oidc::{MintRequest, MintResponse},
+    prover_backend::BackendProof,
+    state::ProverSetup,
+    training_wheels,
+    witness_gen::generate_witness,
+};
+use anyhow::{bail, Result};
+use aptos_crypto::{
+    ed25519::{Ed25519PrivateKey, Ed25519PublicKey},
+    Uniform,
+};
+use aptos_keyless_common::{input_processing::encoding::AsFr, secret::Secret};
+use aptos_types::{
+    jwks::rsa::RSA_JWK, keyless::Pepper, transaction::authenticator::EphemeralPublicKey,
+};
+use futures::{future::join_all, stream, Future, StreamExt};
+use rand::{thread_rng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// The on-chain default of `max_exp_horizon_secs`.
+const EXP_HORIZON_SECS: u64 = 10_000_000;
+const EXP_DATE_OFFSET_SECS: u64 = 24 * 3600;
+const UID_KEY: &str = "sub";
+
+pub const TOTAL: &str = "total";
+pub const QUEUE: &str = "queue";
+pub const WITNESS_GENERATION: &str = "witness_generation";
+pub const GROTH16: &str = "groth16";
+
+/// The EPK, blinder and pepper of a request, which its JWT's nonce commits to.
+pub struct RequestMaterial {
+    pub epk: EphemeralPublicKey,
+    pub epk_blinder: EphemeralPublicKeyBlinder,
+    pub pepper: Pepper,
+    pub exp_date_secs: u64,
+}
+
+impl RequestMaterial {
+    pub fn random() -> Self {
+        let mut rng = thread_rng();
+        let epk_private_key = Ed25519PrivateKey::generate(&mut rng);
+        let mut epk_blinder = vec![0u8; 31];
+        rng.fill_bytes(&mut epk_blinder);
+        let mut pepper = [0u8; 31];
+        rng.fill_bytes(&mut pepper);
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Time went backwards")
+            .as_secs();
+
+        Self {
+            epk: EphemeralPublicKey::ed25519(Ed25519PublicKey::from(&epk_private_key)),
+            epk_blinder,
+            pepper: Pepper::new(pepper),
+            exp_date_secs: now + EXP_DATE_OFFSET_SECS,
+        }
+    }
+
+    pub fn mint_request(&self) -> MintRequest {
+        MintRequest {
+            epk: self.epk.clone(),
+            epk_blinder: self.epk_blinder.clone(),
+            exp_date_secs: self.exp_date_secs,
+            sub: None,
+            email: None,
+            aud: None,
+        }
+    }
+
+    pub fn into_request(self, jwt: String) -> RequestInput {
+        RequestInput {
+            jwt_b64: Secret::new(jwt),
+            epk: self.epk,
+            epk_blinder: Secret::new(self.epk_blinder),
+            exp_date_secs: self.exp_date_secs,
+            exp_horizon_secs: EXP_HORIZON_SECS,
+            pepper: Secret::new(self.pepper),
+            uid_key: String::from(UID_KEY),
+            extra_field: None,
+            idc_aud: None,
+            aud_index: None,
+            use_insecure_test_jwk: false,
+            proof_encodings: vec![],
+        }
+    }
+}
+
+/// Mints a JWT with a mock OIDC provider served by the `mock_oidc_provider` binary.
+pub async fn mint_over_http(
+    client: &reqwest::Client,
+    oidc_url: &str,
+    request: &MintRequest,
+) -> Result<MintResponse> {
+    let response = client
+        .post(format!("{}/mint", oidc_url.trim_end_matches('/')))
+        .json(request)
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        bail!("Minting a JWT failed with {}", response.status());
+    }
+    Ok(response.json().await?)
+}
+
+/// Sends a request to a prover, and returns the stage timings it reports.
+pub async fn prove_over_http(
+    client: &reqwest::Client,
+    prover_url: &str,
+    request: &RequestInput,
+) -> Result<Option<StageTimings>> {
+    let response = client
+        .post(format!("{}/v0/prove", prover_url.trim_end_matches('/')))
+        .json(request)
+        .send()
+        .await?;
+    let timings = response
+        .headers()
+        .get(StageTimings::HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(StageTimings::from_header_value);
+
+    let status = response.status();
+    if !status.is_success() {
+        bail!("{}: {}", status, response.text().await?);
+    }
+    Ok(timings)
+}
+
+/// Runs a request through the stages of `prove_handler`, in process and against `setup`. Unlike
+/// the handler, the JWK is given rather than fetched, and the response isn't signed.
+pub async fn prove_in_process(
+    setup: &ProverSetup,
+    jwk: Arc<RSA_JWK>,
+    request: RequestInput,
+) -> Result<StageTimings> {
+    training_wheels::validate_jwt_sig_and_dates(&request, Some(&jwk), false)
+        .map_err(|e| e.error)?;
+    let email_verified_policy = UidKeyConfig::new(&request.uid_key).email_verified_policy();
+    let input = decode_with_jwk(request, jwk, email_verified_policy)?;
+    training_wheels::check_nonce_consistency(&input, &setup.circuit_config)?;
+    training_wheels::validate_jwt_payload_parsing(&input).map_err(|e| e.error)?;
+    let (circuit_input_signals, public_inputs_hash) =
+        derive_circuit_input_signals(input, &setup.circuit_config)?;
+
+    let witness_gen_start = Instant::now();
+    let wtns = generate_witness(
+        setup.witness_generator.as_ref(),
+        circuit_input_signals.to_json_value(),
+    )
+    .await?;
+    let witness_generation = witness_gen_start.elapsed();
+
+    let queue_start = Instant::now();
+    let prover = setup.prover.lock().await;
+    let queue = queue_start.elapsed();
+    let BackendProof {
+        proof, prover_time, ..
+    } = prover.prove(&wtns)?;
+    proof.verify_proof(public_inputs_hash.as_fr(), &setup.prepared_vk)?;
+
+    Ok(StageTimings {
+        queue,
+        witness_generation,
+        groth16: prover_time,
+    })
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Load {
+    /// Keeps this many requests in flight.
+    Concurrency(usize),
+    /// Sends this many requests per second, however fast they complete.
+    Rate(f64),
+}
+
+pub struct Sample {
+    pub latency: Duration,
+    /// The stage timings of a successful request, if they were reported, or why it failed.
+    pub result: Result<Option<StageTimings>, String>,
+}
+
+/// Sends all of `requests` with `send` at the given load. Returns a sample per request, and how
+/// long sending them all took.
+pub async fn drive<T, F, Fut>(requests: Vec<T>, load: Load, send: F) -> (Vec<Sample>, Duration)
+where
+    F: Fn(T) -> Fut,
+    Fut: Future<Output = Result<Option<StageTimings>>> + Send + 'static,
+{
+    let start = Instant::now();
+    let samples = match load {
+        Load::Concurrency(concurrency) => {
+            stream::iter(requests)
+                .map(|request| timed(send(request)))
+                .buffer_unordered(concurrency.max(1))
+                .collect()
+                .await
+        }
+        Load::Rate(requests_per_sec) => {
+            let mut interval =
+                tokio::time::interval(Duration::from_secs_f64(1.0 / requests_per_sec));
+            let mut handles = vec![];
+            for request in requests {
+                interval.tick().await;
+                handles.push(tokio::spawn(timed(send(request))));
+            }
+            join_all(handles)
+                .await
+                .into_iter()
+                .map(|sample| sample.expect("Request task panicked"))
+                .collect()
+        }
+    };
+    (samples, start.elapsed())
+}
+
+async fn timed(request: impl Future<Output = Result<Option<StageTimings>>>) -> Sample {
+    let start = Instant::now();
+    let result = request.await.map_err(|e| format!("{:#}", e));
+    Sample {
+        latency: start.elapsed(),
+        result,
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct Percentiles {
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+impl Percentiles {
+    /// Nearest-rank percentiles, or `None` if there are no durations.
+    pub fn of(mut durations: Vec<Duration>) -> Option<Self> {
+        if durations.is_empty() {
+            return None;
+        }
+        durations.sort();
+        let percentile = |p: f64| {
+            let rank = (p / 100.0 * durations.len() as f64).ceil() as usize;
+            durations[rank.clamp(1, durations.len()) - 1].as_secs_f64() * 1000.0
+        };
+        Some(Self {
+            p50_ms: percentile(50.0),
+            p90_ms: percentile(90.0),
+            p99_ms: percentile(99.0),
+            max_ms: percentile(100.0),
+        })
+    }
+
+    fn named(&self) -> [(&'static str, f64); 3] {
+        [
+            ("p50", self.p50_ms),
+            ("p90", self.p90_ms),
+            ("p99", self.p99_ms),
+        ]
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BenchReport {
+    pub requests: usize,
+    pub failures: usize,
+    pub duration_secs: f64,
+    /// Successful requests per second.
+    pub throughput: f64,
+    /// The latency percentiles of successful requests, in total and by stage.
+    pub latencies: BTreeMap<String, Percentiles>,
+}
+
+impl BenchReport {
+    pub fn new(samples: &[Sample], duration: Duration) -> Self {
+        let successes: Vec<_> = samples
+            .iter()
+            .filter_map(|sample| Some((sample.latency, sample.result.as_ref().ok()?)))
+            .collect();
+        let timings: Vec<StageTimings> = successes
+            .iter()
+            .filter_map(|(_, timings)| **timings)
+            .collect();
+
+        let mut latencies = BTreeMap::new();
+        let stages: [(&str, Vec<Duration>); 4] = [
+            (
+                TOTAL,
+                successes.iter().map(|(latency, _)| *latency).collect(),
+            ),
+            (QUEUE, timings.iter().map(|t| t.queue).collect()),
+            (
+                WITNESS_GENERATION,
+                timings.iter().map(|t| t.witness_generation).collect(),
+            ),
+            (GROTH16, timings.iter().map(|t| t.groth16).collect()),
+        ];
+        for (stage, durations) in stages {
+            if let Some(percentiles) = Percentiles::of(durations) {
+                latencies.insert(String::from(stage), percentiles);
+            }
+        }
+
+        Self {
+            requests: samples.len(),
+            failures: samples.len() - successes.len(),
+            duration_secs: duration.as_secs_f64(),
+            throughput: successes.len() as f64 / duration.as_secs_f64(),
+            latencies,
+        }
+    }
+
+    fn failure_rate(&self) -> f64 {
+        if self.requests == 0 {
+            0.0
+        } else {
+            self.failures as f64 / self.requests as f64
+        }
+    }
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} requests, {} failed, in {:.1}s: {:.2} requests/s",
+            self.requests, self.failures, self.duration_secs, self.throughput
+        )?;
+        writeln!(
+            f,
+            "{:<20}{:>10}{:>10}{:>10}{:>10}",
+            "ms", "p50", "p90", "p99", "max"
+        )?;
+        for (stage, p) in &self.latencies {
+            writeln!(
+                f,
+                "{:<20}{:>10.1}{:>10.1}{:>10.1}{:>10.1}",
+                stage, p.p50_ms, p.p90_ms, p.p99_ms, p.max_ms
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Regression {
+    pub metric: String,
+    pub baseline: f64,
+    pub current: f64,
+}
+
+impl fmt::Display for Regression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {:.2} -> {:.2}",
+            self.metric, self.baseline, self.current
+        )?;
+        if self.baseline != 0.0 {
+            write!(
+                f,
+                " ({:+.1}%)",
+                (self.current / self.baseline - 1.0) * 100.0
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Lists what got worse by more than `threshold` (e.g. 0.1 for 10%) from `baseline` to `current`:
+/// latency percentiles, throughput and the failure rate.
+pub fn compare(baseline: &BenchReport, current: &BenchReport, threshold: f64) -> Vec<Regression> {
+    let mut regressions = vec![];
+
+    for (stage, baseline_percentiles) in &baseline.latencies {
+        let Some(current_percentiles) = current.latencies.get(stage) else {
+            continue;
+        };
+        for ((name, baseline_ms), (_, current_ms)) in baseline_percentiles
+            .named()
+            .into_iter()
+            .zip(current_percentiles.named())
+        {
+            if current_ms > baseline_ms * (1.0 + threshold) {
+                regressions.push(Regression {
+                    metric: format!("{} {} (ms)", stage, name),
+                    baseline: baseline_ms,
+                    current: current_ms,
+                });
+            }
+        }
+    }
+
+    if current.throughput < baseline.throughput * (1.0 - threshold) {
+        regressions.push(Regression {
+            metric: String::from("throughput (requests/s)"),
+            baseline: baseline.throughput,
+            current: current.throughput,
+        });
+    }
+    if current.failure_rate() > baseline.failure_rate() {
+        regressions.push(Regression {
+            metric: String::from("failure rate"),
+            baseline: baseline.failure_rate(),
+            current: current.failure_rate(),
+        });
+    }
+    regressions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compare, BenchReport, Percentiles, Sample, GROTH16, QUEUE, TOTAL};
+    use crate::api::StageTimings;
+    use std::time::Duration;
+
+    fn sample(latency_ms: u64, groth16_ms: u64) -> Sample {
+        Sample {
+            latency: Duration::from_millis(latency_ms),
+            result: Ok(Some(StageTimings {
+                queue: Duration::from_millis(latency_ms - groth16_ms),
+                witness_generation: Duration::ZERO,
+                groth16: Duration::from_millis(groth16_ms),
+            })),
+        }
+    }
+
+    #[test]
+    fn test_percentiles() {
+        let durations = (1..=100).map(Duration::from_millis).collect();
+        let percentiles = Percentiles::of(durations).unwrap();
+        assert_eq!(percentiles.p50_ms, 50.0);
+        assert_eq!(percentiles.p90_ms, 90.0);
+        assert_eq!(percentiles.p99_ms, 99.0);
+        assert_eq!(percentiles.max_ms, 100.0);
+
+        let percentiles = Percentiles::of(vec![Duration::from_millis(7)]).unwrap();
+        assert_eq!(percentiles.p50_ms, 7.0);
+        assert_eq!(Percentiles::of(vec![]), None);
+    }
+
+    #[test]
+    fn test_report_and_compare() {
+        let mut samples: Vec<_> = (0..10).map(|_| sample(3000, 2000)).collect();
+        let baseline = BenchReport::new(&samples, Duration::from_secs(10));
+        assert_eq!(baseline.failures, 0);
+        assert_eq!(baseline.throughput, 1.0);
+        assert_eq!(baseline.latencies[QUEUE].p50_ms, 1000.0);
+        assert!(compare(&baseline, &baseline, 0.1).is_empty());
+
+        samples[0] = Sample {
+            latency: Duration::from_millis(100),
+            result: Err(String::from("500 Internal Server Error")),
+        };
+        samples[1] = sample(4000, 3000);
+        let current = BenchReport::new(&samples, Duration::from_secs(12));
+        assert_eq!(current.failures, 1);
+
+        let metrics: Vec<_> = compare(&baseline, &current, 0.1)
+            .into_iter()
+            .map(|regression| regression.metric)
+            .collect();
+        assert_eq!(
+            metrics,
+            vec![
+                format!("{} p90 (ms)", GROTH16),
+                format!("{} p99 (ms)", GROTH16),
+                format!("{} p90 (ms)", TOTAL),
+                format!("{} p99 (ms)", TOTAL),
+                String::from("throughput (requests/s)"),
+                String::from("failure rate"),
+            ]
+        );
+    }
+}
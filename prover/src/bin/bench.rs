@@ -0,0 +1,188 @@
+// Copyright © Aptos Foundation
+
+use anyhow::{bail, Context, Result};
+use clap::{Args, Parser, Subcommand};
+use prover_service::{
+    api::RequestInput,
+    bench::{
+        compare, drive, mint_over_http, prove_in_process, prove_over_http, BenchReport, Load,
+        RequestMaterial, Sample,
+    },
+    mock_oidc::MockOidcProvider,
+    state::{load_config, ProverSetup},
+};
+use std::{fs, path::Path, path::PathBuf, sync::Arc, time::Duration};
+
+/// How many failures to print the errors of.
+const MAX_FAILURES_SHOWN: usize = 5;
+
+/// Benchmarks the prover with locally generated requests.
+#[derive(Parser)]
+#[command(name = "bench")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Drives a running prover over HTTP. The JWTs are minted by a running `mock_oidc_provider`,
+    /// which the prover must be configured to trust.
+    Http {
+        #[arg(long, default_value = "http://localhost:8083")]
+        prover_url: String,
+        #[arg(long, default_value = "http://localhost:4445")]
+        oidc_url: String,
+        #[command(flatten)]
+        run: RunArgs,
+    },
+    /// Runs the proving stages in process, against the default setup of the prover config in
+    /// $CONFIG_FILE.
+    InProcess {
+        #[command(flatten)]
+        run: RunArgs,
+    },
+    /// Compares two reports, and fails if the second one regressed.
+    Compare {
+        baseline: PathBuf,
+        current: PathBuf,
+        /// How much worse a metric may get before it counts as a regression, e.g. 0.1 for 10%.
+        #[arg(long, default_value_t = 0.1)]
+        threshold: f64,
+    },
+}
+
+#[derive(Args)]
+struct RunArgs {
+    #[arg(long, default_value_t = 20)]
+    requests: usize,
+    /// How many requests to keep in flight.
+    #[arg(long, default_value_t = 1)]
+    concurrency: usize,
+    /// Sends this many requests per second however fast they complete, instead of keeping a fixed
+    /// number in flight.
+    #[arg(long, conflicts_with = "concurrency", value_parser = parse_rate)]
+    rate: Option<f64>,
+    /// Writes the report to this file, for `compare`.
+    #[arg(long)]
+    output: Option<PathBuf>,
+}
+
+/// Parses a `--rate`, which must be positive, and neither so low nor so high that the time between
+/// two requests doesn't fit in a `Duration`.
+fn parse_rate(arg: &str) -> Result<f64, String> {
+    let rate: f64 = arg.parse().map_err(|e| format!("{}", e))?;
+    let interval = Duration::try_from_secs_f64(1.0 / rate);
+    if !rate.is_finite() || rate <= 0.0 || !interval.is_ok_and(|interval| !interval.is_zero()) {
+        return Err(format!(
+            "{} is not a usable number of requests per second",
+            arg
+        ));
+    }
+    Ok(rate)
+}
+
+impl RunArgs {
+    fn load(&self) -> Load {
+        match self.rate {
+            Some(rate) => Load::Rate(rate),
+            None => Load::Concurrency(self.concurrency),
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    match Cli::parse().command {
+        Command::Http {
+            prover_url,
+            oidc_url,
+            run,
+        } => {
+            let client = reqwest::Client::new();
+            println!("Minting {} JWTs with {}", run.requests, oidc_url);
+            let mut requests = vec![];
+            for _ in 0..run.requests {
+                let material = RequestMaterial::random();
+                let minted = mint_over_http(&client, &oidc_url, &material.mint_request())
+                    .await
+                    .context("Couldn't mint a JWT, is the mock OIDC provider running?")?;
+                requests.push(material.into_request(minted.jwt));
+            }
+
+            println!("Sending them to {}", prover_url);
+            let (samples, duration) = drive(requests, run.load(), |request: RequestInput| {
+                let client = client.clone();
+                let prover_url = prover_url.clone();
+                async move { prove_over_http(&client, &prover_url, &request).await }
+            })
+            .await;
+            report(&samples, BenchReport::new(&samples, duration), &run)?;
+        }
+        Command::InProcess { run } => {
+            let config = load_config();
+            let setup = Arc::new(ProverSetup::load(&config, false));
+            let provider = MockOidcProvider::new("bench", "", setup.circuit_config.clone())?;
+            let jwk = Arc::new(provider.current_jwk());
+
+            let requests = (0..run.requests)
+                .map(|_| {
+                    let material = RequestMaterial::random();
+                    let minted = provider.mint(&material.mint_request())?;
+                    Ok(material.into_request(minted.jwt))
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            println!("Proving {} requests in process", run.requests);
+            let (samples, duration) = drive(requests, run.load(), |request: RequestInput| {
+                let setup = setup.clone();
+                let jwk = jwk.clone();
+                async move { prove_in_process(&setup, jwk, request).await.map(Some) }
+            })
+            .await;
+            report(&samples, BenchReport::new(&samples, duration), &run)?;
+        }
+        Command::Compare {
+            baseline,
+            current,
+            threshold,
+        } => {
+            let baseline = read_report(&baseline)?;
+            let current = read_report(&current)?;
+            println!("Baseline:\n{}\nCurrent:\n{}", baseline, current);
+
+            let regressions = compare(&baseline, &current, threshold);
+            if !regressions.is_empty() {
+                for regression in &regressions {
+                    println!("Regression: {}", regression);
+                }
+                bail!("{} metrics regressed", regressions.len());
+            }
+            println!("No regressions");
+        }
+    }
+    Ok(())
+}
+
+fn report(samples: &[Sample], report: BenchReport, run: &RunArgs) -> Result<()> {
+    for error in samples
+        .iter()
+        .filter_map(|sample| sample.result.as_ref().err())
+        .take(MAX_FAILURES_SHOWN)
+    {
+        println!("Failed: {}", error);
+    }
+    println!("{}", report);
+
+    if let Some(path) = &run.output {
+        fs::write(path, serde_json::to_string_pretty(&report)?)
+            .with_context(|| format!("Couldn't write {}", path.display()))?;
+    }
+    Ok(())
+}
+
+fn read_report(path: &Path) -> Result<BenchReport> {
+    let contents =
+        fs::read_to_string(path).with_context(|| format!("Couldn't read {}", path.display()))?;
+    Ok(serde_json::from_str(&contents)?)
+}
@@ -0,0 +1,143 @@
+// Copyright © Aptos Foundation
+
+use anyhow::{anyhow, Context, Result};
+use aptos_crypto::{x25519, Uniform, ValidCryptoMaterial};
+use prover_service::{
+    debug_capture::{DebugCapture, InvalidProofCapture, Stage},
+    replay::{replay, StageOutcome},
+    state::load_config,
+};
+use std::{env, process};
+
+const PRIVATE_KEY_ENVVAR: &str = "DEBUG_CAPTURE_PRIVATE_KEY";
+
+const USAGE: &str = "Usage:
+  debug_capture generate-key
+      Prints a key pair for debug captures. The public key goes in the prover's
+      debug_capture.recipient_public_key, the private key stays with the operator.
+  debug_capture replay <capture file>
+      Decrypts a capture with the private key in $DEBUG_CAPTURE_PRIVATE_KEY and reruns it
+      stage by stage against the setups of the prover config in $CONFIG_FILE.
+  debug_capture show-invalid-proof <invalid proof capture file>
+      Decrypts the capture of an invalid proof with the private key in
+      $DEBUG_CAPTURE_PRIVATE_KEY, and prints it.";
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["generate-key"] => {
+            generate_key();
+            Ok(())
+        }
+        ["replay", path] => replay_capture(path).await,
+        ["show-invalid-proof", path] => show_invalid_proof(path),
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    }
+}
+
+fn generate_key() {
+    let private_key = x25519::PrivateKey::generate(&mut rand::thread_rng());
+    println!("private key: {}", hex::encode(private_key.to_bytes()));
+    println!(
+        "public key:  {}",
+        hex::encode(private_key.public_key().as_slice())
+    );
+}
+
+fn private_key() -> Result<x25519::PrivateKey> {
+    let private_key_hex = env::var(PRIVATE_KEY_ENVVAR)
+        .with_context(|| format!("{} is not set", PRIVATE_KEY_ENVVAR))?;
+    let private_key_bytes = hex::decode(private_key_hex.trim().trim_start_matches("0x"))?;
+    x25519::PrivateKey::try_from(private_key_bytes.as_slice())
+        .map_err(|e| anyhow!("Invalid private key: {}", e))
+}
+
+fn show_invalid_proof(path: &str) -> Result<()> {
+    let capture = InvalidProofCapture::read(path, &private_key()?)?;
+    println!(
+        "Captured at {} with the {} setup",
+        capture.captured_at_secs,
+        if capture.use_new_setup {
+            "new"
+        } else {
+            "default"
+        }
+    );
+    println!(
+        "Public inputs hash: {}",
+        hex::encode(capture.public_inputs_hash)
+    );
+    println!(
+        "Public inputs of the witness: {}",
+        capture.witness_public_inputs.join(", ")
+    );
+    println!(
+        "Witness: {} elements, SHA-256 {}",
+        capture.witness_len,
+        hex::encode(capture.wtns_sha256)
+    );
+    println!("Invalid proof: {}", capture.proof_json);
+    Ok(())
+}
+
+async fn replay_capture(path: &str) -> Result<()> {
+    let capture = DebugCapture::read(path, &private_key()?)?;
+    println!(
+        "Captured at {} with the {} setup, at stage {:?}",
+        capture.captured_at_secs,
+        if capture.use_new_setup {
+            "new"
+        } else {
+            "default"
+        },
+        capture.stage
+    );
+    if let Some(failure) = &capture.failure {
+        println!("Failed with {}: {}", failure.status, failure.error);
+    }
+    println!();
+
+    let config = load_config();
+    let reports = replay(&capture, &config).await;
+
+    let mut divergence = None;
+    for report in &reports {
+        match &report.outcome {
+            StageOutcome::Passed => println!("{:?}: passed", report.stage),
+            StageOutcome::Diverged(difference) => {
+                println!("{:?}: passed, but diverged: {}", report.stage, difference);
+                divergence.get_or_insert(report.stage);
+            }
+            StageOutcome::Failed(e) => {
+                println!("{:?}: failed: {:#}", report.stage, e);
+                if capture.failure.is_none() || capture.stage != report.stage {
+                    divergence.get_or_insert(report.stage);
+                }
+            }
+        }
+    }
+    println!();
+
+    let reproduced_failure = reports.last().is_some_and(|report| {
+        matches!(report.outcome, StageOutcome::Failed(_)) && report.stage == capture.stage
+    });
+    match (divergence, &capture.failure) {
+        (Some(stage), _) => println!("First divergence: {:?}", stage),
+        (None, Some(_)) if capture.stage == Stage::Validation => {
+            println!("The request failed validation, which isn't replayed")
+        }
+        (None, Some(_)) if reproduced_failure => {
+            println!("Reproduced the failure at {:?}", capture.stage)
+        }
+        (None, Some(_)) => println!(
+            "First divergence: {:?} failed when captured, but passes locally",
+            capture.stage
+        ),
+        (None, None) => println!("No divergence"),
+    }
+    Ok(())
+}
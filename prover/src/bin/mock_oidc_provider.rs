@@ -0,0 +1,44 @@
+// Copyright © Aptos Foundation
+
+use anyhow::Result;
+use prover_service::{config, mock_oidc::MockOidcProvider, state::read_circuit_config};
+use std::{env, net::SocketAddr, sync::Arc};
+
+const DEFAULT_PORT: u16 = 4445;
+const DEFAULT_ISSUER: &str = "test.oidc.provider";
+
+/// Serves a mock OIDC provider on localhost, for running a prover without an internet connection.
+///
+/// - `MOCK_OIDC_PORT` is the port to listen on, 4445 by default.
+/// - `MOCK_OIDC_ISSUER` is the iss of the JWTs, which must be one of the prover's
+///   `oidc_providers`. Defaults to the one of the local testing configs.
+/// - `CIRCUIT_CONFIG_FILE` is the padding config the nonces are computed with, the default one by
+///   default.
+#[tokio::main]
+async fn main() -> Result<()> {
+    let port = match env::var("MOCK_OIDC_PORT") {
+        Ok(port) => port.parse()?,
+        Err(_) => DEFAULT_PORT,
+    };
+    let issuer = env::var("MOCK_OIDC_ISSUER").unwrap_or_else(|_| String::from(DEFAULT_ISSUER));
+    let circuit_config_path = env::var("CIRCUIT_CONFIG_FILE")
+        .unwrap_or_else(|_| String::from(config::CIRCUIT_CONFIG_FILE_PATH));
+
+    let base_url = format!("http://localhost:{}", port);
+    let provider = Arc::new(MockOidcProvider::new(
+        &issuer,
+        &base_url,
+        read_circuit_config(&circuit_config_path),
+    )?);
+
+    println!("Mock OIDC provider for iss {} at {}", issuer, base_url);
+    println!("  JWK set:      GET  {}/jwks", base_url);
+    println!("  mint a JWT:   POST {}/mint", base_url);
+    println!("  rotate keys:  POST {}/rotate", base_url);
+    println!("  misbehaviour: PUT  {}/misbehaviour", base_url);
+
+    let addr = SocketAddr::from(([127, 0, 0, 1], port));
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    axum::serve(listener, provider.router()).await?;
+    Ok(())
+}
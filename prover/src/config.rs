@@ -1,10 +1,15 @@
 // Copyright © Aptos Foundation
 
+use crate::witness_gen::WitnessGenLimits;
+use anyhow::{anyhow, bail, Result};
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 pub const CONFIG_FILE_PATH: &str = "config.yml";
 pub const LOCAL_TESTING_CONFIG_FILE_PATH: &str = "config_local_testing.yml";
 pub const CONFIG_FILE_PATH_ENVVAR: &str = "CONFIG_FILE";
+/// The padding config used for setups that don't ship their own.
+pub const CIRCUIT_CONFIG_FILE_PATH: &str = "conversion_config.yml";
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 //#[serde(deny_unknown_fields)]
@@ -16,13 +21,46 @@ pub struct ProverServiceConfig {
     pub zkey_filename: String,
     pub test_verification_key_filename: String,
     pub witness_gen_binary_filename: String,
+    #[serde(default)]
+    pub witness_gen_mode: WitnessGenMode,
+    #[serde(default = "default_witness_gen_timeout_secs")]
+    pub witness_gen_timeout_secs: u64,
+    /// Limits the address space of the witness generation subprocess, or the circuit's memory
+    /// when generating witnesses in-process.
+    #[serde(default)]
+    pub witness_gen_max_memory_bytes: Option<u64>,
+    /// Limits the CPU time of the witness generation subprocess.
+    #[serde(default)]
+    pub witness_gen_max_cpu_secs: Option<u64>,
+    #[serde(default)]
+    pub prover_backend: ProverBackendKind,
+    /// Also proves every request with the arkworks backend, and warns if only one of the proofs
+    /// verifies. This keeps a second copy of the proving key in memory, so it is for debugging.
+    #[serde(default)]
+    pub cross_check_prover_backend: bool,
+    /// How many times a witness is proven before giving up, as long as the proofs fail
+    /// verification.
+    #[serde(default = "default_max_proof_attempts")]
+    pub max_proof_attempts: u32,
+    /// After this many requests in a row for which a setup only produced invalid proofs, requests
+    /// for that setup are refused. 0 disables this.
+    #[serde(default = "default_invalid_proof_breaker_threshold")]
+    pub invalid_proof_breaker_threshold: u32,
+    /// While a setup is refusing requests because of invalid proofs, one request is let through
+    /// this often, and the setup serves requests again once one of them gets a valid proof.
+    #[serde(default = "default_invalid_proof_breaker_cooldown_secs")]
+    pub invalid_proof_breaker_cooldown_secs: u64,
 
     pub oidc_providers: Vec<OidcProvider>,
+    /// The uid keys accepted for issuers which don't list their own `uid_keys`.
+    #[serde(default = "default_uid_keys")]
+    pub default_uid_keys: Vec<UidKeyConfig>,
     pub jwk_refresh_rate_secs: u64,
     pub port: u16,
     pub metrics_port: u16,
-    // Whether to log sensitive data
-    pub enable_dangerous_logging: bool,
+    /// If set, the circuit input of every request is kept, encrypted, for debugging.
+    #[serde(default)]
+    pub debug_capture: Option<DebugCaptureConfig>,
     pub enable_debug_checks: bool,
     #[serde(default)]
     pub enable_test_provider: bool,
@@ -32,9 +70,90 @@ pub struct ProverServiceConfig {
     pub disable_iat_in_past_check: bool,
     #[serde(default)]
     pub use_insecure_jwk_for_test: bool,
+    /// Skips proving a built-in request against each setup at startup, which otherwise has to
+    /// pass before the service reports itself ready.
+    #[serde(default)]
+    pub disable_self_test: bool,
+    /// Lets setups that don't ship the `main.circom` they were compiled from start anyway. Their
+    /// circuit config is then only checked against the input size of their witness calculator.
+    #[serde(default)]
+    pub allow_missing_main_circom: bool,
+}
+
+fn default_witness_gen_timeout_secs() -> u64 {
+    10
+}
+
+fn default_max_proof_attempts() -> u32 {
+    3
+}
+
+fn default_invalid_proof_breaker_threshold() -> u32 {
+    5
+}
+
+fn default_invalid_proof_breaker_cooldown_secs() -> u64 {
+    60
+}
+
+fn default_max_captures() -> usize {
+    100
+}
+
+fn default_capture_retention_secs() -> u64 {
+    24 * 60 * 60
+}
+
+fn default_uid_keys() -> Vec<UidKeyConfig> {
+    vec![UidKeyConfig::new("email"), UidKeyConfig::new("sub")]
 }
 
 impl ProverServiceConfig {
+    /// Checks the parts of the config that deserialization alone can't.
+    pub fn validate(&self) -> Result<()> {
+        if self.max_proof_attempts == 0 {
+            bail!("max_proof_attempts must be at least 1");
+        }
+
+        let all_uid_keys = self.default_uid_keys.iter().chain(
+            self.oidc_providers
+                .iter()
+                .filter_map(|provider| provider.uid_keys.as_ref())
+                .flatten(),
+        );
+
+        for uid_key_config in all_uid_keys {
+            // The circuit itself enforces email_verified when the uid key is "email".
+            if uid_key_config.key == "email"
+                && uid_key_config.email_verified_policy() != EmailVerifiedPolicy::Required
+            {
+                bail!("uid key \"email\" must have email_verified policy \"required\"");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the config of `uid_key` if it is allowed for JWTs issued by `iss`.
+    pub fn uid_key_config(&self, iss: &str, uid_key: &str) -> Result<&UidKeyConfig> {
+        self.oidc_providers
+            .iter()
+            .find(|provider| provider.iss == iss)
+            .and_then(|provider| provider.uid_keys.as_ref())
+            .unwrap_or(&self.default_uid_keys)
+            .iter()
+            .find(|uid_key_config| uid_key_config.key == uid_key)
+            .ok_or_else(|| anyhow!("uid key \"{}\" is not allowed for issuer {}", uid_key, iss))
+    }
+
+    pub fn witness_gen_limits(&self) -> WitnessGenLimits {
+        WitnessGenLimits {
+            timeout: Duration::from_secs(self.witness_gen_timeout_secs),
+            max_memory_bytes: self.witness_gen_max_memory_bytes,
+            max_cpu_secs: self.witness_gen_max_cpu_secs,
+        }
+    }
+
     pub fn setup_dir(&self, use_new_setup: bool) -> &String {
         if use_new_setup {
             self.new_setup_dir.as_ref().unwrap()
@@ -95,6 +214,73 @@ impl ProverServiceConfig {
         )
         .into_owned()
     }
+
+    pub fn circuit_config_path(&self, use_new_setup: bool) -> String {
+        shellexpand::tilde(
+            &(String::from(&self.resources_dir)
+                + "/"
+                + self.setup_dir(use_new_setup)
+                + "/"
+                + CIRCUIT_CONFIG_FILE_PATH),
+        )
+        .into_owned()
+    }
+
+    /// The `main.circom` the setup was compiled from, if the setup ships one.
+    pub fn main_circom_path(&self, use_new_setup: bool) -> String {
+        shellexpand::tilde(
+            &(String::from(&self.resources_dir)
+                + "/"
+                + self.setup_dir(use_new_setup)
+                + "/main.circom"),
+        )
+        .into_owned()
+    }
+}
+
+/// How the prover computes witnesses.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WitnessGenMode {
+    /// Runs the setup's `main.wasm` witness calculator in-process.
+    #[default]
+    InProcess,
+    /// Runs the setup's witness generation binary, or `node generate_witness.js` on non-x86
+    /// hosts, in a subprocess.
+    Subprocess,
+}
+
+/// Which Groth16 prover proves witnesses.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProverBackendKind {
+    /// rapidsnark, through `rust-rapidsnark`.
+    #[default]
+    Rapidsnark,
+    /// A pure-Rust prover built on arkworks. It is slower, and holds the whole proving key in
+    /// memory, but doesn't depend on native code.
+    Arkworks,
+}
+
+/// Where to keep debug captures. They are encrypted to `recipient_public_key`, a hex-encoded
+/// x25519 public key, so that only whoever holds the matching private key can read them.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct DebugCaptureConfig {
+    pub dir: String,
+    pub recipient_public_key: String,
+    #[serde(default = "default_max_captures")]
+    pub max_captures: usize,
+    #[serde(default = "default_capture_retention_secs")]
+    pub retention_secs: u64,
+    /// Only capture the requests that failed on our end, rather than every request.
+    #[serde(default)]
+    pub only_failed_requests: bool,
+    /// Also keep the proof, the public inputs and a digest of the witness of every request whose
+    /// proof failed verification. The rest of the witness is left out, as it holds the request's
+    /// secrets.
+    #[serde(default)]
+    pub capture_invalid_proofs: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -102,4 +288,169 @@ impl ProverServiceConfig {
 pub struct OidcProvider {
     pub iss: String,
     pub endpoint_url: String,
+    /// The uid keys accepted for this issuer. If unset, `default_uid_keys` applies.
+    #[serde(default)]
+    pub uid_keys: Option<Vec<UidKeyConfig>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct UidKeyConfig {
+    pub key: String,
+    /// Defaults to `required` for "email" and to `not_required` for every other key.
+    #[serde(default)]
+    pub email_verified: Option<EmailVerifiedPolicy>,
+}
+
+impl UidKeyConfig {
+    pub fn new(key: &str) -> Self {
+        Self {
+            key: String::from(key),
+            email_verified: None,
+        }
+    }
+
+    pub fn email_verified_policy(&self) -> EmailVerifiedPolicy {
+        self.email_verified.unwrap_or(if self.key == "email" {
+            EmailVerifiedPolicy::Required
+        } else {
+            EmailVerifiedPolicy::NotRequired
+        })
+    }
+}
+
+/// Whether a JWT proven against a given uid key must have its email verified.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EmailVerifiedPolicy {
+    /// The JWT must have an `email_verified` claim of `true` or `"true"`, and that field is passed
+    /// to the circuit as the ev field.
+    Required,
+    /// `email_verified` is ignored and the circuit gets a default ev field.
+    NotRequired,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        EmailVerifiedPolicy, OidcProvider, ProverBackendKind, ProverServiceConfig, UidKeyConfig,
+        WitnessGenMode,
+    };
+    use figment::{
+        providers::{Format as _, Yaml},
+        Figment,
+    };
+
+    fn config_with_providers(providers: &str) -> ProverServiceConfig {
+        Figment::new()
+            .merge(Yaml::file(super::LOCAL_TESTING_CONFIG_FILE_PATH))
+            .merge(Yaml::string(providers))
+            .extract()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_uid_key_config_defaults() {
+        let config = config_with_providers(
+            r#"
+oidc_providers:
+  - iss: "https://accounts.google.com"
+    endpoint_url: "https://www.googleapis.com/oauth2/v3/certs"
+"#,
+        );
+
+        assert!(config.validate().is_ok());
+        let email = config
+            .uid_key_config("https://accounts.google.com", "email")
+            .unwrap();
+        assert_eq!(email.email_verified_policy(), EmailVerifiedPolicy::Required);
+        let sub = config
+            .uid_key_config("https://accounts.google.com", "sub")
+            .unwrap();
+        assert_eq!(
+            sub.email_verified_policy(),
+            EmailVerifiedPolicy::NotRequired
+        );
+        assert!(config
+            .uid_key_config("https://accounts.google.com", "oid")
+            .is_err());
+    }
+
+    #[test]
+    fn test_uid_key_config_per_issuer() {
+        let config = config_with_providers(
+            r#"
+oidc_providers:
+  - iss: "https://login.microsoftonline.com/tenant/v2.0"
+    endpoint_url: "https://login.microsoftonline.com/tenant/discovery/v2.0/keys"
+    uid_keys:
+      - key: "oid"
+      - key: "preferred_username"
+        email_verified: required
+"#,
+        );
+
+        assert!(config.validate().is_ok());
+        let iss = "https://login.microsoftonline.com/tenant/v2.0";
+        assert_eq!(
+            config.uid_key_config(iss, "oid").unwrap(),
+            &UidKeyConfig::new("oid")
+        );
+        assert_eq!(
+            config
+                .uid_key_config(iss, "preferred_username")
+                .unwrap()
+                .email_verified_policy(),
+            EmailVerifiedPolicy::Required
+        );
+        // The issuer's list replaces the default one.
+        assert!(config.uid_key_config(iss, "sub").is_err());
+        // Other issuers still get the defaults.
+        assert!(config.uid_key_config("some.other.issuer", "sub").is_ok());
+    }
+
+    #[test]
+    fn test_email_uid_key_must_require_email_verified() {
+        let mut config = config_with_providers("oidc_providers: []");
+        config.oidc_providers.push(OidcProvider {
+            iss: String::from("iss"),
+            endpoint_url: String::from("https://example.com"),
+            uid_keys: Some(vec![UidKeyConfig {
+                key: String::from("email"),
+                email_verified: Some(EmailVerifiedPolicy::NotRequired),
+            }]),
+        });
+
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_witness_gen_mode() {
+        let config = config_with_providers("oidc_providers: []");
+        assert_eq!(config.witness_gen_mode, WitnessGenMode::InProcess);
+
+        let config = config_with_providers("witness_gen_mode: subprocess");
+        assert_eq!(config.witness_gen_mode, WitnessGenMode::Subprocess);
+    }
+
+    #[test]
+    fn test_prover_backend() {
+        let config = config_with_providers("oidc_providers: []");
+        assert_eq!(config.prover_backend, ProverBackendKind::Rapidsnark);
+        assert!(!config.cross_check_prover_backend);
+
+        let config = config_with_providers("prover_backend: arkworks");
+        assert_eq!(config.prover_backend, ProverBackendKind::Arkworks);
+    }
+
+    #[test]
+    fn test_max_proof_attempts() {
+        let config = config_with_providers("oidc_providers: []");
+        assert_eq!(config.max_proof_attempts, 3);
+        assert_eq!(config.invalid_proof_breaker_threshold, 5);
+        assert_eq!(config.invalid_proof_breaker_cooldown_secs, 60);
+
+        let config = config_with_providers("max_proof_attempts: 0");
+        assert!(config.validate().is_err());
+    }
 }
@@ -0,0 +1,534 @@
+// Copyright © Aptos Foundation
+
+use crate::{
+    api::RequestInput,
+    config::DebugCaptureConfig,
+    zkey::{read_witness_len, read_witness_public_inputs},
+};
+use aes_gcm::{
+    aead::{Aead, KeyInit},
+    Aes256Gcm, Nonce,
+};
+use anyhow::{anyhow, bail, Result};
+use aptos_crypto::{x25519, Uniform};
+use aptos_keyless_common::PoseidonHash;
+use aptos_types::jwks::rsa::RSA_JWK;
+use ark_bn254::Fr;
+use axum::http::StatusCode;
+use hkdf::Hkdf;
+use rand::{thread_rng, RngCore};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::{
+    fs,
+    io::{Cursor, ErrorKind},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tracing::warn;
+
+pub const CAPTURE_EXTENSION: &str = "capture";
+pub const INVALID_PROOF_CAPTURE_EXTENSION: &str = "invalid_proof";
+const KDF_INFO: &[u8] = b"keyless prover debug capture v1";
+const NONCE_SIZE: usize = 12;
+/// How often the captures past retention are deleted, besides after every capture.
+pub const RETENTION_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// The stages a request goes through in `prove_handler`.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    /// Checking the JWT's signature and dates.
+    Validation,
+    /// Turning the request into circuit input signals.
+    InputProcessing,
+    WitnessGeneration,
+    Proving,
+}
+
+/// Everything needed to replay a request against a local setup. This holds the pepper, the EPK
+/// blinder and the JWT in the clear, so it must only ever be written out encrypted. It deliberately
+/// doesn't implement `Debug`.
+#[derive(Serialize, Deserialize)]
+pub struct DebugCapture {
+    pub captured_at_secs: u64,
+    pub request: RequestInput,
+    /// The JWK the request was processed with, once it is known.
+    pub jwk: Option<RSA_JWK>,
+    pub use_new_setup: bool,
+    pub circuit_input_signals: Option<Value>,
+    /// The stage the request had reached, which is where it failed if it did.
+    pub stage: Stage,
+    pub failure: Option<CapturedFailure>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CapturedFailure {
+    pub status: u16,
+    pub error: String,
+}
+
+impl DebugCapture {
+    pub fn new(request: RequestInput) -> Self {
+        Self {
+            captured_at_secs: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            request,
+            jwk: None,
+            use_new_setup: false,
+            circuit_input_signals: None,
+            stage: Stage::Validation,
+            failure: None,
+        }
+    }
+
+    /// Reads a capture written by `DebugCapturer::capture`.
+    pub fn read(
+        path: impl AsRef<Path>,
+        recipient_private_key: &x25519::PrivateKey,
+    ) -> Result<Self> {
+        let plaintext = decrypt(recipient_private_key, &fs::read(path)?)?;
+        Ok(serde_json::from_slice(&plaintext)?)
+    }
+}
+
+/// What is kept of a request whose proof failed verification. The witness itself isn't: besides
+/// the private inputs, its intermediate signals hold copies of the JWT and the pepper. Only its
+/// public inputs are, to check them against the public inputs hash, and a digest of it, to check
+/// whether replaying the request's `DebugCapture` computes the same witness. Nothing in here is
+/// secret, but it is written encrypted like the other captures.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InvalidProofCapture {
+    pub captured_at_secs: u64,
+    pub use_new_setup: bool,
+    pub public_inputs_hash: PoseidonHash,
+    /// The first invalid proof, as the prover backend output it.
+    pub proof_json: String,
+    /// The public inputs of the witness, in decimal.
+    pub witness_public_inputs: Vec<String>,
+    /// How many elements the witness has.
+    pub witness_len: u32,
+    /// The SHA-256 of the witness, in the `.wtns` format.
+    pub wtns_sha256: [u8; 32],
+}
+
+impl InvalidProofCapture {
+    /// Keeps what isn't secret of `wtns`, a witness in the `.wtns` format with `n_public` public
+    /// inputs.
+    pub fn new(
+        use_new_setup: bool,
+        public_inputs_hash: PoseidonHash,
+        proof_json: String,
+        wtns: &[u8],
+        n_public: u32,
+    ) -> Result<Self> {
+        Ok(Self {
+            captured_at_secs: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            use_new_setup,
+            public_inputs_hash,
+            proof_json,
+            witness_public_inputs: read_witness_public_inputs(Cursor::new(wtns), n_public)?
+                .iter()
+                .map(Fr::to_string)
+                .collect(),
+            witness_len: read_witness_len(Cursor::new(wtns))?,
+            wtns_sha256: Sha256::digest(wtns).into(),
+        })
+    }
+
+    /// Reads a capture written by `DebugCapturer::capture_invalid_proof`.
+    pub fn read(
+        path: impl AsRef<Path>,
+        recipient_private_key: &x25519::PrivateKey,
+    ) -> Result<Self> {
+        let plaintext = decrypt(recipient_private_key, &fs::read(path)?)?;
+        Ok(serde_json::from_slice(&plaintext)?)
+    }
+}
+
+/// Writes encrypted debug captures to a directory, keeping at most `max_captures` of them and
+/// none older than `retention`.
+#[derive(Clone)]
+pub struct DebugCapturer {
+    dir: PathBuf,
+    recipient_public_key: x25519::PublicKey,
+    max_captures: usize,
+    retention: Duration,
+    only_failed_requests: bool,
+    capture_invalid_proofs: bool,
+}
+
+impl DebugCapturer {
+    pub fn new(config: &DebugCaptureConfig) -> Result<Self> {
+        let key_bytes = hex::decode(config.recipient_public_key.trim_start_matches("0x"))?;
+        let recipient_public_key = x25519::PublicKey::try_from(key_bytes.as_slice())
+            .map_err(|e| anyhow!("Invalid debug capture recipient public key: {}", e))?;
+        fs::create_dir_all(&config.dir)?;
+
+        Ok(Self {
+            dir: PathBuf::from(&config.dir),
+            recipient_public_key,
+            max_captures: config.max_captures,
+            retention: Duration::from_secs(config.retention_secs),
+            only_failed_requests: config.only_failed_requests,
+            capture_invalid_proofs: config.capture_invalid_proofs,
+        })
+    }
+
+    /// Whether a request should be captured, given how it ended. Requests rejected as bad are
+    /// never captured when only capturing failures: they can't be fixed on our end, and anybody
+    /// could fill the capture directory with them.
+    pub fn should_capture(&self, status: Option<StatusCode>) -> bool {
+        match status {
+            Some(status) => !self.only_failed_requests || status.is_server_error(),
+            None => !self.only_failed_requests,
+        }
+    }
+
+    pub fn captures_invalid_proofs(&self) -> bool {
+        self.capture_invalid_proofs
+    }
+
+    /// Encrypts and writes `capture`, then deletes the captures that are past retention. Returns
+    /// the path of the new capture.
+    pub fn capture(&self, capture: &DebugCapture) -> Result<PathBuf> {
+        self.write(
+            &serde_json::to_vec(capture)?,
+            CAPTURE_EXTENSION,
+            self.max_captures,
+        )
+    }
+
+    /// Like `capture`, for an invalid proof. These are kept apart from the other captures, and
+    /// don't take their place.
+    pub fn capture_invalid_proof(&self, capture: &InvalidProofCapture) -> Result<PathBuf> {
+        self.write(
+            &serde_json::to_vec(capture)?,
+            INVALID_PROOF_CAPTURE_EXTENSION,
+            self.max_captures,
+        )
+    }
+
+    /// `capture` on a blocking thread, since it writes to and lists the capture directory.
+    pub async fn capture_async(&self, capture: DebugCapture) -> Result<PathBuf> {
+        let debug_capturer = self.clone();
+        tokio::task::spawn_blocking(move || debug_capturer.capture(&capture)).await?
+    }
+
+    /// `capture_invalid_proof` on a blocking thread.
+    pub async fn capture_invalid_proof_async(
+        &self,
+        capture: InvalidProofCapture,
+    ) -> Result<PathBuf> {
+        let debug_capturer = self.clone();
+        tokio::task::spawn_blocking(move || debug_capturer.capture_invalid_proof(&capture)).await?
+    }
+
+    /// Deletes the captures past retention every `RETENTION_INTERVAL`, starting now, so that they
+    /// also expire while nothing is captured.
+    pub fn start_retention_loop(&self) {
+        let debug_capturer = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(RETENTION_INTERVAL);
+            loop {
+                interval.tick().await;
+                let debug_capturer = debug_capturer.clone();
+                let result = tokio::task::spawn_blocking(move || {
+                    [CAPTURE_EXTENSION, INVALID_PROOF_CAPTURE_EXTENSION]
+                        .into_iter()
+                        .try_for_each(|extension| {
+                            debug_capturer.enforce_retention(extension, debug_capturer.max_captures)
+                        })
+                })
+                .await
+                .unwrap_or_else(|e| Err(e.into()));
+                if let Err(e) = result {
+                    warn!("Couldn't delete old debug captures: {:?}", e);
+                }
+            }
+        });
+    }
+
+    fn write(&self, plaintext: &[u8], extension: &str, max_captures: usize) -> Result<PathBuf> {
+        let ciphertext = encrypt(&self.recipient_public_key, plaintext)?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?;
+        let path = self.dir.join(format!(
+            "{}-{:08x}.{}",
+            now.as_millis(),
+            thread_rng().next_u32(),
+            extension
+        ));
+        fs::write(&path, ciphertext)?;
+
+        self.enforce_retention(extension, max_captures)?;
+        Ok(path)
+    }
+
+    /// Deletes the captures with `extension` that are past retention. Captures that are gone by
+    /// the time they would be deleted, e.g. because a concurrent capture deleted them, are skipped.
+    fn enforce_retention(&self, extension: &str, max_captures: usize) -> Result<()> {
+        let mut captures = vec![];
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            if path.extension() != Some(extension.as_ref()) {
+                continue;
+            }
+            match entry.metadata().and_then(|metadata| metadata.modified()) {
+                Ok(modified) => captures.push((modified, path)),
+                Err(e) if e.kind() == ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        // Oldest first.
+        captures.sort();
+
+        let now = SystemTime::now();
+        let excess = captures.len().saturating_sub(max_captures);
+        for (i, (modified, path)) in captures.iter().enumerate() {
+            let expired = now
+                .duration_since(*modified)
+                .is_ok_and(|age| age > self.retention);
+            if i < excess || expired {
+                match fs::remove_file(path) {
+                    Err(e) if e.kind() != ErrorKind::NotFound => return Err(e.into()),
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Encrypts `plaintext` so that only the holder of the private key matching
+/// `recipient_public_key` can read it: an ephemeral x25519 key agreement, HKDF-SHA256 and
+/// AES-256-GCM. The output is the ephemeral public key, followed by the nonce and the ciphertext.
+pub fn encrypt(recipient_public_key: &x25519::PublicKey, plaintext: &[u8]) -> Result<Vec<u8>> {
+    let mut rng = thread_rng();
+    let ephemeral_private_key = x25519::PrivateKey::generate(&mut rng);
+    let ephemeral_public_key = ephemeral_private_key.public_key();
+    let cipher = derive_cipher(
+        &ephemeral_private_key.diffie_hellman(recipient_public_key),
+        &ephemeral_public_key,
+        recipient_public_key,
+    )?;
+
+    let mut nonce = [0u8; NONCE_SIZE];
+    rng.fill_bytes(&mut nonce);
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), plaintext)
+        .map_err(|_| anyhow!("Couldn't encrypt debug capture"))?;
+
+    Ok([ephemeral_public_key.as_slice(), &nonce, &ciphertext].concat())
+}
+
+pub fn decrypt(recipient_private_key: &x25519::PrivateKey, data: &[u8]) -> Result<Vec<u8>> {
+    if data.len() < x25519::PUBLIC_KEY_SIZE + NONCE_SIZE {
+        bail!("Debug capture is truncated");
+    }
+    let (ephemeral_public_key, rest) = data.split_at(x25519::PUBLIC_KEY_SIZE);
+    let (nonce, ciphertext) = rest.split_at(NONCE_SIZE);
+    let ephemeral_public_key = x25519::PublicKey::try_from(ephemeral_public_key)
+        .map_err(|e| anyhow!("Invalid ephemeral public key in debug capture: {}", e))?;
+
+    let cipher = derive_cipher(
+        &recipient_private_key.diffie_hellman(&ephemeral_public_key),
+        &ephemeral_public_key,
+        &recipient_private_key.public_key(),
+    )?;
+    cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| anyhow!("Couldn't decrypt debug capture, is this the right key?"))
+}
+
+fn derive_cipher(
+    shared_secret: &[u8],
+    ephemeral_public_key: &x25519::PublicKey,
+    recipient_public_key: &x25519::PublicKey,
+) -> Result<Aes256Gcm> {
+    let salt = [
+        ephemeral_public_key.as_slice(),
+        recipient_public_key.as_slice(),
+    ]
+    .concat();
+    let mut key = [0u8; 32];
+    Hkdf::<Sha256>::new(Some(&salt), shared_secret)
+        .expand(KDF_INFO, &mut key)
+        .map_err(|e| anyhow!("{}", e))?;
+    Ok(Aes256Gcm::new(&key.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        decrypt, encrypt, DebugCapture, DebugCapturer, InvalidProofCapture, Stage,
+        CAPTURE_EXTENSION, INVALID_PROOF_CAPTURE_EXTENSION,
+    };
+    use crate::config::DebugCaptureConfig;
+    use crate::tests::common::{
+        gen_test_jwk_keypair, get_test_pepper,
+        mock::write_mock_witness,
+        types::{ProofTestCase, TestJWTPayload},
+    };
+    use aptos_crypto::{x25519, Uniform};
+    use ark_bn254::Fr;
+    use ark_ff::One;
+    use axum::http::StatusCode;
+    use rand::thread_rng;
+    use serde_json::json;
+    use sha2::{Digest, Sha256};
+    use std::{
+        fs,
+        time::{Duration, SystemTime},
+    };
+
+    #[test]
+    fn test_encryption_roundtrip() {
+        let private_key = x25519::PrivateKey::generate(&mut thread_rng());
+        let other_private_key = x25519::PrivateKey::generate(&mut thread_rng());
+
+        let mut data = encrypt(&private_key.public_key(), b"pepper").unwrap();
+        assert!(!data.windows(6).any(|window| window == b"pepper"));
+        assert_eq!(decrypt(&private_key, &data).unwrap(), b"pepper");
+        assert!(decrypt(&other_private_key, &data).is_err());
+
+        let last = data.len() - 1;
+        data[last] ^= 1;
+        assert!(decrypt(&private_key, &data).is_err());
+    }
+
+    fn test_capture() -> DebugCapture {
+        let request = ProofTestCase::default_with_payload(TestJWTPayload::default())
+            .convert_to_prover_request(&gen_test_jwk_keypair());
+        DebugCapture {
+            stage: Stage::WitnessGeneration,
+            ..DebugCapture::new(request)
+        }
+    }
+
+    fn test_capturer(dir: &str, private_key: &x25519::PrivateKey) -> DebugCapturer {
+        DebugCapturer::new(&DebugCaptureConfig {
+            dir: String::from(dir),
+            recipient_public_key: hex::encode(private_key.public_key().as_slice()),
+            max_captures: 2,
+            retention_secs: 3600,
+            only_failed_requests: true,
+            capture_invalid_proofs: true,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_should_capture() {
+        let dir = tempfile::tempdir().unwrap();
+        let private_key = x25519::PrivateKey::generate(&mut thread_rng());
+        let mut capturer = test_capturer(dir.path().to_str().unwrap(), &private_key);
+
+        assert!(capturer.should_capture(Some(StatusCode::INTERNAL_SERVER_ERROR)));
+        assert!(capturer.should_capture(Some(StatusCode::GATEWAY_TIMEOUT)));
+        assert!(!capturer.should_capture(Some(StatusCode::BAD_REQUEST)));
+        assert!(!capturer.should_capture(None));
+
+        capturer.only_failed_requests = false;
+        assert!(capturer.should_capture(Some(StatusCode::BAD_REQUEST)));
+        assert!(capturer.should_capture(None));
+    }
+
+    #[test]
+    fn test_capture_retention() {
+        let dir = tempfile::tempdir().unwrap();
+        let private_key = x25519::PrivateKey::generate(&mut thread_rng());
+        let capturer = test_capturer(dir.path().to_str().unwrap(), &private_key);
+
+        let mut capture = test_capture();
+        let paths: Vec<_> = (0..3)
+            .map(|i| {
+                capture.circuit_input_signals = Some(json!({ "pepper": i.to_string() }));
+                capturer.capture(&capture).unwrap()
+            })
+            .collect();
+
+        let remaining = fs::read_dir(dir.path())
+            .unwrap()
+            .filter(|entry| {
+                entry.as_ref().unwrap().path().extension() == Some(CAPTURE_EXTENSION.as_ref())
+            })
+            .count();
+        assert_eq!(remaining, 2);
+        assert!(!paths[0].exists());
+
+        let capture = DebugCapture::read(&paths[2], &private_key).unwrap();
+        assert_eq!(
+            capture.circuit_input_signals,
+            Some(json!({ "pepper": "2" }))
+        );
+        assert_eq!(capture.stage, Stage::WitnessGeneration);
+        assert_eq!(capture.request.pepper.expose_secret(), &get_test_pepper());
+    }
+
+    #[tokio::test]
+    async fn test_retention_loop_deletes_expired_captures() {
+        let dir = tempfile::tempdir().unwrap();
+        let private_key = x25519::PrivateKey::generate(&mut thread_rng());
+        let capturer = test_capturer(dir.path().to_str().unwrap(), &private_key);
+        let path = capturer.capture_async(test_capture()).await.unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(7200))
+            .unwrap();
+
+        capturer.start_retention_loop();
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while path.exists() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("The expired capture wasn't deleted");
+    }
+
+    #[test]
+    fn test_invalid_proof_captures() {
+        let dir = tempfile::tempdir().unwrap();
+        let private_key = x25519::PrivateKey::generate(&mut thread_rng());
+        let capturer = test_capturer(dir.path().to_str().unwrap(), &private_key);
+
+        let request_capture = capturer.capture(&test_capture()).unwrap();
+        let secret = Fr::from(0x5ec7e7_5ec7e7_5ec7e7_5ec7e7u128);
+        let wtns = write_mock_witness(&[Fr::one(), Fr::from(42u64), secret]);
+        let paths: Vec<_> = (0..3)
+            .map(|_| {
+                let capture =
+                    InvalidProofCapture::new(true, [42; 32], String::from("{}"), &wtns, 1).unwrap();
+                capturer.capture_invalid_proof(&capture).unwrap()
+            })
+            .collect();
+
+        // They don't take the place of request captures.
+        assert!(request_capture.exists());
+        assert!(!paths[0].exists());
+        assert_eq!(
+            paths[2].extension(),
+            Some(INVALID_PROOF_CAPTURE_EXTENSION.as_ref())
+        );
+
+        let plaintext =
+            String::from_utf8(decrypt(&private_key, &fs::read(&paths[2]).unwrap()).unwrap())
+                .unwrap();
+        assert!(!plaintext.contains(&secret.to_string()));
+        let capture = InvalidProofCapture::read(&paths[2], &private_key).unwrap();
+        assert!(capture.use_new_setup);
+        assert_eq!(capture.witness_public_inputs, vec![String::from("42")]);
+        assert_eq!(capture.witness_len, 3);
+        assert_eq!(capture.wtns_sha256, <[u8; 32]>::from(Sha256::digest(&wtns)));
+        assert!(DebugCapture::read(&paths[2], &private_key).is_err());
+    }
+}
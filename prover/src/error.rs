@@ -1,6 +1,9 @@
 // Copyright © Aptos Foundation
 
-use crate::{api::ProverServiceResponse, logging};
+use crate::{
+    api::ProverServiceResponse, logging, prover_backend::ProverBackendError,
+    witness_gen::WitnessGenError,
+};
 use axum::{extract::rejection::JsonRejection, http::StatusCode, response::IntoResponse, Json};
 use rust_rapidsnark::ProverError;
 use tracing::{error, warn};
@@ -153,6 +156,20 @@ pub fn handle_prover_lib_error(e: ProverError) -> ErrorWithCode {
     }
 }
 
+pub fn handle_prover_backend_error(e: ProverBackendError) -> ErrorWithCode {
+    match e {
+        ProverBackendError::Rapidsnark(e) => handle_prover_lib_error(e),
+        ProverBackendError::Failed(e) => server_error(e),
+    }
+}
+
+pub fn handle_witness_gen_error(e: WitnessGenError) -> ErrorWithCode {
+    ErrorWithCode {
+        code: Some(e.status_code()),
+        error: e.into(),
+    }
+}
+
 #[macro_export]
 macro_rules! bail {
     ($msg:literal $(,)?) => {
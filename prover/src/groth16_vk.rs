@@ -1,26 +1,35 @@
 use crate::watcher::ExternalResource;
-use anyhow::{anyhow, Result};
-use ark_bn254::{Fq, Fq2, G1Projective, G2Projective};
+use anyhow::{anyhow, bail, Context, Result};
+use ark_bn254::{Bn254, Fq, Fq2, G1Affine, G1Projective, G2Affine, G2Projective};
+use ark_ec::{
+    short_weierstrass::{Affine, SWCurveConfig},
+    CurveGroup,
+};
 use ark_ff::PrimeField;
+use ark_groth16::{PreparedVerifyingKey, VerifyingKey};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
 use num_bigint::BigUint;
 use num_traits::Num;
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
-#[cfg(test)]
-use std::fs::File;
-#[cfg(test)]
-use std::io::Write;
-use std::sync::{Arc, RwLock};
+use serde_json::Value;
+use std::{
+    fs,
+    path::Path,
+    sync::{Arc, RwLock},
+};
 
 //
-// Below are some utils for converting a VK from snarkjs to its on-chain representation.
+// Below are some utils for decoding a VK from snarkjs.
 //
 
 type SnarkJsFqRepr = String;
 fn try_as_fq(repr: &SnarkJsFqRepr) -> Result<Fq> {
     let val = BigUint::from_str_radix(repr.as_str(), 10)?;
-    let bytes = val.to_bytes_be();
-    Ok(Fq::from_be_bytes_mod_order(bytes.as_slice()))
+    if val >= BigUint::from(Fq::MODULUS) {
+        bail!("{} is not a base field element", repr);
+    }
+    Ok(Fq::from_be_bytes_mod_order(val.to_bytes_be().as_slice()))
 }
 
 type SnarkJsFq2Repr = [SnarkJsFqRepr; 2];
@@ -31,22 +40,34 @@ fn try_as_fq2(repr: &SnarkJsFq2Repr) -> Result<Fq2> {
 }
 
 type SnarkJsG1Repr = [SnarkJsFqRepr; 3];
-fn try_as_g1_proj(repr: &SnarkJsG1Repr) -> Result<G1Projective> {
+pub(crate) fn try_as_g1(repr: &SnarkJsG1Repr) -> Result<G1Affine> {
     let a = try_as_fq(&repr[0])?;
     let b = try_as_fq(&repr[1])?;
     let c = try_as_fq(&repr[2])?;
-    Ok(G1Projective::new(a, b, c))
+    check_point(G1Projective::new_unchecked(a, b, c).into_affine())
 }
 
 type SnarkJsG2Repr = [SnarkJsFq2Repr; 3];
-fn try_as_g2_proj(repr: &SnarkJsG2Repr) -> Result<G2Projective> {
+pub(crate) fn try_as_g2(repr: &SnarkJsG2Repr) -> Result<G2Affine> {
     let a = try_as_fq2(&repr[0])?;
     let b = try_as_fq2(&repr[1])?;
     let c = try_as_fq2(&repr[2])?;
-    Ok(G2Projective::new(a, b, c))
+    check_point(G2Projective::new_unchecked(a, b, c).into_affine())
+}
+
+/// `Projective::new` panics on invalid points, so points are built unchecked and checked here.
+pub(crate) fn check_point<P: SWCurveConfig>(point: Affine<P>) -> Result<Affine<P>> {
+    if !point.is_on_curve() {
+        bail!("the point is not on the curve");
+    }
+    if !point.is_in_correct_subgroup_assuming_on_curve() {
+        bail!("the point is not in the prime order subgroup");
+    }
+    Ok(point)
 }
 
-#[derive(Deserialize, Serialize)]
+/// The `verification_key.json` output by snarkjs, with points in decimal projective coordinates.
+#[derive(Clone, Deserialize, Serialize)]
 pub struct SnarkJsGroth16VerificationKey {
     vk_alpha_1: SnarkJsG1Repr,
     vk_beta_2: SnarkJsG2Repr,
@@ -56,48 +77,6 @@ pub struct SnarkJsGroth16VerificationKey {
     ic: Vec<SnarkJsG1Repr>,
 }
 
-#[test]
-fn test_local_vk_load_convert() {
-    let local_vk_json = include_str!("../resources/202405_vk.vkey");
-    let local_vk: SnarkJsGroth16VerificationKey = serde_json::from_str(local_vk_json).unwrap();
-
-    // The VK we currently use on chain.
-    // For the full setup details, see https://github.com/aptos-labs/aptos-keyless-trusted-setup-contributions-may-2024.
-    let expected = OnChainGroth16VerificationKey {
-        r#type: "0x1::keyless_account::Groth16VerificationKey".to_string(),
-        data: VKeyData {
-            alpha_g1: "0xe2f26dbea299f5223b646cb1fb33eadb059d9407559d7441dfd902e3a79a4d2d".to_string(),
-            beta_g2: "0xabb73dc17fbc13021e2471e0c08bd67d8401f52b73d6d07483794cad4778180e0c06f33bbc4c79a9cadef253a68084d382f17788f885c9afd176f7cb2f036789".to_string(),
-            delta_g2: "0x6176de7d77e614e09ef5e8e19cbf785ffed405d6531cee13cd71a46e2b4ef30deb18f6976c172bdcd7ea8ab2b509991bb5ce34f9fbb42486b78aac62a894a480".to_string(),
-            gamma_abc_g1: vec![
-                "0x7e92d0c6818f2e51248cd1e8e82eb14521d990b0bb155ab0e3cf99b888bc5387".to_string(),
-                "0xbe1ad9f5fec081770956f846e1d0ea97219a3f6499acc33e1a67aef6d6e16898".to_string(),
-            ],
-            gamma_g2: "0xedf692d95cbdde46ddda5ef7d422436779445c5e66006a42761e1f12efde0018c212f3aeb785e49712e7a9353349aaf1255dfb31b7bf60723a480d9293938e19".to_string(),
-        },
-    };
-    let actual = local_vk.try_as_onchain_repr().unwrap();
-    assert_eq!(expected, actual);
-}
-
-/// This is not a UT, but a tool to convert a .vkey to its on-chain representation and save in a file.
-#[test]
-fn groth16_vk_rewriter() {
-    if let (Ok(path_in), Ok(path_out)) = (
-        std::env::var("LOCAL_VK_IN"),
-        std::env::var("ONCHAIN_VK_OUT"),
-    ) {
-        let local_vk_json = std::fs::read_to_string(path_in.as_str()).unwrap();
-        let local_vk: SnarkJsGroth16VerificationKey = serde_json::from_str(&local_vk_json).unwrap();
-        let onchain_vk = local_vk.try_as_onchain_repr().unwrap();
-        let json_out = serde_json::to_string_pretty(&onchain_vk).unwrap();
-        File::create(path_out)
-            .unwrap()
-            .write_all(json_out.as_bytes())
-            .unwrap();
-    }
-}
-
 //
 // Utils end.
 //
@@ -131,52 +110,249 @@ pub struct VKeyData {
     pub gamma_g2: String,
 }
 
-impl SnarkJsGroth16VerificationKey {
-    pub fn try_as_onchain_repr(&self) -> Result<OnChainGroth16VerificationKey> {
+/// A Groth16 verification key, decoded from any of the formats it is found in: the snarkjs
+/// `verification_key.json`, the on-chain [`VKeyData`], and arkworks' canonical bytes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Groth16VerificationKey {
+    vk: VerifyingKey<Bn254>,
+}
+
+impl Groth16VerificationKey {
+    pub fn from_snarkjs(snarkjs_vk: &SnarkJsGroth16VerificationKey) -> Result<Self> {
         let SnarkJsGroth16VerificationKey {
             vk_alpha_1,
             vk_beta_2,
             vk_gamma_2,
             vk_delta_2,
             ic,
-        } = self;
-        let alpha_g1 =
-            try_as_g1_proj(vk_alpha_1).map_err(|e| anyhow!("alpha_g1 decoding error: {e}"))?;
-        let beta_g2 =
-            try_as_g2_proj(vk_beta_2).map_err(|e| anyhow!("beta_g2 decoding error: {e}"))?;
-        let delta_g2 =
-            try_as_g2_proj(vk_delta_2).map_err(|e| anyhow!("delta_g2 decoding error: {e}"))?;
-        let gamma_abc_g1_0 =
-            try_as_g1_proj(&ic[0]).map_err(|e| anyhow!("gamma_abc_g1[0] decoding error: {e}"))?;
-        let gamma_abc_g1_1 =
-            try_as_g1_proj(&ic[1]).map_err(|e| anyhow!("gamma_abc_g1[1] decoding error: {e}"))?;
-        let gamma_g2 =
-            try_as_g2_proj(vk_gamma_2).map_err(|e| anyhow!("gamma_g2 decoding error: {e}"))?;
+        } = snarkjs_vk;
+        Self::new(VerifyingKey {
+            alpha_g1: try_as_g1(vk_alpha_1).map_err(|e| anyhow!("alpha_g1 decoding error: {e}"))?,
+            beta_g2: try_as_g2(vk_beta_2).map_err(|e| anyhow!("beta_g2 decoding error: {e}"))?,
+            gamma_g2: try_as_g2(vk_gamma_2).map_err(|e| anyhow!("gamma_g2 decoding error: {e}"))?,
+            delta_g2: try_as_g2(vk_delta_2).map_err(|e| anyhow!("delta_g2 decoding error: {e}"))?,
+            gamma_abc_g1: ic
+                .iter()
+                .enumerate()
+                .map(|(i, point)| {
+                    try_as_g1(point).map_err(|e| anyhow!("gamma_abc_g1[{i}] decoding error: {e}"))
+                })
+                .collect::<Result<_>>()?,
+        })
+    }
+
+    pub fn from_onchain(data: &VKeyData) -> Result<Self> {
+        let VKeyData {
+            alpha_g1,
+            beta_g2,
+            delta_g2,
+            gamma_abc_g1,
+            gamma_g2,
+        } = data;
+        Self::new(VerifyingKey {
+            alpha_g1: from_onchain_repr(alpha_g1)
+                .map_err(|e| anyhow!("alpha_g1 decoding error: {e}"))?,
+            beta_g2: from_onchain_repr(beta_g2)
+                .map_err(|e| anyhow!("beta_g2 decoding error: {e}"))?,
+            gamma_g2: from_onchain_repr(gamma_g2)
+                .map_err(|e| anyhow!("gamma_g2 decoding error: {e}"))?,
+            delta_g2: from_onchain_repr(delta_g2)
+                .map_err(|e| anyhow!("delta_g2 decoding error: {e}"))?,
+            gamma_abc_g1: gamma_abc_g1
+                .iter()
+                .enumerate()
+                .map(|(i, point)| {
+                    from_onchain_repr(point)
+                        .map_err(|e| anyhow!("gamma_abc_g1[{i}] decoding error: {e}"))
+                })
+                .collect::<Result<_>>()?,
+        })
+    }
+
+    /// Decodes a `VerifyingKey<Bn254>` serialized compressed, as arkworks does.
+    pub fn from_canonical_bytes(bytes: &[u8]) -> Result<Self> {
+        Self::new(VerifyingKey::deserialize_compressed(bytes)?)
+    }
+
+    /// Decodes a snarkjs VK, an on-chain `0x1::keyless_account::Groth16VerificationKey` resource,
+    /// or just its `data`.
+    pub fn from_json(json: Value) -> Result<Self> {
+        if json.get("vk_alpha_1").is_some() {
+            Self::from_snarkjs(&serde_json::from_value(json)?)
+        } else if let Some(data) = json.get("data") {
+            Self::from_onchain(&serde_json::from_value(data.clone())?)
+        } else {
+            Self::from_onchain(&serde_json::from_value(json)?)
+        }
+    }
+
+    /// Reads a VK from a file in any of the formats above. Files that aren't JSON are read as
+    /// canonical bytes.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let bytes = fs::read(path).with_context(|| format!("Unable to read {}", path.display()))?;
+        match serde_json::from_slice(&bytes) {
+            Ok(json) => Self::from_json(json),
+            Err(_) => Self::from_canonical_bytes(&bytes),
+        }
+        .with_context(|| format!("Couldn't decode the VK in {}", path.display()))
+    }
+
+    fn new(vk: VerifyingKey<Bn254>) -> Result<Self> {
+        if vk.gamma_abc_g1.is_empty() {
+            bail!("The VK has no gamma_abc_g1 points");
+        }
+        Ok(Self { vk })
+    }
 
+    pub fn num_public_inputs(&self) -> usize {
+        self.vk.gamma_abc_g1.len() - 1
+    }
+
+    /// Does the pairing precomputation, so the result should be kept rather than recomputed for
+    /// every proof.
+    pub fn prepared_vk(&self) -> PreparedVerifyingKey<Bn254> {
+        PreparedVerifyingKey::from(self.vk.clone())
+    }
+
+    pub fn try_as_onchain_repr(&self) -> Result<OnChainGroth16VerificationKey> {
+        let VerifyingKey {
+            alpha_g1,
+            beta_g2,
+            gamma_g2,
+            delta_g2,
+            gamma_abc_g1,
+        } = &self.vk;
         Ok(OnChainGroth16VerificationKey {
             r#type: "0x1::keyless_account::Groth16VerificationKey".to_string(),
             data: VKeyData {
-                alpha_g1: as_onchain_repr(&alpha_g1)
+                alpha_g1: as_onchain_repr(alpha_g1)
                     .map_err(|e| anyhow!("alpha_g1 re-encoding error: {e}"))?,
-                beta_g2: as_onchain_repr(&beta_g2)
+                beta_g2: as_onchain_repr(beta_g2)
                     .map_err(|e| anyhow!("beta_g2 re-encoding error: {e}"))?,
-                delta_g2: as_onchain_repr(&delta_g2)
+                delta_g2: as_onchain_repr(delta_g2)
                     .map_err(|e| anyhow!("delta_g2 re-encoding error: {e}"))?,
-                gamma_abc_g1: vec![
-                    as_onchain_repr(&gamma_abc_g1_0)
-                        .map_err(|e| anyhow!("gamma_abc_g1[0] re-encoding error: {e}"))?,
-                    as_onchain_repr(&gamma_abc_g1_1)
-                        .map_err(|e| anyhow!("gamma_abc_g1[1] re-encoding error: {e}"))?,
-                ],
-                gamma_g2: as_onchain_repr(&gamma_g2)
+                gamma_abc_g1: gamma_abc_g1
+                    .iter()
+                    .enumerate()
+                    .map(|(i, point)| {
+                        as_onchain_repr(point)
+                            .map_err(|e| anyhow!("gamma_abc_g1[{i}] re-encoding error: {e}"))
+                    })
+                    .collect::<Result<_>>()?,
+                gamma_g2: as_onchain_repr(gamma_g2)
                     .map_err(|e| anyhow!("gamma_g2 re-encoding error: {e}"))?,
             },
         })
     }
+
+    pub fn to_canonical_bytes(&self) -> Result<Vec<u8>> {
+        let mut buf = vec![];
+        self.vk.serialize_compressed(&mut buf)?;
+        Ok(buf)
+    }
 }
 
-fn as_onchain_repr<T: ark_serialize::CanonicalSerialize>(point: &T) -> Result<String> {
+fn as_onchain_repr<T: CanonicalSerialize>(point: &T) -> Result<String> {
     let mut buf = vec![];
     point.serialize_compressed(&mut buf)?;
     Ok(format!("0x{}", hex::encode(buf)))
 }
+
+fn from_onchain_repr<T: CanonicalDeserialize>(repr: &str) -> Result<T> {
+    let bytes = hex::decode(repr.trim_start_matches("0x"))?;
+    Ok(T::deserialize_compressed(bytes.as_slice())?)
+}
+
+#[test]
+fn test_local_vk_load_convert() {
+    let local_vk_json = include_str!("../resources/202405_vk.vkey");
+    let local_vk: SnarkJsGroth16VerificationKey = serde_json::from_str(local_vk_json).unwrap();
+
+    // The VK we currently use on chain.
+    // For the full setup details, see https://github.com/aptos-labs/aptos-keyless-trusted-setup-contributions-may-2024.
+    let expected = OnChainGroth16VerificationKey {
+        r#type: "0x1::keyless_account::Groth16VerificationKey".to_string(),
+        data: VKeyData {
+            alpha_g1: "0xe2f26dbea299f5223b646cb1fb33eadb059d9407559d7441dfd902e3a79a4d2d".to_string(),
+            beta_g2: "0xabb73dc17fbc13021e2471e0c08bd67d8401f52b73d6d07483794cad4778180e0c06f33bbc4c79a9cadef253a68084d382f17788f885c9afd176f7cb2f036789".to_string(),
+            delta_g2: "0x6176de7d77e614e09ef5e8e19cbf785ffed405d6531cee13cd71a46e2b4ef30deb18f6976c172bdcd7ea8ab2b509991bb5ce34f9fbb42486b78aac62a894a480".to_string(),
+            gamma_abc_g1: vec![
+                "0x7e92d0c6818f2e51248cd1e8e82eb14521d990b0bb155ab0e3cf99b888bc5387".to_string(),
+                "0xbe1ad9f5fec081770956f846e1d0ea97219a3f6499acc33e1a67aef6d6e16898".to_string(),
+            ],
+            gamma_g2: "0xedf692d95cbdde46ddda5ef7d422436779445c5e66006a42761e1f12efde0018c212f3aeb785e49712e7a9353349aaf1255dfb31b7bf60723a480d9293938e19".to_string(),
+        },
+    };
+    let actual = Groth16VerificationKey::from_snarkjs(&local_vk)
+        .unwrap()
+        .try_as_onchain_repr()
+        .unwrap();
+    assert_eq!(expected, actual);
+}
+
+#[test]
+fn test_vk_formats_roundtrip() {
+    let vk = Groth16VerificationKey::from_file("resources/202405_vk.vkey").unwrap();
+    assert_eq!(vk.num_public_inputs(), 1);
+
+    let onchain_vk = vk.try_as_onchain_repr().unwrap();
+    assert_eq!(
+        Groth16VerificationKey::from_onchain(&onchain_vk.data).unwrap(),
+        vk
+    );
+    assert_eq!(
+        Groth16VerificationKey::from_json(serde_json::to_value(&onchain_vk).unwrap()).unwrap(),
+        vk
+    );
+    let bytes = vk.to_canonical_bytes().unwrap();
+    assert_eq!(
+        Groth16VerificationKey::from_canonical_bytes(&bytes).unwrap(),
+        vk
+    );
+}
+
+#[test]
+fn test_vk_any_number_of_public_inputs() {
+    let mut snarkjs_vk: SnarkJsGroth16VerificationKey =
+        serde_json::from_str(include_str!("../resources/202405_vk.vkey")).unwrap();
+    snarkjs_vk.ic.push(snarkjs_vk.ic[1].clone());
+    snarkjs_vk.ic.push(snarkjs_vk.ic[0].clone());
+
+    let vk = Groth16VerificationKey::from_snarkjs(&snarkjs_vk).unwrap();
+    assert_eq!(vk.num_public_inputs(), 3);
+    let onchain_vk = vk.try_as_onchain_repr().unwrap();
+    assert_eq!(onchain_vk.data.gamma_abc_g1.len(), 4);
+    assert_eq!(
+        Groth16VerificationKey::from_onchain(&onchain_vk.data).unwrap(),
+        vk
+    );
+}
+
+#[test]
+fn test_vk_invalid_points() {
+    let snarkjs_vk: SnarkJsGroth16VerificationKey =
+        serde_json::from_str(include_str!("../resources/202405_vk.vkey")).unwrap();
+
+    let mut off_curve = snarkjs_vk.clone();
+    off_curve.vk_alpha_1[1] = "1".to_string();
+    let err = Groth16VerificationKey::from_snarkjs(&off_curve).unwrap_err();
+    assert!(err.to_string().contains("alpha_g1"));
+
+    let mut too_large = snarkjs_vk.clone();
+    too_large.vk_beta_2[0][0] = Fq::MODULUS.to_string();
+    let err = Groth16VerificationKey::from_snarkjs(&too_large).unwrap_err();
+    assert!(err.to_string().contains("beta_g2"));
+
+    let mut no_ic = snarkjs_vk;
+    no_ic.ic.clear();
+    assert!(Groth16VerificationKey::from_snarkjs(&no_ic).is_err());
+
+    let mut onchain_vk = Groth16VerificationKey::from_file("resources/202405_vk.vkey")
+        .unwrap()
+        .try_as_onchain_repr()
+        .unwrap();
+    onchain_vk.data.gamma_abc_g1[1] = "0x1234".to_string();
+    let err = Groth16VerificationKey::from_onchain(&onchain_vk.data).unwrap_err();
+    assert!(err.to_string().contains("gamma_abc_g1[1]"));
+}
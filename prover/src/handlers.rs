@@ -1,36 +1,43 @@
 // Copyright © Aptos Foundation
 
 use crate::{
-    api::{ProverServiceResponse, RequestInput},
+    api::{ProverServiceResponse, RequestInput, StageTimings},
+    debug_capture::{CapturedFailure, DebugCapture, InvalidProofCapture, Stage},
     error::{self, ErrorWithCode, ThrowCodeOnError},
     input_processing::{derive_circuit_input_signals, preprocess},
     jwk_fetching::{get_federated_jwk, get_jwk},
-    load_vk::prepared_vk,
     metrics,
-    state::ProverServiceState,
+    proof_encoding::ProofEncodings,
+    prover_backend::BackendProof,
+    state::{ProverServiceState, Readiness},
     training_wheels,
-    witness_gen::{witness_gen, PathStr},
-};
-use anyhow::Result;
-use aptos_types::{
-    jwks::rsa::RSA_JWK,
-    keyless::{G1Bytes, G2Bytes, Groth16Proof},
-    transaction::authenticator::EphemeralSignature,
+    witness_gen::generate_witness,
 };
+use anyhow::{anyhow, Result};
+use aptos_keyless_common::PoseidonHash;
+use aptos_types::{jwks::rsa::RSA_JWK, transaction::authenticator::EphemeralSignature};
 use ark_ff::PrimeField;
-use axum::{extract::State, http::StatusCode, Json};
+use axum::{
+    extract::State,
+    http::{HeaderName, HeaderValue, StatusCode},
+    response::{IntoResponseParts, ResponseParts},
+    Json,
+};
 use axum_extra::extract::WithRejection;
 
 use crate::groth16_vk::ON_CHAIN_GROTH16_VK;
 use crate::prover_key::ON_CHAIN_TW_PK;
-use serde::Deserialize;
-use std::{fs, sync::Arc, time::Instant};
+use std::{
+    convert::Infallible,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use tracing::{info, info_span, warn};
 
 pub async fn prove_handler(
     State(state): State<Arc<ProverServiceState>>,
     WithRejection(Json(body), _): WithRejection<Json<RequestInput>, error::ApiError>,
-) -> Result<Json<ProverServiceResponse>, ErrorWithCode> {
+) -> Result<(StageTimings, Json<ProverServiceResponse>), ErrorWithCode> {
     let start_time: Instant = Instant::now();
     let span = info_span!("Handling /prove");
     let _enter = span.enter();
@@ -39,6 +46,42 @@ pub async fn prove_handler(
 
     metrics::REQUEST_QUEUE_TIME_SECS.observe(start_time.elapsed().as_secs_f64());
 
+    if state.readiness() != Readiness::Ready {
+        return Err(error::service_unavailable(anyhow!(
+            "The prover isn't ready, see /healthcheck"
+        )));
+    }
+
+    let mut capture = state
+        .debug_capturer
+        .as_ref()
+        .map(|_| DebugCapture::new(body.clone()));
+    let result = prove(&state, body, &mut capture).await;
+
+    if let (Some(debug_capturer), Some(mut capture)) = (&state.debug_capturer, capture) {
+        let status = result.as_ref().err().map(ErrorWithCode::code);
+        if debug_capturer.should_capture(status) {
+            if let Err(e) = &result {
+                capture.failure = Some(CapturedFailure {
+                    status: e.code().as_u16(),
+                    error: format!("{:#}", e.error),
+                });
+            }
+            if let Err(e) = debug_capturer.capture_async(capture).await {
+                warn!("Couldn't write debug capture: {:?}", e);
+            }
+        }
+    }
+
+    result
+}
+
+/// Proves a request, recording how far it got in `capture`.
+async fn prove(
+    state: &ProverServiceState,
+    body: RequestInput,
+    capture: &mut Option<DebugCapture>,
+) -> Result<(StageTimings, Json<ProverServiceResponse>), ErrorWithCode> {
     let mut jwk_override: Option<RSA_JWK> = None;
     if state.config.enable_federated_jwks {
         jwk_override = get_federated_jwk(&body)
@@ -51,7 +94,7 @@ pub async fn prove_handler(
     }
     if state.config.use_insecure_jwk_for_test && body.use_insecure_test_jwk {
         info!("Using insecure test jwk");
-        jwk_override = get_jwk(&body.jwt_b64, "https://github.com/aptos-labs/aptos-core/raw/main/types/src/jwks/rsa/insecure_test_jwk.json").await.ok().map(|arc| (*arc).clone());
+        jwk_override = get_jwk(body.jwt_b64.expose_secret(), "https://github.com/aptos-labs/aptos-core/raw/main/types/src/jwks/rsa/insecure_test_jwk.json").await.ok().map(|arc| (*arc).clone());
     }
 
     training_wheels::validate_jwt_sig_and_dates(
@@ -60,27 +103,13 @@ pub async fn prove_handler(
         state.config.disable_iat_in_past_check,
     )
     .with_status(StatusCode::BAD_REQUEST)?;
+    if let Some(capture) = capture.as_mut() {
+        capture.stage = Stage::InputProcessing;
+    }
 
-    let input = preprocess::decode_and_add_jwk(body, jwk_override.as_ref())
-        .with_status(StatusCode::BAD_REQUEST)?;
-
-    training_wheels::check_nonce_consistency(&input, &state.circuit_config)
+    let requested_encodings = body.proof_encodings.clone();
+    let input = preprocess::decode_and_add_jwk(body, jwk_override.as_ref(), &state.config)
         .with_status(StatusCode::BAD_REQUEST)?;
-    training_wheels::validate_jwt_payload_parsing(&input).with_status(StatusCode::BAD_REQUEST)?;
-
-    // TODO seems not super clean to output public_inputs_hash here
-    let (circuit_input_signals, public_inputs_hash) =
-        derive_circuit_input_signals(input, &state.circuit_config)
-            .with_status(StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    let formatted_input_str = serde_json::to_string(&circuit_input_signals.to_json_value())
-        .map_err(anyhow::Error::new)
-        .with_status(StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    // Only sensitive values to disk.
-    if state.config.enable_dangerous_logging {
-        fs::write("formatted_input.json", &formatted_input_str).unwrap();
-    }
 
     #[allow(clippy::match_like_matches_macro)]
     let use_new_setup = match (
@@ -92,51 +121,98 @@ pub async fn prove_handler(
     };
 
     info!("use_new_setup={use_new_setup}");
+    if let Some(capture) = capture.as_mut() {
+        capture.jwk = Some((*input.jwk).clone());
+        capture.use_new_setup = use_new_setup;
+    }
 
-    let witness_file = witness_gen(&state.config, use_new_setup, &formatted_input_str)
-        .with_status(StatusCode::INTERNAL_SERVER_ERROR)?;
+    let setup = state.setup(use_new_setup);
 
-    // Prove!
-    let prover_unlocked = if use_new_setup {
-        state.full_prover_new.as_ref().unwrap().lock().await
-    } else {
-        state.full_prover_default.lock().await
-    };
+    training_wheels::check_nonce_consistency(&input, &setup.circuit_config)
+        .with_status(StatusCode::BAD_REQUEST)?;
+    training_wheels::validate_jwt_payload_parsing(&input).with_status(StatusCode::BAD_REQUEST)?;
+
+    // TODO seems not super clean to output public_inputs_hash here
+    let (circuit_input_signals, public_inputs_hash) =
+        derive_circuit_input_signals(input, &setup.circuit_config)
+            .with_status(StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let circuit_input_json = circuit_input_signals.to_json_value();
+    if let Some(capture) = capture.as_mut() {
+        capture.circuit_input_signals = Some(circuit_input_json.clone());
+        capture.stage = Stage::WitnessGeneration;
+    }
+
+    // Checked only now, so that requests that are rejected anyway aren't let through as probes.
+    let breaker_cooldown = Duration::from_secs(state.config.invalid_proof_breaker_cooldown_secs);
+    if !setup.invalid_proof_breaker.admit(breaker_cooldown) {
+        return Err(error::service_unavailable(anyhow!(
+            "The {} setup keeps producing invalid proofs",
+            if use_new_setup { "new" } else { "default" }
+        )));
+    }
+
+    let witness_gen_start = Instant::now();
+    let wtns = generate_witness(setup.witness_generator.as_ref(), circuit_input_json)
+        .await
+        .map_err(error::handle_witness_gen_error)?;
+    let witness_generation_time = witness_gen_start.elapsed();
+    if let Some(capture) = capture.as_mut() {
+        capture.stage = Stage::Proving;
+    }
 
-    let g16vk = prepared_vk(&state.config.verification_key_path(use_new_setup));
-    let max_retries = 3;
-    let mut retries = 0;
-    let (proof, proof_json, internal_metrics) = loop {
-        let (proof_json, internal_metrics) = prover_unlocked
-            .prove(witness_file.path_str()?)
-            .map_err(error::handle_prover_lib_error)?;
+    // Prove!
+    let queue_start = Instant::now();
+    let prover_unlocked = setup.prover.lock().await;
+    let queue_time = queue_start.elapsed();
+
+    let setup_label = if use_new_setup { "new" } else { "default" };
+    let public_inputs_hash_fr = ark_bn254::Fr::from_le_bytes_mod_order(&public_inputs_hash);
+    let mut attempts = 0;
+    let BackendProof {
+        proof,
+        proof_json,
+        prover_time,
+    } = loop {
+        let backend_proof = prover_unlocked
+            .prove(&wtns)
+            .map_err(error::handle_prover_backend_error)?;
+        attempts += 1;
         // TODO constructing the response struct should be its own func, so that I can test it
-        let proof = encode_proof(
-            &serde_json::from_str(proof_json)
-                .map_err(anyhow::Error::from)
-                .with_status(StatusCode::INTERNAL_SERVER_ERROR)?,
-        )
-        .with_status(StatusCode::INTERNAL_SERVER_ERROR)?;
-
-        let verify_result = proof
-            .verify_proof(
-                ark_bn254::Fr::from_le_bytes_mod_order(&public_inputs_hash),
-                &g16vk,
-            )
-            .with_status(StatusCode::INTERNAL_SERVER_ERROR);
-
-        match verify_result {
+        let proof = backend_proof.proof;
+
+        match proof.verify_proof(public_inputs_hash_fr, &setup.prepared_vk) {
             Ok(_) => {
-                break (proof, proof_json, internal_metrics);
+                state.record_proof_outcome(use_new_setup, true);
+                break backend_proof;
             }
             Err(e) => {
-                warn!("Generated an invalid proof");
-                warn!("Proof: {:?}", proof);
-                warn!("Public inputs hash: {:?}", hex::encode(public_inputs_hash));
-                retries += 1;
-                if retries >= max_retries {
-                    warn!("Reached max retries. Exiting.");
-                    return Err(e);
+                metrics::INVALID_PROOFS
+                    .with_label_values(&[setup_label])
+                    .inc();
+                warn!(
+                    attempt = attempts,
+                    setup = setup_label,
+                    "Generated an invalid proof {:?} for public inputs hash {}",
+                    proof,
+                    hex::encode(public_inputs_hash)
+                );
+                if attempts == 1 {
+                    capture_invalid_proof(
+                        state,
+                        use_new_setup,
+                        public_inputs_hash,
+                        &backend_proof.proof_json,
+                        &wtns,
+                    )
+                    .await;
+                }
+                if attempts >= state.config.max_proof_attempts {
+                    state.record_proof_outcome(use_new_setup, false);
+                    return Err(error::server_error(e.context(format!(
+                        "The proof was still invalid after {} attempts",
+                        attempts
+                    ))));
                 }
             }
         }
@@ -144,10 +220,14 @@ pub async fn prove_handler(
 
     let span = info_span!(
         "Proof generation finished, building response",
-        rapidsnark_response_json = proof_json
+        rapidsnark_response_json = proof_json.as_str()
     );
     let _enter = span.enter();
 
+    let proof_encodings =
+        ProofEncodings::new(&requested_encodings, &proof_json, &[public_inputs_hash_fr])
+            .with_status(StatusCode::INTERNAL_SERVER_ERROR)?;
+
     let (using_new_tw_keys, actual_tw_sk, actual_tw_pk) = match (
         ON_CHAIN_TW_PK.read().unwrap().as_ref(),
         state.tw_keypair_new.as_ref(),
@@ -175,50 +255,92 @@ pub async fn prove_handler(
         public_inputs_hash,
         training_wheels_signature: bcs::to_bytes(&training_wheels_signature)
             .expect("Only unhandleable errors happen here."),
+        proof_encodings,
     };
 
     if state.config.enable_debug_checks {
         assert!(training_wheels::verify(&response, actual_tw_pk).is_ok());
     }
 
-    metrics::GROTH16_TIME_SECS.observe((f64::from(internal_metrics.prover_time)) / 1000.0);
+    metrics::GROTH16_TIME_SECS.observe(prover_time.as_secs_f64());
 
-    Ok(Json(response))
+    let timings = StageTimings {
+        queue: queue_time,
+        witness_generation: witness_generation_time,
+        groth16: prover_time,
+    };
+    Ok((timings, Json(response)))
 }
 
-/// Added on request by Christian: Kubernetes apparently needs a GET route to check whether
-/// this service is ready for requests.
-pub async fn healthcheck_handler() -> (StatusCode, &'static str) {
-    // TODO: CHECK FOR A REAL STATUS OF PROVER HERE?
-    (StatusCode::OK, "OK")
+/// Keeps what isn't secret of an invalid proof and its witness, if the debug captures are
+/// configured to.
+async fn capture_invalid_proof(
+    state: &ProverServiceState,
+    use_new_setup: bool,
+    public_inputs_hash: PoseidonHash,
+    proof_json: &str,
+    wtns: &[u8],
+) {
+    let Some(debug_capturer) = state
+        .debug_capturer
+        .as_ref()
+        .filter(|debug_capturer| debug_capturer.captures_invalid_proofs())
+    else {
+        return;
+    };
+    let n_public = state
+        .setup(use_new_setup)
+        .prepared_vk
+        .vk
+        .gamma_abc_g1
+        .len()
+        .saturating_sub(1) as u32;
+    let result = match InvalidProofCapture::new(
+        use_new_setup,
+        public_inputs_hash,
+        String::from(proof_json),
+        wtns,
+        n_public,
+    ) {
+        Ok(capture) => debug_capturer.capture_invalid_proof_async(capture).await,
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(path) => info!("Captured the invalid proof in {}", path.display()),
+        Err(e) => warn!("Couldn't capture the invalid proof: {:?}", e),
+    }
 }
 
-/// On all unrecognized routes, return 404.
-pub async fn fallback_handler() -> (StatusCode, &'static str) {
-    (StatusCode::NOT_FOUND, "Invalid route")
-}
+/// Reports the stage timings of a request in the `Server-Timing` header of its response.
+impl IntoResponseParts for StageTimings {
+    type Error = Infallible;
 
-#[derive(Deserialize)]
-pub struct RapidsnarkProofResponse {
-    pi_a: [String; 3],
-    pi_b: [[String; 2]; 3],
-    pi_c: [String; 3],
+    fn into_response_parts(self, mut res: ResponseParts) -> Result<ResponseParts, Self::Error> {
+        if let Ok(value) = HeaderValue::from_str(&self.to_header_value()) {
+            res.headers_mut()
+                .insert(HeaderName::from_static(StageTimings::HEADER), value);
+        }
+        Ok(res)
+    }
 }
 
-impl RapidsnarkProofResponse {
-    fn pi_b_str(&self) -> [[&str; 2]; 3] {
-        [
-            [&self.pi_b[0][0], &self.pi_b[0][1]],
-            [&self.pi_b[1][0], &self.pi_b[1][1]],
-            [&self.pi_b[2][0], &self.pi_b[2][1]],
-        ]
+/// Readiness probe. Answers 200 once the startup self-test passed, and 503 with the reason while
+/// it is running or after it failed. A setup whose invalid proof breaker tripped doesn't make the
+/// service unready, since it then gets no requests to find out that the setup recovered.
+pub async fn healthcheck_handler(
+    State(state): State<Arc<ProverServiceState>>,
+) -> (StatusCode, String) {
+    match state.readiness() {
+        Readiness::Ready => (StatusCode::OK, String::from("OK")),
+        Readiness::SelfTestRunning => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            String::from("Running the startup self-test"),
+        ),
+        Readiness::SelfTestFailed(error) => (StatusCode::SERVICE_UNAVAILABLE, error),
     }
 }
 
-pub fn encode_proof(proof: &RapidsnarkProofResponse) -> Result<Groth16Proof> {
-    let new_pi_a = G1Bytes::new_unchecked(&proof.pi_a[0], &proof.pi_a[1])?;
-    let new_pi_b = G2Bytes::new_unchecked(proof.pi_b_str()[0], proof.pi_b_str()[1])?;
-    let new_pi_c = G1Bytes::new_unchecked(&proof.pi_c[0], &proof.pi_c[1])?;
-
-    Ok(Groth16Proof::new(new_pi_a, new_pi_b, new_pi_c))
+/// On all unrecognized routes, return 404.
+pub async fn fallback_handler() -> (StatusCode, &'static str) {
+    (StatusCode::NOT_FOUND, "Invalid route")
 }
@@ -1,30 +1,28 @@
 // Copyright © Aptos Foundation
 
 use super::{field_parser::ParsedField, types::Input};
-use crate::input_processing::field_parser::FieldParser;
-use anyhow::Result;
-use aptos_keyless_common::input_processing::circuit_input_signals::{
-    CircuitInputSignals, Unpadded,
+use crate::{config::EmailVerifiedPolicy, input_processing::field_parser::FieldParser};
+use anyhow::{anyhow, Result};
+use aptos_keyless_common::input_processing::{
+    circuit_input_signals::{CircuitInputSignals, Unpadded},
+    encoding::{AudSelectionError, JwtAud, JwtPayload},
 };
 
+/// Mirrors the circuit's `StringBodies` template: a byte is part of a string body iff it lies
+/// strictly between an opening quote and its closing quote. A quote is escaped iff it follows an
+/// odd-length run of backslashes.
 fn calc_string_bodies(s: &str) -> Vec<bool> {
-    let bytes = s.as_bytes();
     let mut string_bodies = vec![false; s.len()];
-    let _quotes = vec![false; s.len()];
-
-    string_bodies[0] = false;
-    string_bodies[1] = bytes[0] == b'"';
-
-    for i in 2..bytes.len() {
-        // should we start a string body?
-        if !string_bodies[i - 2] && bytes[i - 1] == b'"' && bytes[i - 2] != b'\\' {
-            string_bodies[i] = true;
-        // should we end a string body?
-        } else if string_bodies[i - 1] && bytes[i] == b'"' && bytes[i - 1] != b'\\' {
-            string_bodies[i] = false;
-        } else {
-            string_bodies[i] = string_bodies[i - 1];
+    let mut quote_parity = false;
+    let mut escaping_backslash = false;
+
+    for (i, &b) in s.as_bytes().iter().enumerate() {
+        let prev_quote_parity = quote_parity;
+        if b == b'"' && !escaping_backslash {
+            quote_parity = !quote_parity;
         }
+        string_bodies[i] = prev_quote_parity && quote_parity;
+        escaping_backslash = b == b'\\' && !escaping_backslash;
     }
 
     string_bodies
@@ -92,8 +90,10 @@ pub fn field_components_signals(
 }
 
 pub fn signals_for_field(input: &Input, name: &str) -> Result<CircuitInputSignals<Unpadded>> {
-    let parsed_field =
-        FieldParser::find_and_parse_field(input.jwt_parts.payload_decoded()?.as_str(), name)?;
+    let parsed_field = FieldParser::find_and_parse_field(
+        input.jwt_parts.expose_secret().payload_decoded()?.as_str(),
+        name,
+    )?;
 
     let result = CircuitInputSignals::new()
         .merge(whole_field_signals(&parsed_field, name)?)?
@@ -107,8 +107,10 @@ pub fn signals_for_field_with_key(
     name: &str,
     key_in_jwt: &str,
 ) -> Result<CircuitInputSignals<Unpadded>> {
-    let parsed_field =
-        FieldParser::find_and_parse_field(input.jwt_parts.payload_decoded()?.as_str(), key_in_jwt)?;
+    let parsed_field = FieldParser::find_and_parse_field(
+        input.jwt_parts.expose_secret().payload_decoded()?.as_str(),
+        key_in_jwt,
+    )?;
 
     let result = CircuitInputSignals::new()
         .merge(whole_field_signals(&parsed_field, name)?)?
@@ -121,29 +123,45 @@ pub fn signals_for_field_with_key(
 // These signals have custom logic
 //
 
+/// Returns the aud value in the JWT that the proof is about, as the circuit sees it. If the JWT's
+/// aud claim is an array, `input.aud_index` selects the element. The circuit can currently only
+/// parse a single-string aud field, so array-valued claims are rejected with
+/// [`AudSelectionError::ArrayNotSupportedByCircuit`] once the selection itself has been validated.
+pub fn jwt_aud_value(input: &Input) -> Result<String> {
+    let payload_decoded = input.jwt_parts.expose_secret().payload_decoded()?;
+    let payload_struct: JwtPayload = serde_json::from_str(&payload_decoded)?;
+    let aud = payload_struct
+        .aud
+        .ok_or_else(|| anyhow!("JWT has no aud claim"))?;
+    aud.select(input.aud_index)?;
+
+    match aud {
+        JwtAud::Single(_) => Ok(FieldParser::find_and_parse_field(&payload_decoded, "aud")?.value),
+        JwtAud::Multiple(_) => Err(AudSelectionError::ArrayNotSupportedByCircuit.into()),
+    }
+}
+
 pub fn private_aud_value(input: &Input) -> Result<String> {
     if let Some(v) = &input.idc_aud {
         Ok(v.clone())
     } else {
-        let parsed_field =
-            FieldParser::find_and_parse_field(input.jwt_parts.payload_decoded()?.as_str(), "aud")?;
-        Ok(parsed_field.value)
+        jwt_aud_value(input)
     }
 }
 
 pub fn override_aud_value(input: &Input) -> Result<String> {
     if let Some(_v) = &input.idc_aud {
-        let parsed_field =
-            FieldParser::find_and_parse_field(input.jwt_parts.payload_decoded()?.as_str(), "aud")?;
-        Ok(parsed_field.value)
+        jwt_aud_value(input)
     } else {
         Ok(String::from(""))
     }
 }
 
 pub fn aud_signals(input: &Input) -> Result<CircuitInputSignals<Unpadded>> {
-    let parsed_field =
-        FieldParser::find_and_parse_field(input.jwt_parts.payload_decoded()?.as_str(), "aud")?;
+    let parsed_field = FieldParser::find_and_parse_field(
+        input.jwt_parts.expose_secret().payload_decoded()?.as_str(),
+        "aud",
+    )?;
 
     let private_aud_value = private_aud_value(input)?;
     let override_aud_value = override_aud_value(input)?;
@@ -185,20 +203,19 @@ pub fn extra_field_signals(input: &Input) -> Result<CircuitInputSignals<Unpadded
 }
 
 pub fn parsed_email_verified_field_or_default(input: &Input) -> Result<ParsedField<usize>> {
-    if input.uid_key == "email" {
-        Ok(FieldParser::find_and_parse_field(
-            input.jwt_parts.payload_decoded()?.as_str(),
+    match input.email_verified_policy {
+        EmailVerifiedPolicy::Required => Ok(FieldParser::find_and_parse_field(
+            input.jwt_parts.expose_secret().payload_decoded()?.as_str(),
             "email_verified",
-        )?)
-    } else {
-        Ok(email_verified_field_default_value())
+        )?),
+        EmailVerifiedPolicy::NotRequired => Ok(email_verified_field_default_value()),
     }
 }
 
 pub fn parsed_extra_field_or_default(input: &Input) -> Result<ParsedField<usize>> {
     if let Some(extra_field_key) = &input.extra_field {
         Ok(FieldParser::find_and_parse_field(
-            input.jwt_parts.payload_decoded()?.as_str(),
+            input.jwt_parts.expose_secret().payload_decoded()?.as_str(),
             extra_field_key,
         )?)
     } else {
@@ -211,6 +228,7 @@ pub fn extra_field_default_value() -> ParsedField<usize> {
         index: 1,
         key: String::from(""),
         value: String::from(""),
+        unescaped_value: String::from(""),
         colon_index: 0,
         value_index: 0,
         whole_field: String::from(" "),
@@ -222,8 +240,92 @@ pub fn email_verified_field_default_value() -> ParsedField<usize> {
         index: 1,
         key: String::from("email_verified"),
         value: String::from("true"),
+        unescaped_value: String::from("true"),
         colon_index: 16,
         value_index: 17,
         whole_field: String::from("\"email_verified\":true,"),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::calc_string_bodies;
+    use crate::input_processing::field_parser::FieldParser;
+
+    fn string_body_ranges(s: &str) -> Vec<(usize, usize)> {
+        let bodies = calc_string_bodies(s);
+        let mut ranges = vec![];
+        let mut start = None;
+        for (i, &in_body) in bodies.iter().chain([false].iter()).enumerate() {
+            match (start, in_body) {
+                (None, true) => start = Some(i),
+                (Some(st), false) => {
+                    ranges.push((st, i));
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+        ranges
+    }
+
+    #[test]
+    fn test_calc_string_bodies() {
+        let bodies: Vec<u8> = calc_string_bodies(r#""a":"b\"c""#)
+            .into_iter()
+            .map(u8::from)
+            .collect();
+        assert_eq!(bodies, vec![0, 1, 0, 0, 0, 1, 1, 1, 1, 0]);
+    }
+
+    #[test]
+    fn test_calc_string_bodies_agrees_with_field_parser() {
+        for payload in [
+            r#"{"name":"Michael \"Mike\" Straka","sub":"1"}"#,
+            r#"{"name":"a\\","sub":"1"}"#,
+            r#"{"name":"\\\"\\","sub":"1"}"#,
+            r#"{"name":"\u0022\/","sub":"1"}"#,
+            r#"{"name":"","sub":"1"}"#,
+        ] {
+            let parsed = FieldParser::find_and_parse_field(payload, "name").unwrap();
+            let value_span = parsed.value_span();
+            let key_span = 1..1 + parsed.key.len();
+
+            let expected = if value_span.is_empty() {
+                vec![(key_span.start, key_span.end)]
+            } else {
+                vec![
+                    (key_span.start, key_span.end),
+                    (value_span.start, value_span.end),
+                ]
+            };
+            assert_eq!(
+                string_body_ranges(&parsed.whole_field),
+                expected,
+                "{}",
+                payload
+            );
+        }
+    }
+
+    #[test]
+    fn test_field_index_agrees_with_string_bodies() {
+        for payload in [
+            r#"{"name":"\"sub\":\"victim\",","sub":"real"}"#,
+            r#"{"name":"\\\"sub\\\":","sub":"real"}"#,
+            r#"{"extra":{"sub":"victim"},"sub":"real"}"#,
+        ] {
+            let parsed = FieldParser::find_and_parse_field(payload, "sub").unwrap();
+            let payload_bodies = calc_string_bodies(payload);
+            let field_range = parsed.index..parsed.index + parsed.whole_field.len();
+
+            assert!(!payload_bodies[parsed.index], "{}", payload);
+            assert_eq!(
+                payload_bodies[field_range],
+                calc_string_bodies(&parsed.whole_field),
+                "{}",
+                payload
+            );
+        }
+    }
+}
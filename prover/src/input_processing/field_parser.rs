@@ -1,61 +1,91 @@
 // Copyright © Aptos Foundation
 
-use std::{iter::Peekable, str::CharIndices};
+use std::ops::Range;
 use thiserror::Error;
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct ParsedField<IndexInJwt> {
     pub index: IndexInJwt,
     pub key: String,
+    /// The value exactly as it appears in the JWT, i.e., with any escape sequences left in. This is
+    /// what the circuit operates on.
     pub value: String,
+    /// The value with its escape sequences decoded, as a JSON parser would return it.
+    pub unescaped_value: String,
     pub colon_index: usize,
     pub value_index: usize,
     pub whole_field: String,
 }
 
+impl<IndexInJwt> ParsedField<IndexInJwt> {
+    /// The byte range of the raw value within `whole_field`.
+    pub fn value_span(&self) -> Range<usize> {
+        self.value_index..self.value_index + self.value.len()
+    }
+}
+
+/// A consumed value, both as it appears in the JWT and with its escape sequences decoded. The two
+/// are only different for strings.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ConsumedValue {
+    pub raw: String,
+    pub unescaped: String,
+}
+
+impl ConsumedValue {
+    fn verbatim(raw: String) -> Self {
+        Self {
+            unescaped: raw.clone(),
+            raw,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub struct IndexInJwtNotSet {}
 
-pub type ConsumeResultStr = Result<(usize, String), FieldParserError>;
-pub type ConsumeResultChar = Result<(usize, char), FieldParserError>;
+pub type ConsumeResultValue = Result<(usize, ConsumedValue), FieldParserError>;
+pub type ConsumeResultByte = Result<(usize, u8), FieldParserError>;
 pub type ConsumeResultEmpty = Result<(usize, ()), FieldParserError>;
 
+/// Doesn't hold the string being parsed: that is the JWT payload, and this ends up in logs and
+/// responses.
 #[derive(Debug, PartialEq, Eq, Error)]
 #[error(
-    "Parse error. {}. Occurred at index {} of {}",
+    "Parse error. {}. Occurred at index {} of the jwt payload",
     explanation,
-    index,
-    whole_str
+    index
 )]
 pub struct FieldParserError {
     explanation: String,
     index: usize,
-    whole_str: String,
 }
 
 #[derive(Debug)]
 pub struct FieldParser<'a> {
-    char_indices: Peekable<CharIndices<'a>>,
-    whole_str: String,
+    bytes: &'a [u8],
+    position: usize,
+    whole_str: &'a str,
+}
+
+/// Whitespace as defined by JSON. The circuit's `isWhitespace` template accepts all of these.
+fn is_json_whitespace(b: u8) -> bool {
+    matches!(b, b' ' | b'\t' | b'\n' | b'\r')
 }
 
 impl<'a> FieldParser<'a> {
     pub fn new(s: &'a str) -> Self {
-        let char_indices = s.char_indices().peekable();
         Self {
-            char_indices,
-            whole_str: String::from(s),
+            bytes: s.as_bytes(),
+            position: 0,
+            whole_str: s,
         }
     }
 
     pub fn error(&mut self, explanation: &str) -> FieldParserError {
         FieldParserError {
             explanation: String::from(explanation),
-            index: match self.char_indices.peek() {
-                Some((i, _)) => *i,
-                None => self.whole_str.len(),
-            },
-            whole_str: String::from(&self.whole_str),
+            index: self.position,
         }
     }
 
@@ -64,127 +94,287 @@ impl<'a> FieldParser<'a> {
     }
 
     pub fn parse(&mut self) -> Result<ParsedField<IndexInJwtNotSet>, FieldParserError> {
+        Ok(self.parse_field()?.0)
+    }
+
+    /// Parses the field starting at the current position, up to and including the `,` or `}` that
+    /// ends it. Indices in the result are relative to the start of the field. The key is also
+    /// returned unescaped.
+    fn parse_field(&mut self) -> Result<(ParsedField<IndexInJwtNotSet>, String), FieldParserError> {
+        let (field_start_index, _) = self.peek()?;
         let (_, key) = self.consume_string()?;
-        let (colon_index, _) = self.consume_non_whitespace_char(&[':'])?;
-        let (value_index, value) = self.consume_value()?;
-        let (field_end_delimiter_index, _) = self.consume_non_whitespace_char(&[',', '}'])?;
-        let whole_field = String::from(&self.whole_str[..field_end_delimiter_index + 1]);
+        let (colon_index, _) = self.consume_non_whitespace_char(b":")?;
+        let (value_index, ConsumedValue { raw, unescaped }) = self.consume_value()?;
+        let (field_end_delimiter_index, _) = self.consume_non_whitespace_char(b",}")?;
+        let whole_field =
+            String::from(&self.whole_str[field_start_index..field_end_delimiter_index + 1]);
 
-        Ok(ParsedField {
+        let parsed_field = ParsedField {
             index: IndexInJwtNotSet {},
             // key should not have quotes
-            key,
-            value,
-            colon_index,
-            value_index,
+            key: key.raw,
+            value: raw,
+            unescaped_value: unescaped,
+            colon_index: colon_index - field_start_index,
+            value_index: value_index - field_start_index,
             whole_field,
-        })
+        };
+
+        Ok((parsed_field, key.unescaped))
     }
 
-    pub fn peek(&mut self) -> ConsumeResultChar {
-        match self.char_indices.peek() {
-            Some(p) => Ok(*p),
+    pub fn peek(&mut self) -> ConsumeResultByte {
+        match self.bytes.get(self.position) {
+            Some(b) => Ok((self.position, *b)),
             None => Err(self.eos_error()),
         }
     }
 
     #[allow(clippy::all)]
-    pub fn next(&mut self) -> ConsumeResultChar {
-        self.char_indices.next().ok_or_else(|| self.eos_error())
+    pub fn next(&mut self) -> ConsumeResultByte {
+        let result = self.peek()?;
+        self.position += 1;
+        Ok(result)
     }
 
     pub fn consume_whitespace(&mut self) -> ConsumeResultEmpty {
         let (index, _) = self.peek()?;
-        while self.peek()?.1 == ' ' {
-            self.next().map_err(|_| self.eos_error())?;
+        while is_json_whitespace(self.peek()?.1) {
+            self.next()?;
         }
         Ok((index, ()))
     }
 
-    pub fn consume_non_whitespace_char(&mut self, char_options: &[char]) -> ConsumeResultChar {
-        while self.peek()?.1 == ' ' {
-            self.next().map_err(|_| self.eos_error())?;
-        }
+    pub fn consume_non_whitespace_char(&mut self, char_options: &[u8]) -> ConsumeResultByte {
+        self.consume_whitespace()?;
 
-        let c = self.peek()?.1;
-        if char_options.contains(&c) {
-            self.next().map_err(|_| self.eos_error())
+        let b = self.peek()?.1;
+        if char_options.contains(&b) {
+            self.next()
         } else {
             Err(self.error(&format!(
-                "Expected a character in {:?}, got {}",
-                char_options, c
+                "Expected a character in {:?}, got {:?}",
+                String::from_utf8_lossy(char_options),
+                b as char
             )))
         }
     }
 
-    fn consume_value(&mut self) -> ConsumeResultStr {
+    fn consume_value(&mut self) -> ConsumeResultValue {
         self.consume_whitespace()?;
         match self.peek()?.1 {
-            '"' => self.consume_string(),
+            b'"' => self.consume_string(),
+            b'[' | b'{' => self.consume_array_or_object(),
             _ => self.consume_unquoted(),
         }
     }
 
-    // Should this handle escaped characters (e.g., quotes, newlines)? It doesn't currently.
-    fn consume_string(&mut self) -> ConsumeResultStr {
-        if self.peek()?.1 != '"' {
-            Err(self.error("Expected a string here"))
-        } else {
-            self.next()?; // ignore the '"'
+    /// Returns the text between `start` and the current position. Only called at positions next to
+    /// an ASCII delimiter, so the slice always falls on UTF-8 character boundaries.
+    fn text_from(&self, start: usize) -> String {
+        String::from(&self.whole_str[start..self.position])
+    }
 
-            let (index, _) = self.peek()?;
-            let mut result = String::new();
+    /// Consumes an array value (e.g., an `aud` claim with several audiences) or a nested object and
+    /// returns it verbatim, brackets included, so that the whole field can still be located in the
+    /// JWT.
+    fn consume_array_or_object(&mut self) -> ConsumeResultValue {
+        let (index, _) = self.peek()?;
+        let mut depth = 0;
+        let mut in_string = false;
+        let mut escaped = false;
+
+        loop {
+            let (_, b) = self.next()?;
+            match b {
+                _ if escaped => escaped = false,
+                b'\\' if in_string => escaped = true,
+                b'"' => in_string = !in_string,
+                b'[' | b'{' if !in_string => depth += 1,
+                b']' | b'}' if !in_string => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
 
-            result.push(self.next()?.1); // push the '"'
+        Ok((index, ConsumedValue::verbatim(self.text_from(index))))
+    }
 
-            while self.peek()?.1 != '"' {
-                result.push(self.next()?.1);
+    /// Consumes a string, including its quotes. The returned index is that of the first byte after
+    /// the opening quote, which is what the circuit expects for value indices.
+    fn consume_string(&mut self) -> ConsumeResultValue {
+        if self.peek()?.1 != b'"' {
+            return Err(self.error("Expected a string here"));
+        }
+        self.next()?; // ignore the '"'
+
+        let (index, _) = self.peek()?;
+        let mut unescaped = String::new();
+        let mut unescaped_run_start = index;
+
+        loop {
+            let (position, b) = self.peek()?;
+            match b {
+                b'"' => break,
+                b'\\' => {
+                    unescaped.push_str(&self.whole_str[unescaped_run_start..position]);
+                    self.next()?;
+                    unescaped.push(self.consume_escape_sequence()?);
+                    unescaped_run_start = self.position;
+                }
+                b if b < 0x20 => {
+                    return Err(self.error("Unescaped control character in string"));
+                }
+                _ => {
+                    self.next()?;
+                }
             }
+        }
 
-            self.next()?; // ignore the '"'
+        unescaped.push_str(&self.whole_str[unescaped_run_start..self.position]);
+        let raw = self.text_from(index);
+        self.next()?; // ignore the '"'
+
+        Ok((index, ConsumedValue { raw, unescaped }))
+    }
+
+    /// Consumes what follows a backslash in a string and returns the character it stands for.
+    fn consume_escape_sequence(&mut self) -> Result<char, FieldParserError> {
+        let (_, b) = self.peek()?;
+        let unescaped = match b {
+            b'"' | b'\\' | b'/' => b as char,
+            b'b' => '\u{8}',
+            b'f' => '\u{c}',
+            b'n' => '\n',
+            b'r' => '\r',
+            b't' => '\t',
+            b'u' => {
+                self.next()?;
+                return self.consume_unicode_escape();
+            }
+            _ => return Err(self.error("Invalid escape sequence")),
+        };
+
+        self.next()?;
+        Ok(unescaped)
+    }
 
-            // The circuit requires the value_index to be for the first character after the quote.
-            Ok((index, result))
+    /// Consumes the hex digits of a `\uXXXX` escape, plus the low half of a surrogate pair if the
+    /// first escape is a high surrogate.
+    fn consume_unicode_escape(&mut self) -> Result<char, FieldParserError> {
+        let code_unit = self.consume_hex4()?;
+
+        let code_point = match code_unit {
+            0xD800..=0xDBFF => {
+                for expected in [b'\\', b'u'] {
+                    if self.peek()?.1 != expected {
+                        return Err(self.error("Expected the low half of a surrogate pair"));
+                    }
+                    self.next()?;
+                }
+                let low = self.consume_hex4()?;
+                if !(0xDC00..=0xDFFF).contains(&low) {
+                    return Err(self.error("Expected the low half of a surrogate pair"));
+                }
+                0x10000 + ((code_unit - 0xD800) << 10) + (low - 0xDC00)
+            }
+            0xDC00..=0xDFFF => return Err(self.error("Unpaired low surrogate")),
+            _ => code_unit,
+        };
+
+        char::from_u32(code_point).ok_or_else(|| self.error("Invalid unicode escape"))
+    }
+
+    fn consume_hex4(&mut self) -> Result<u32, FieldParserError> {
+        let mut result = 0;
+        for _ in 0..4 {
+            let (_, b) = self.peek()?;
+            let digit = (b as char)
+                .to_digit(16)
+                .ok_or_else(|| self.error("Expected a hex digit"))?;
+            self.next()?;
+            result = result * 16 + digit;
         }
+        Ok(result)
     }
 
-    fn consume_unquoted(&mut self) -> ConsumeResultStr {
+    fn consume_unquoted(&mut self) -> ConsumeResultValue {
         let (index, _) = self.peek()?;
-        let mut result = String::new();
 
-        while self.peek()?.1 != ' ' && self.peek()?.1 != ',' && self.peek()?.1 != '}' {
-            result.push(self.next()?.1);
+        while !is_json_whitespace(self.peek()?.1) && !b",}".contains(&self.peek()?.1) {
+            self.next()?;
         }
 
-        Ok((index, result))
+        Ok((index, ConsumedValue::verbatim(self.text_from(index))))
     }
 
+    /// Finds the field with the given key among the top-level fields of the JSON object
+    /// `jwt_payload`, so that keys appearing inside strings or nested values are never matched. The
+    /// returned index is the position of the key's opening quote.
+    ///
+    /// Fails if the key occurs more than once at the top level or if it is spelled with escape
+    /// sequences, since the circuit compares key names byte by byte.
     pub fn find_and_parse_field(
         jwt_payload: &'a str,
         key: &str,
     ) -> Result<ParsedField<usize>, FieldParserError> {
-        let key_in_quotes = String::from("\"") + key + "\"";
-
-        let index = jwt_payload
-            .find(&key_in_quotes)
-            .ok_or_else(|| FieldParserError {
-                explanation: format!(
-                    "Could not find {} in jwt payload: {}",
-                    key_in_quotes, jwt_payload
-                ),
-                index: 0,
-                whole_str: String::from(jwt_payload),
-            })?;
+        let mut parser = Self::new(jwt_payload);
+        let mut result: Option<ParsedField<usize>> = None;
+
+        parser.consume_non_whitespace_char(b"{")?;
+        parser.consume_whitespace()?;
+        let mut at_end = parser.peek()?.1 == b'}';
+        if at_end {
+            parser.next()?;
+        }
 
-        let field_check_input = Self::new(&jwt_payload[index..]).parse()?;
+        while !at_end {
+            parser.consume_whitespace()?;
+            let (index, _) = parser.peek()?;
+            let (parsed_field, unescaped_key) = parser.parse_field()?;
+            at_end = parsed_field.whole_field.ends_with('}');
 
-        Ok(ParsedField {
-            index,
-            key: field_check_input.key,
-            value: field_check_input.value,
-            colon_index: field_check_input.colon_index,
-            value_index: field_check_input.value_index,
-            whole_field: field_check_input.whole_field,
+            if unescaped_key != key {
+                continue;
+            }
+            if result.is_some() {
+                return Err(FieldParserError {
+                    explanation: format!("Duplicate key \"{}\" in jwt payload", key),
+                    index,
+                });
+            }
+            if parsed_field.key != unescaped_key {
+                return Err(FieldParserError {
+                    explanation: format!("Key \"{}\" is spelled with escape sequences", key),
+                    index,
+                });
+            }
+
+            result = Some(ParsedField {
+                index,
+                key: parsed_field.key,
+                value: parsed_field.value,
+                unescaped_value: parsed_field.unescaped_value,
+                colon_index: parsed_field.colon_index,
+                value_index: parsed_field.value_index,
+                whole_field: parsed_field.whole_field,
+            });
+        }
+
+        while let Some(&b) = parser.bytes.get(parser.position) {
+            if !is_json_whitespace(b) {
+                return Err(parser.error("Unexpected characters after the end of the jwt payload"));
+            }
+            parser.position += 1;
+        }
+
+        result.ok_or_else(|| FieldParserError {
+            explanation: format!("Could not find \"{}\" in jwt payload", key),
+            index: 0,
         })
     }
 }
@@ -193,6 +383,8 @@ impl<'a> FieldParser<'a> {
 mod tests {
     use super::{FieldParserError, IndexInJwtNotSet, ParsedField};
     use crate::input_processing::field_parser::FieldParser;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use serde_json::Value;
 
     // TODO other test cases to potentially use
     //    const TEST_FIELDS : [&'static str; 16] = [
@@ -220,11 +412,23 @@ mod tests {
         colon_index: usize,
         value_index: usize,
         whole_field: &str,
+    ) -> Result<ParsedField<IndexInJwtNotSet>, FieldParserError> {
+        success_escaped(key, value, value, colon_index, value_index, whole_field)
+    }
+
+    fn success_escaped(
+        key: &str,
+        value: &str,
+        unescaped_value: &str,
+        colon_index: usize,
+        value_index: usize,
+        whole_field: &str,
     ) -> Result<ParsedField<IndexInJwtNotSet>, FieldParserError> {
         Ok(ParsedField {
             index: IndexInJwtNotSet {},
             key: String::from(key),
             value: String::from(value),
+            unescaped_value: String::from(unescaped_value),
             colon_index,
             value_index,
             whole_field: String::from(whole_field),
@@ -266,4 +470,288 @@ mod tests {
             )
         );
     }
+
+    #[test]
+    fn test_parse_aud_array() {
+        let result = FieldParser::new("\"aud\":[\"a,b\", \"c\"],\"sub\":\"d\"}").parse();
+
+        assert_eq!(
+            result,
+            success("aud", "[\"a,b\", \"c\"]", 5, 6, "\"aud\":[\"a,b\", \"c\"],")
+        );
+    }
+
+    #[test]
+    fn test_parse_escaped_quote() {
+        let result = FieldParser::new(r#""name":"Michael \"Mike\" Straka","#).parse();
+
+        assert_eq!(
+            result,
+            success_escaped(
+                "name",
+                r#"Michael \"Mike\" Straka"#,
+                r#"Michael "Mike" Straka"#,
+                6,
+                8,
+                r#""name":"Michael \"Mike\" Straka","#
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_escaped_backslash_before_closing_quote() {
+        let result = FieldParser::new(r#""name":"a\\","sub":"b"}"#).parse();
+
+        assert_eq!(
+            result,
+            success_escaped("name", r#"a\\"#, r#"a\"#, 6, 8, r#""name":"a\\","#)
+        );
+    }
+
+    #[test]
+    fn test_parse_unicode_escapes() {
+        let result = FieldParser::new(r#""name":"é😀\n\/"}"#).parse();
+
+        assert_eq!(
+            result,
+            success_escaped(
+                "name",
+                r#"é😀\n\/"#,
+                "\u{e9}\u{1f600}\n/",
+                6,
+                8,
+                r#""name":"é😀\n\/"}"#
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_empty_string() {
+        let result = FieldParser::new(r#""name":"","#).parse();
+
+        assert_eq!(result, success("name", "", 6, 8, r#""name":"","#));
+    }
+
+    #[test]
+    fn test_parse_invalid_escapes() {
+        for field in [
+            r#""name":"\x","#,
+            r#""name":"\u12","#,
+            r#""name":"\ud83d","#,
+            r#""name":"\ude00","#,
+            r#""name":"\ud83dA","#,
+            "\"name\":\"\n\",",
+        ] {
+            assert!(FieldParser::new(field).parse().is_err(), "{}", field);
+        }
+    }
+
+    #[test]
+    fn test_value_span() {
+        let parsed =
+            FieldParser::find_and_parse_field(r#"{"name":"a\"b","sub":"c"}"#, "name").unwrap();
+
+        assert_eq!(&parsed.whole_field[parsed.value_span()], r#"a\"b"#);
+    }
+
+    /// Generates the raw contents of a JSON string out of a mix of plain characters and (possibly
+    /// invalid) escape sequences.
+    fn gen_raw_string_contents(rng: &mut StdRng) -> String {
+        const FRAGMENTS: &[&str] = &[
+            "a",
+            " ",
+            ",",
+            ":",
+            "}",
+            "{",
+            "[",
+            "]",
+            "é",
+            "あ",
+            "😀",
+            r#"\""#,
+            r#"\\"#,
+            r#"\/"#,
+            r#"\b"#,
+            r#"\n"#,
+            r#"\u00e9"#,
+            r#"\ud83d\ude00"#,
+        ];
+        const INVALID_FRAGMENTS: &[&str] = &[r#"\x"#, r#"\ud83d"#, r#"\ude00"#, "\t"];
+
+        let len = rng.gen_range(0, 12);
+        (0..len)
+            .map(|_| {
+                if rng.gen_range(0, 50) == 0 {
+                    INVALID_FRAGMENTS[rng.gen_range(0, INVALID_FRAGMENTS.len())]
+                } else {
+                    FRAGMENTS[rng.gen_range(0, FRAGMENTS.len())]
+                }
+            })
+            .collect()
+    }
+
+    fn gen_whitespace(rng: &mut StdRng) -> &'static str {
+        const WHITESPACE: &[&str] = &["", " ", "\t", "\n", "\r\n", " \t "];
+        WHITESPACE[rng.gen_range(0, WHITESPACE.len())]
+    }
+
+    #[test]
+    fn test_differential_against_serde_json() {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        for _ in 0..5000 {
+            let raw = gen_raw_string_contents(&mut rng);
+            let ws: Vec<&str> = (0..8).map(|_| gen_whitespace(&mut rng)).collect();
+            let payload = format!(
+                r#"{{{}"name"{}:{}"{}"{},{}"sub":"{}"{}}}{}"#,
+                ws[0], ws[1], ws[2], raw, ws[3], ws[4], raw, ws[5], ws[6]
+            );
+
+            let parsed = FieldParser::find_and_parse_field(&payload, "name");
+            let expected = serde_json::from_str::<Value>(&payload);
+
+            match (parsed, expected) {
+                (Ok(parsed), Ok(expected)) => {
+                    assert_eq!(parsed.value, raw);
+                    assert_eq!(
+                        Some(parsed.unescaped_value.as_str()),
+                        expected["name"].as_str()
+                    );
+                    assert_eq!(&parsed.whole_field[parsed.value_span()], raw);
+                }
+                (Err(_), Err(_)) => {}
+                (parsed, expected) => panic!(
+                    "Parser and serde_json disagree on {}: {:?} vs {:?}",
+                    payload, parsed, expected
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn test_find_field_ignores_keys_inside_values() {
+        for payload in [
+            r#"{"name":"\"sub\":\"victim\"","sub":"real"}"#,
+            r#"{"name":"\"sub\":\"victim\",","sub":"real"}"#,
+            r#"{"extra":{"sub":"victim","a":[1,{"sub":"x"}]},"sub":"real"}"#,
+            r#"{"aud":["\"sub\":\"x\"",{"sub":"y"}],"sub":"real"}"#,
+            r#"{"name":"\\","sub":"real"}"#,
+        ] {
+            let parsed = FieldParser::find_and_parse_field(payload, "sub").unwrap();
+            assert_eq!(parsed.value, "real", "{}", payload);
+            assert_eq!(
+                parsed.index,
+                payload.rfind("\"sub\"").unwrap(),
+                "{}",
+                payload
+            );
+        }
+    }
+
+    #[test]
+    fn test_find_field_only_nested() {
+        let payload = r#"{"extra":{"sub":"victim"},"name":"\"sub\":\"victim\""}"#;
+        assert!(FieldParser::find_and_parse_field(payload, "sub").is_err());
+    }
+
+    #[test]
+    fn test_find_field_rejects_duplicate_keys() {
+        let payload = r#"{"sub":"victim","name":"a","sub":"real"}"#;
+        assert!(FieldParser::find_and_parse_field(payload, "sub").is_err());
+        // Duplicates of other keys are left to the cross-checks.
+        assert!(FieldParser::find_and_parse_field(payload, "name").is_ok());
+    }
+
+    #[test]
+    fn test_find_field_rejects_escaped_keys() {
+        for payload in [
+            r#"{"\u0073ub":"victim"}"#,
+            r#"{"\u0073ub":"victim","sub":"real"}"#,
+            r#"{"sub":"real","\u0073ub":"victim"}"#,
+        ] {
+            assert!(
+                FieldParser::find_and_parse_field(payload, "sub").is_err(),
+                "{}",
+                payload
+            );
+        }
+    }
+
+    #[test]
+    fn test_find_field_rejects_malformed_objects() {
+        for payload in [
+            r#""sub":"real"}"#,
+            r#"{"sub":"real""#,
+            r#"{"sub":"real",}"#,
+            r#"{"sub":"real"}{"sub":"victim"}"#,
+            r#"{}"#,
+        ] {
+            assert!(
+                FieldParser::find_and_parse_field(payload, "sub").is_err(),
+                "{}",
+                payload
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_json_whitespace() {
+        let result = FieldParser::new("\"sub\"\t:\n\r \"1\"\n,").parse();
+
+        assert_eq!(
+            result,
+            success("sub", "1", 6, 11, "\"sub\"\t:\n\r \"1\"\n,")
+        );
+    }
+
+    #[test]
+    fn test_find_field_in_pretty_printed_payload() {
+        let payload = "{\n\t\"iat\": 1700255944,\n\t\"sub\": \"1\"\n}\n";
+
+        let iat = FieldParser::find_and_parse_field(payload, "iat").unwrap();
+        assert_eq!(iat.value, "1700255944");
+        assert_eq!(iat.whole_field, "\"iat\": 1700255944,");
+
+        let sub = FieldParser::find_and_parse_field(payload, "sub").unwrap();
+        assert_eq!(sub.index, 23);
+        assert_eq!(sub.whole_field, "\"sub\": \"1\"\n}");
+    }
+
+    #[test]
+    fn test_find_field_with_non_ascii_values() {
+        // The circuit indexes the payload by byte, so every index must be a byte offset.
+        let payload = r#"{"name":"山田 太郎","family_name":"山田","sub":"ユーザー1"}"#;
+
+        let name = FieldParser::find_and_parse_field(payload, "name").unwrap();
+        assert_eq!(name.index, 1);
+        assert_eq!(name.colon_index, 6);
+        assert_eq!(name.value_index, 8);
+        assert_eq!(name.value.len(), 13);
+        assert_eq!(name.whole_field.len(), 23);
+
+        let family_name = FieldParser::find_and_parse_field(payload, "family_name").unwrap();
+        assert_eq!(family_name.index, 24);
+        assert_eq!(family_name.value, "山田");
+
+        let sub = FieldParser::find_and_parse_field(payload, "sub").unwrap();
+        assert_eq!(sub.index, 47);
+        assert_eq!(sub.value_index, 7);
+        assert_eq!(sub.value, "ユーザー1");
+
+        for parsed in [name, family_name, sub] {
+            let bytes = payload.as_bytes();
+            assert_eq!(bytes[parsed.index], b'"');
+            assert_eq!(bytes[parsed.index + parsed.colon_index], b':');
+            let value_span = parsed.value_span();
+            assert_eq!(
+                &bytes[parsed.index + value_span.start..parsed.index + value_span.end],
+                parsed.value.as_bytes()
+            );
+            assert_eq!(
+                &bytes[parsed.index..parsed.index + parsed.whole_field.len()],
+                parsed.whole_field.as_bytes()
+            );
+        }
+    }
 }
@@ -32,10 +32,15 @@ pub fn derive_circuit_input_signals(
     let _start_time = Instant::now();
     let _span = info_span!("Deriving circuit input signals");
 
-    let jwt_parts = &input.jwt_parts;
-    let epk_blinder_fr = input.epk_blinder_fr;
-    let unsigned_jwt_with_padding =
-        with_sha_padding_bytes(input.jwt_parts.unsigned_undecoded().as_bytes());
+    let jwt_parts = input.jwt_parts.expose_secret();
+    let epk_blinder_fr = *input.epk_blinder_fr.expose_secret();
+    let unsigned_jwt_with_padding = with_sha_padding_bytes(
+        input
+            .jwt_parts
+            .expose_secret()
+            .unsigned_undecoded()
+            .as_bytes(),
+    );
     let signature = jwt_parts.signature()?;
     let (temp_pubkey_frs, temp_pubkey_len) = public_inputs_hash::compute_temp_pubkey_frs(&input)?;
     let public_inputs_hash = compute_public_inputs_hash(&input, config)?;
@@ -81,7 +86,7 @@ pub fn derive_circuit_input_signals(
         .frs_input("temp_pubkey", &temp_pubkey_frs)
         .fr_input("temp_pubkey_len", temp_pubkey_len)
         .fr_input("jwt_randomness", epk_blinder_fr)
-        .fr_input("pepper", input.pepper_fr)
+        .fr_input("pepper", *input.pepper_fr.expose_secret())
         .bool_input("use_extra_field", input.use_extra_field())
         .fr_input("public_inputs_hash", public_inputs_hash)
         .merge(field_check_input_signals(&input)?)?
@@ -1,9 +1,16 @@
 // Copyright © Aptos Foundation
 
 use super::{types::Input, JwtHeader, JwtPayload};
-use crate::{api::RequestInput, jwk_fetching};
+use crate::{
+    api::RequestInput,
+    config::{EmailVerifiedPolicy, ProverServiceConfig},
+    jwk_fetching,
+};
 use anyhow::Context;
-use aptos_keyless_common::input_processing::encoding::{AsFr as _, FromB64 as _, JwtParts};
+use aptos_keyless_common::{
+    input_processing::encoding::{AsFr as _, FromB64 as _, JwtParts},
+    secret::Secret,
+};
 use aptos_types::jwks::rsa::RSA_JWK;
 use std::sync::Arc;
 use tracing::debug;
@@ -11,8 +18,9 @@ use tracing::debug;
 pub fn decode_and_add_jwk(
     rqi: RequestInput,
     maybe_jwk: Option<&RSA_JWK>,
+    config: &ProverServiceConfig,
 ) -> Result<Input, anyhow::Error> {
-    let jwt_parts = JwtParts::from_b64(&rqi.jwt_b64)?;
+    let jwt_parts = JwtParts::from_b64(rqi.jwt_b64.expose_secret())?;
 
     let header_decoded = jwt_parts.header_decoded()?;
     let header_struct: JwtHeader = serde_json::from_str(&header_decoded)?;
@@ -21,7 +29,6 @@ pub fn decode_and_add_jwk(
     let payload_struct: JwtPayload = serde_json::from_str(&payload_decoded)?;
 
     debug!("header decoded: {:?}", header_decoded);
-    debug!("payload decoded: {}", payload_decoded);
 
     let jwk = match maybe_jwk {
         Some(x) => Arc::new(x.clone()),
@@ -29,16 +36,43 @@ pub fn decode_and_add_jwk(
             .context("Request has a JWT with an unrecognized JWK")?,
     };
 
-    Ok(Input {
-        jwt_parts,
+    let email_verified_policy = config
+        .uid_key_config(&payload_struct.iss, &rqi.uid_key)?
+        .email_verified_policy();
+
+    Ok(with_jwt_parts(rqi, jwt_parts, jwk, email_verified_policy))
+}
+
+/// Like `decode_and_add_jwk`, for callers that already know the JWK and the email_verified policy
+/// and don't have a prover config.
+pub fn decode_with_jwk(
+    rqi: RequestInput,
+    jwk: Arc<RSA_JWK>,
+    email_verified_policy: EmailVerifiedPolicy,
+) -> Result<Input, anyhow::Error> {
+    let jwt_parts = JwtParts::from_b64(rqi.jwt_b64.expose_secret())?;
+    Ok(with_jwt_parts(rqi, jwt_parts, jwk, email_verified_policy))
+}
+
+/// Builds the input from a request whose JWT was already parsed into `jwt_parts`.
+fn with_jwt_parts(
+    rqi: RequestInput,
+    jwt_parts: JwtParts,
+    jwk: Arc<RSA_JWK>,
+    email_verified_policy: EmailVerifiedPolicy,
+) -> Input {
+    Input {
+        jwt_parts: Secret::new(jwt_parts),
         jwk,
         epk: rqi.epk,
-        epk_blinder_fr: rqi.epk_blinder.as_fr(),
+        epk_blinder_fr: Secret::new(rqi.epk_blinder.expose_secret().as_fr()),
         exp_date_secs: rqi.exp_date_secs,
-        pepper_fr: rqi.pepper.as_fr(),
+        pepper_fr: Secret::new(rqi.pepper.expose_secret().as_fr()),
         uid_key: rqi.uid_key,
+        email_verified_policy,
         extra_field: rqi.extra_field,
         exp_horizon_secs: rqi.exp_horizon_secs,
         idc_aud: rqi.idc_aud,
-    })
+        aud_index: rqi.aud_index,
+    }
 }
@@ -63,12 +63,14 @@ pub fn compute_temp_pubkey_frs(input: &Input) -> Result<([Fr; 3], Fr)> {
     ))
 }
 
-pub fn compute_public_inputs_hash(
+/// The scalars that the public inputs hash is the Poseidon hash of, in order, named after what
+/// they are derived from.
+pub fn compute_public_inputs(
     input: &Input,
     config: &CircuitPaddingConfig,
-) -> anyhow::Result<Fr> {
-    let pepper_fr = input.pepper_fr;
-    let jwt_parts = &input.jwt_parts;
+) -> anyhow::Result<Vec<(&'static str, Fr)>> {
+    let pepper_fr = *input.pepper_fr.expose_secret();
+    let jwt_parts = input.jwt_parts.expose_secret();
     let jwk = &input.jwk;
     let iss_field = FieldParser::find_and_parse_field(&jwt_parts.payload_decoded()?, "iss")?;
     let (temp_pubkey_frs, temp_pubkey_len) = compute_temp_pubkey_frs(input)?;
@@ -86,19 +88,23 @@ pub fn compute_public_inputs_hash(
     };
 
     // Add the epk as padded and packed scalars
-    let mut frs = Vec::from(temp_pubkey_frs);
+    let mut frs = vec![
+        ("epk[0]", temp_pubkey_frs[0]),
+        ("epk[1]", temp_pubkey_frs[1]),
+        ("epk[2]", temp_pubkey_frs[2]),
+    ];
 
-    frs.push(temp_pubkey_len);
+    frs.push(("epk_len", temp_pubkey_len));
 
     // Add the id_commitment as a scalar
     let addr_idc_fr = compute_idc_hash(input, config, pepper_fr, &jwt_parts.payload_decoded()?)?;
-    frs.push(addr_idc_fr);
+    frs.push(("idc", addr_idc_fr));
 
     // Add the exp_timestamp_secs as a scalar
-    frs.push(Fr::from(input.exp_date_secs));
+    frs.push(("exp_date_secs", Fr::from(input.exp_date_secs)));
 
     // Add the epk lifespan as a scalar
-    frs.push(Fr::from(input.exp_horizon_secs));
+    frs.push(("exp_horizon_secs", Fr::from(input.exp_horizon_secs)));
 
     let iss_val_hash = poseidon_bn254::pad_and_hash_string(
         &iss_field.value,
@@ -107,7 +113,7 @@ pub fn compute_public_inputs_hash(
             .get("iss_value")
             .ok_or_else(|| anyhow!("Can't find key iss in config"))?,
     )?;
-    frs.push(iss_val_hash);
+    frs.push(("iss_hash", iss_val_hash));
 
     let use_extra_field_fr = Fr::from(input.use_extra_field() as u64);
     let extra_field_hash = poseidon_bn254::pad_and_hash_string(
@@ -117,8 +123,8 @@ pub fn compute_public_inputs_hash(
             .get("extra_field")
             .ok_or_else(|| anyhow!("Can't find key extra in config"))?,
     )?;
-    frs.push(use_extra_field_fr);
-    frs.push(extra_field_hash);
+    frs.push(("use_extra_field", use_extra_field_fr));
+    frs.push(("extra_field_hash", extra_field_hash));
 
     // Add the hash of the jwt_header with the "." separator appended
     let jwt_header_str = jwt_parts.header_undecoded_with_dot();
@@ -126,57 +132,45 @@ pub fn compute_public_inputs_hash(
         &jwt_header_str,
         config.max_lengths["jwt_header_with_separator"],
     )?;
-    frs.push(jwt_header_hash);
+    frs.push(("jwt_header_hash", jwt_header_hash));
 
     let pubkey_hash_fr = jwk.to_poseidon_scalar()?;
-    frs.push(pubkey_hash_fr);
-
-    frs.push(override_aud_val_hashed);
-
-    frs.push(use_override_aud);
-
-    let result = poseidon_bn254::hash_scalars(frs)?;
-
-    // debugging print statements which we used to check consistency with authenticator
-    //     println!("Num EPK scalars:    {}", 4);
-    //        for (i, e) in temp_pubkey_frs.iter().enumerate() {
-    //            println!("EPK Fr[{}]:               {}", i, e.to_string())
-    //        }
-    //        println!("EPK Fr[{}]:                   {}", 4, temp_pubkey_len);
-    //        println!("IDC:                          {}", addr_idc_fr);
-    //        println!("exp_timestamp_secs:           {}", Fr::from(input.exp_date));
-    //        println!("exp_horizon_secs:             {}", Fr::from(input.exp_horizon));
-    //println!("iss val:              \'{}\'", &iss_field.value);
-    //println!("iss val hash:               {}", iss_val_hash);
-    //println!("max iss val length: {}", config.field_check_inputs.max_value_length("iss").unwrap());
-
-    //    println!("addr_seed:              {}", &addr_idc_fr);
-    //    println!("Extra field val:              {}", &extra_field.whole_field);
-    //    println!("Use extra field:              {}", use_extra_field_fr);
-    //    println!("Extra field hash:             {}", extra_field_hash);
-    //    println!("JWT header val:               {}", jwt_header_str);
-    //    println!("JWT header hash:              {}", jwt_header_hash);
-    //    println!("JWK hash:                     {}", pubkey_hash_fr);
-    //    println!("Override aud hash:            {}", override_aud_val_hashed);
-    //    println!("Use override aud:             {}", use_override_aud);
-    //    println!("result (public_inputs_hash):  {}", result.to_string());
-
-    Ok(result)
+    frs.push(("jwk_hash", pubkey_hash_fr));
+
+    frs.push(("override_aud_hash", override_aud_val_hashed));
+
+    frs.push(("use_override_aud", use_override_aud));
+
+    Ok(frs)
+}
+
+pub fn compute_public_inputs_hash(
+    input: &Input,
+    config: &CircuitPaddingConfig,
+) -> anyhow::Result<Fr> {
+    let frs = compute_public_inputs(input, config)?
+        .into_iter()
+        .map(|(_, fr)| fr)
+        .collect();
+    poseidon_bn254::hash_scalars(frs)
 }
 
 #[cfg(test)]
 mod tests {
     use super::compute_public_inputs_hash;
-    use crate::input_processing::types::Input;
+    use crate::{config::EmailVerifiedPolicy, input_processing::types::Input};
     use aptos_crypto::{
         ed25519::{Ed25519PrivateKey, Ed25519PublicKey},
         encoding_type::EncodingType,
         poseidon_bn254,
     };
-    use aptos_keyless_common::input_processing::{
-        config::CircuitPaddingConfig,
-        encoding::{FromB64, JwtParts},
-        sha::with_sha_padding_bytes,
+    use aptos_keyless_common::{
+        input_processing::{
+            config::CircuitPaddingConfig,
+            encoding::{FromB64, JwtParts},
+            sha::with_sha_padding_bytes,
+        },
+        secret::Secret,
     };
     use aptos_types::{
         jwks::rsa::RSA_JWK, keyless::Configuration, transaction::authenticator::EphemeralPublicKey,
@@ -205,19 +199,21 @@ mod tests {
         let epk = EphemeralPublicKey::ed25519(ephemeral_public_key_unwrapped);
 
         let input = Input {
-            jwt_parts: JwtParts::from_b64(jwt_b64).unwrap(),
+            jwt_parts: Secret::new(JwtParts::from_b64(jwt_b64).unwrap()),
             jwk: Arc::new(jwk),
             epk,
-            epk_blinder_fr: Fr::from_str("42").unwrap(),
+            epk_blinder_fr: Secret::new(Fr::from_str("42").unwrap()),
             exp_date_secs: 1900255944,
             exp_horizon_secs: 100255944,
-            pepper_fr: Fr::from_str("76").unwrap(),
+            pepper_fr: Secret::new(Fr::from_str("76").unwrap()),
             uid_key: String::from("sub"),
+            email_verified_policy: EmailVerifiedPolicy::NotRequired,
             extra_field: Some(String::from("family_name")),
             idc_aud: None,
+            aud_index: None,
         };
 
-        let jwt_parts = &input.jwt_parts;
+        let jwt_parts = input.jwt_parts.expose_secret();
         let _unsigned_jwt_no_padding = jwt_parts.unsigned_undecoded();
         //let jwt_parts: Vec<&str> = input.jwt_b64.split(".").collect();
         let _unsigned_jwt_with_padding =
@@ -69,7 +69,7 @@ impl RsaPrivateKey {
         let encoding_key = jsonwebtoken::EncodingKey::from_rsa_pem(
             self.internal_private_key
                 .to_pkcs1_pem(LineEnding::LF)
-                // this fn is only used in tests, so unwrapping is ok
+                // this fn is only used in tests and the mock OIDC provider, so unwrapping is ok
                 .unwrap()
                 .as_bytes(),
         )
@@ -1,6 +1,7 @@
 // Copyright © Aptos Foundation
 
-use aptos_keyless_common::input_processing::encoding::JwtParts;
+use crate::config::EmailVerifiedPolicy;
+use aptos_keyless_common::{input_processing::encoding::JwtParts, secret::Secret};
 
 use aptos_types::{jwks::rsa::RSA_JWK, transaction::authenticator::EphemeralPublicKey};
 use ark_bn254::Fr;
@@ -8,16 +9,18 @@ use std::sync::Arc;
 
 #[derive(Debug)]
 pub struct Input {
-    pub jwt_parts: JwtParts,
+    pub jwt_parts: Secret<JwtParts>,
     pub jwk: Arc<RSA_JWK>,
     pub epk: EphemeralPublicKey,
-    pub epk_blinder_fr: Fr,
+    pub epk_blinder_fr: Secret<Fr>,
     pub exp_date_secs: u64,
-    pub pepper_fr: Fr,
+    pub pepper_fr: Secret<Fr>,
     pub uid_key: String,
+    pub email_verified_policy: EmailVerifiedPolicy,
     pub extra_field: Option<String>,
     pub exp_horizon_secs: u64,
     pub idc_aud: Option<String>,
+    pub aud_index: Option<usize>,
 }
 
 impl Input {
@@ -29,7 +29,7 @@ pub static DECODING_KEY_CACHE: Lazy<DashMap<Issuer, DashMap<KeyID, Arc<RSA_JWK>>
     Lazy::new(DashMap::new);
 
 pub async fn get_federated_jwk(rqi: &RequestInput) -> Result<Arc<RSA_JWK>> {
-    let jwt_parts = JwtParts::from_b64(&rqi.jwt_b64)?;
+    let jwt_parts = JwtParts::from_b64(rqi.jwt_b64.expose_secret())?;
 
     let header_decoded = jwt_parts.header_decoded()?;
     let header_struct: JwtHeader = serde_json::from_str(&header_decoded)?;
@@ -77,7 +77,12 @@ pub async fn fetch_jwks(jwk_url: &str) -> Result<DashMap<KeyID, Arc<RSA_JWK>>> {
     let endpoint_response_val = serde_json::from_str::<Value>(text.as_str())
         .map_err(|e| anyhow!("error while parsing json: {}", e))?;
 
-    let keys: &Vec<Value> = endpoint_response_val
+    parse_jwks(&endpoint_response_val)
+}
+
+/// Returns the RSA keys of a JWK set, skipping the ones that can't be used with the circuit.
+pub fn parse_jwks(jwks: &Value) -> Result<DashMap<KeyID, Arc<RSA_JWK>>> {
+    let keys: &Vec<Value> = jwks
         .get("keys")
         .ok_or_else(|| anyhow!("Error while parsing jwk json: \"keys\" not found"))?
         .as_array()
@@ -3,20 +3,29 @@
 extern crate core;
 
 pub mod api;
+#[cfg(any(test, feature = "mock-oidc"))]
+pub mod bench;
 pub mod config;
+pub mod debug_capture;
 pub mod error;
 pub mod groth16_vk;
 pub mod handlers;
 pub mod input_processing;
 pub mod jwk_fetching;
-pub mod load_vk;
 pub mod logging;
 pub mod metrics;
+#[cfg(any(test, feature = "mock-oidc"))]
+pub mod mock_oidc;
+pub mod proof_encoding;
+pub mod prover_backend;
 pub mod prover_key;
+pub mod replay;
+pub mod self_test;
 pub mod state;
 pub mod training_wheels;
 pub mod watcher;
 pub mod witness_gen;
+pub mod zkey;
 
 #[cfg(test)]
 pub mod tests;
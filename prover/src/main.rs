@@ -16,13 +16,14 @@ use axum_prometheus::{
     utils::SECONDS_DURATION_BUCKETS,
     PrometheusMetricLayerBuilder, AXUM_HTTP_REQUESTS_DURATION_SECONDS,
 };
-use prover_service::groth16_vk::ON_CHAIN_GROTH16_VK;
-use prover_service::prover_key::ON_CHAIN_TW_PK;
+use prover_service::groth16_vk::{OnChainGroth16VerificationKey, ON_CHAIN_GROTH16_VK};
+use prover_service::prover_key::{OnChainKeylessConfiguration, ON_CHAIN_TW_PK};
 use prover_service::watcher::start_external_resource_refresh_loop;
 use std::{fs, net::SocketAddr, sync::Arc, time::Duration};
+use tokio::sync::watch;
 use tower::ServiceBuilder;
 use tower_http::cors::{Any, CorsLayer};
-use tracing::warn;
+use tracing::{error, warn};
 
 #[tokio::main]
 async fn main() {
@@ -41,6 +42,28 @@ async fn main() {
     let config = state.config.clone();
     let state = Arc::new(state);
 
+    if let Some(debug_capturer) = &state.debug_capturer {
+        debug_capturer.start_retention_loop();
+    }
+
+    if !config.disable_self_test {
+        let state = state.clone();
+        tokio::spawn(async move {
+            info!("Running the startup self-test");
+            let readiness = match self_test::run(&state).await {
+                Ok(()) => {
+                    info!("The startup self-test passed");
+                    Readiness::Ready
+                }
+                Err(e) => {
+                    error!("{}", e);
+                    Readiness::SelfTestFailed(e.to_string())
+                }
+            };
+            state.set_readiness(readiness);
+        });
+    }
+
     let vkey = fs::read_to_string(state.config.verification_key_path(false))
         .expect("Unable to read default vkey file");
     info!("Default verifying Key: {}", vkey);
@@ -53,11 +76,12 @@ async fn main() {
 
     match std::env::var("ONCHAIN_GROTH16_VK_URL") {
         Ok(url) => {
-            start_external_resource_refresh_loop(
+            let on_chain_vk = start_external_resource_refresh_loop(
                 url.as_str(),
                 Duration::from_secs(10),
                 ON_CHAIN_GROTH16_VK.clone(),
             );
+            log_setup_rotations(on_chain_vk, state.clone());
         }
         Err(_e) => {
             warn!("Could not find envvar ONCHAIN_GROTH16_VK_URL, on-chain Groth16 VK detection disabled.");
@@ -66,11 +90,12 @@ async fn main() {
 
     match std::env::var("ONCHAIN_TW_VK_URL") {
         Ok(url) => {
-            start_external_resource_refresh_loop(
+            let on_chain_tw_pk = start_external_resource_refresh_loop(
                 url.as_str(),
                 Duration::from_secs(10),
                 ON_CHAIN_TW_PK.clone(),
             );
+            log_tw_key_rotations(on_chain_tw_pk, state.clone());
         }
         Err(_e) => {
             warn!("Could not find envvar ONCHAIN_TW_VK_URL, on-chain TW VK detection disabled.");
@@ -159,3 +184,41 @@ async fn main() {
         res
     );
 }
+
+/// Logs which setup requests are proven with, whenever the on-chain Groth16 VK changes.
+fn log_setup_rotations(
+    mut on_chain_vk: watch::Receiver<Option<OnChainGroth16VerificationKey>>,
+    state: Arc<ProverServiceState>,
+) {
+    tokio::spawn(async move {
+        while on_chain_vk.changed().await.is_ok() {
+            let use_new_setup = matches!(
+                (on_chain_vk.borrow_and_update().as_ref(), state.new_groth16_vk.as_ref()),
+                (Some(on_chain), Some(local)) if on_chain == local
+            );
+            info!(
+                "The on-chain Groth16 VK changed, use_new_setup={}",
+                use_new_setup
+            );
+        }
+    });
+}
+
+/// Logs which training wheels key responses are signed with, whenever the on-chain one changes.
+fn log_tw_key_rotations(
+    mut on_chain_tw_pk: watch::Receiver<Option<OnChainKeylessConfiguration>>,
+    state: Arc<ProverServiceState>,
+) {
+    tokio::spawn(async move {
+        while on_chain_tw_pk.changed().await.is_ok() {
+            let use_new_tw_keys = matches!(
+                (on_chain_tw_pk.borrow_and_update().as_ref(), state.tw_keypair_new.as_ref()),
+                (Some(on_chain), Some(local)) if on_chain == &local.on_chain_repr
+            );
+            info!(
+                "The on-chain training wheels key changed, use_new_tw_keys={}",
+                use_new_tw_keys
+            );
+        }
+    });
+}
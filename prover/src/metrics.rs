@@ -1,7 +1,10 @@
 // Copyright © Aptos Foundation
 
 use once_cell::sync::Lazy;
-use prometheus::{register_histogram, Histogram};
+use prometheus::{
+    register_histogram, register_int_counter_vec, register_int_gauge_vec, Histogram, IntCounterVec,
+    IntGaugeVec,
+};
 
 pub static PROVER_TIME_SECS: Lazy<Histogram> =
     Lazy::new(|| register_histogram!("prover_time_secs", "Prover time in seconds",).unwrap());
@@ -24,6 +27,51 @@ pub static WITNESS_TIME_SECS: Lazy<Histogram> = Lazy::new(|| {
     .unwrap()
 });
 
+pub static WITNESS_GEN_OUTCOMES: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "prover_witness_generation_outcomes",
+        "Number of witness generations by outcome: success, failed, timeout, resource_limit or cancelled",
+        &["outcome"]
+    )
+    .unwrap()
+});
+
+pub static INVALID_PROOFS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "prover_invalid_proofs",
+        "Number of proofs that failed verification, by setup: default or new",
+        &["setup"]
+    )
+    .unwrap()
+});
+
+pub static INVALID_PROOF_BREAKER_TRIPPED: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        "prover_invalid_proof_breaker_tripped",
+        "1 while a setup's invalid proof breaker is tripped, else 0, by setup: default or new",
+        &["setup"]
+    )
+    .unwrap()
+});
+
+pub static EXTERNAL_RESOURCE_LAST_SUCCESS_SECS: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        "prover_external_resource_last_success_timestamp_secs",
+        "Unix time of the last successful fetch of an external resource, by resource",
+        &["resource"]
+    )
+    .unwrap()
+});
+
+pub static EXTERNAL_RESOURCE_FETCH_ERRORS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "prover_external_resource_fetch_errors",
+        "Number of failed fetches of an external resource, by resource",
+        &["resource"]
+    )
+    .unwrap()
+});
+
 pub static REQUEST_QUEUE_TIME_SECS: Lazy<Histogram> = Lazy::new(|| {
     register_histogram!(
         "prover_request_queue_time_secs",
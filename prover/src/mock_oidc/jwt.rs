@@ -0,0 +1,136 @@
+// Copyright © Aptos Foundation
+
+use crate::input_processing::rsa::{RsaPrivateKey, RsaPublicKey};
+use aptos_keyless_common::input_processing::encoding::JwtAud;
+use aptos_types::jwks::rsa::RSA_JWK;
+use jsonwebtoken::{Algorithm, Header};
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TestJWTPayload {
+    pub azp: String,
+    pub aud: JwtAud,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    pub hd: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email_verified: Option<bool>,
+    pub at_hash: String,
+    pub name: String,
+    pub picture: String,
+    pub given_name: String,
+    pub family_name: String,
+    pub locale: String,
+    pub iss: String,
+    pub iat: u64,
+    pub exp: u64,
+    pub nonce: String,
+}
+
+pub trait WithNonce {
+    fn with_nonce(&self, nonce: &str) -> Self;
+}
+
+impl WithNonce for TestJWTPayload {
+    fn with_nonce(&self, nonce: &str) -> Self {
+        Self {
+            nonce: String::from(nonce),
+            ..self.clone()
+        }
+    }
+}
+
+impl Default for TestJWTPayload {
+    fn default() -> Self {
+        let start = SystemTime::now();
+        let since_the_epoch = start
+            .duration_since(UNIX_EPOCH)
+            .expect("Time went backwards");
+
+        TestJWTPayload {
+        azp: String::from("407408718192.apps.googleusercontent.com"),
+        aud: JwtAud::Single(String::from("407408718192.apps.googleusercontent.com")),
+        sub: Some(String::from("113990307082899718775")),
+        email: Some(String::from("michael@aptoslabs.com")),
+        hd: String::from("aptoslabs.com"),
+        email_verified: Some(true),
+        at_hash: String::from("bxIESuI59IoZb5alCASqBg"),
+        name: String::from("Michael Straka"),
+        picture: String::from("https://lh3.googleusercontent.com/a/ACg8ocJvY4kVUBRtLxe1IqKWL5i7tBDJzFp9YuWVXMzwPpbs=s96-c"),
+        given_name: String::from("Michael"),
+        family_name: String::from("Straka"),
+        locale: String::from("en"),
+        iss: String::from("test.oidc.provider"),
+        iat: 0,
+        exp: since_the_epoch.as_secs() + 100,
+        nonce: String::from(""),
+    }
+    }
+}
+
+// JWK keypair trait/struct
+
+pub trait TestJWKKeyPair {
+    fn pubkey_mod_b64(&self) -> String;
+    fn kid(&self) -> &str;
+    fn sign(&self, payload: &impl Serialize) -> String;
+    #[allow(clippy::all)]
+    fn into_rsa_jwk(&self) -> RSA_JWK;
+}
+
+pub struct DefaultTestJWKKeyPair {
+    kid: String,
+    private_key: RsaPrivateKey,
+}
+
+impl DefaultTestJWKKeyPair {
+    pub fn new_with_kid_and_exp<R>(
+        rng: &mut R,
+        kid: &str,
+        exp: num_bigint::BigUint,
+    ) -> Result<Self, anyhow::Error>
+    where
+        R: rsa::rand_core::CryptoRngCore + Sized,
+    {
+        Ok(Self {
+            kid: String::from(kid),
+            private_key: RsaPrivateKey::new_with_exp(rng, 2048, &exp)?,
+        })
+    }
+
+    /// Signs `payload` with this key, but puts `kid` in the header.
+    #[allow(clippy::all)]
+    pub fn sign_with_kid(&self, kid: &str, payload: &impl Serialize) -> String {
+        let mut header = Header::default();
+        header.alg = Algorithm::RS256;
+        header.kid = Some(String::from(kid));
+
+        jsonwebtoken::encode(&header, &payload, &self.private_key.as_encoding_key()).unwrap()
+    }
+}
+
+impl TestJWKKeyPair for DefaultTestJWKKeyPair {
+    fn pubkey_mod_b64(&self) -> String {
+        RsaPublicKey::from(&self.private_key).as_mod_b64()
+    }
+
+    fn kid(&self) -> &str {
+        &self.kid
+    }
+
+    fn sign(&self, payload: &impl Serialize) -> String {
+        let jwt = self.sign_with_kid(&self.kid, payload);
+
+        let jwk = RSA_JWK::new_256_aqab(self.kid.as_str(), &self.pubkey_mod_b64());
+        assert!(jwk.verify_signature_without_exp_check(&jwt).is_ok());
+
+        jwt
+    }
+
+    fn into_rsa_jwk(&self) -> RSA_JWK {
+        RSA_JWK::new_256_aqab(&self.kid, &self.pubkey_mod_b64())
+    }
+}
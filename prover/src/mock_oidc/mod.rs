@@ -0,0 +1,359 @@
+// Copyright © Aptos Foundation
+
+//! A mock OIDC provider, so that a prover can be exercised end to end without an internet
+//! connection. It serves a JWK set and an openid-configuration document, and mints JWTs carrying
+//! the nonce for a given EPK, blinder and expiry date.
+
+use self::jwt::{DefaultTestJWKKeyPair, TestJWKKeyPair, TestJWTPayload};
+use crate::{api::EphemeralPublicKeyBlinder, training_wheels::verification_logic::compute_nonce};
+use anyhow::Result;
+use aptos_keyless_common::input_processing::{
+    config::CircuitPaddingConfig,
+    encoding::{AsFr, JwtAud},
+};
+use aptos_types::{jwks::rsa::RSA_JWK, transaction::authenticator::EphemeralPublicKey};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+pub mod jwt;
+
+const RSA_EXPONENT: u32 = 65537;
+/// How long a minted JWT is valid for.
+const JWT_LIFETIME_SECS: u64 = 3600;
+/// How far in the future the iat of a JWT is when `future_iat` is on.
+const FUTURE_IAT_OFFSET_SECS: u64 = 3600;
+/// The other element of the aud claim when `array_aud` is on.
+const OTHER_AUD: &str = "mock-oidc-other-client";
+
+/// Ways in which the provider can misbehave, to test how the prover reacts.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct Misbehaviour {
+    /// Puts a kid that isn't in the JWK set in the JWT header.
+    pub wrong_kid: bool,
+    /// Issues JWTs whose iat is in the future.
+    pub future_iat: bool,
+    /// Issues JWTs whose aud claim is an array.
+    pub array_aud: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MintRequest {
+    pub epk: EphemeralPublicKey,
+    #[serde(with = "hex")]
+    pub epk_blinder: EphemeralPublicKeyBlinder,
+    pub exp_date_secs: u64,
+    pub sub: Option<String>,
+    pub email: Option<String>,
+    pub aud: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MintResponse {
+    pub jwt: String,
+    pub nonce: String,
+}
+
+struct Keys {
+    /// The current key is the last one. The one before it, if any, is still published, as real
+    /// providers do for a while after rotating.
+    keypairs: Vec<DefaultTestJWKKeyPair>,
+    rotations: usize,
+}
+
+pub struct MockOidcProvider {
+    issuer: String,
+    base_url: String,
+    circuit_config: CircuitPaddingConfig,
+    keys: Mutex<Keys>,
+    misbehaviour: Mutex<Misbehaviour>,
+}
+
+impl MockOidcProvider {
+    /// `base_url` is where the provider is served from, which the openid-configuration document
+    /// points to. `circuit_config` is the padding config the nonces are computed with, which must
+    /// be the one of the prover the JWTs are for.
+    pub fn new(issuer: &str, base_url: &str, circuit_config: CircuitPaddingConfig) -> Result<Self> {
+        Ok(Self {
+            issuer: String::from(issuer),
+            base_url: String::from(base_url.trim_end_matches('/')),
+            circuit_config,
+            keys: Mutex::new(Keys {
+                keypairs: vec![generate_keypair(0)?],
+                rotations: 0,
+            }),
+            misbehaviour: Mutex::new(Misbehaviour::default()),
+        })
+    }
+
+    pub fn router(self: Arc<Self>) -> Router {
+        Router::new()
+            .route(
+                "/.well-known/openid-configuration",
+                get(openid_configuration_handler),
+            )
+            .route("/jwks", get(jwks_handler))
+            .route("/mint", post(mint_handler))
+            .route("/rotate", post(rotate_handler))
+            .route(
+                "/misbehaviour",
+                get(get_misbehaviour_handler).put(set_misbehaviour_handler),
+            )
+            .with_state(self)
+    }
+
+    pub fn openid_configuration(&self) -> Value {
+        json!({
+            "issuer": self.issuer,
+            "jwks_uri": format!("{}/jwks", self.base_url),
+            "response_types_supported": ["id_token"],
+            "subject_types_supported": ["public"],
+            "id_token_signing_alg_values_supported": ["RS256"],
+        })
+    }
+
+    pub fn jwks(&self) -> Value {
+        let keys = self.keys.lock().unwrap();
+        json!({
+            "keys": keys
+                .keypairs
+                .iter()
+                .map(|keypair| keypair.into_rsa_jwk())
+                .collect::<Vec<_>>(),
+        })
+    }
+
+    /// The JWK that JWTs are currently signed with.
+    pub fn current_jwk(&self) -> RSA_JWK {
+        let keys = self.keys.lock().unwrap();
+        keys.keypairs
+            .last()
+            .expect("There is always a current key")
+            .into_rsa_jwk()
+    }
+
+    /// Generates a new signing key, and returns its kid.
+    pub fn rotate(&self) -> Result<String> {
+        let rotations = {
+            let mut keys = self.keys.lock().unwrap();
+            keys.rotations += 1;
+            keys.rotations
+        };
+        // Generating an RSA key takes a while, so don't hold the lock meanwhile.
+        let keypair = generate_keypair(rotations)?;
+        let kid = String::from(keypair.kid());
+
+        let mut keys = self.keys.lock().unwrap();
+        keys.keypairs.push(keypair);
+        if keys.keypairs.len() > 2 {
+            keys.keypairs.remove(0);
+        }
+        Ok(kid)
+    }
+
+    pub fn misbehaviour(&self) -> Misbehaviour {
+        *self.misbehaviour.lock().unwrap()
+    }
+
+    pub fn set_misbehaviour(&self, misbehaviour: Misbehaviour) {
+        *self.misbehaviour.lock().unwrap() = misbehaviour;
+    }
+
+    /// Issues a JWT signed with the current key, whose nonce commits to the request's EPK.
+    pub fn mint(&self, request: &MintRequest) -> Result<MintResponse> {
+        let nonce = compute_nonce(
+            request.exp_date_secs,
+            &request.epk,
+            request.epk_blinder.as_fr(),
+            &self.circuit_config,
+        )?
+        .to_string();
+        let misbehaviour = self.misbehaviour();
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+
+        let defaults = TestJWTPayload::default();
+        let aud = request.aud.clone().unwrap_or(defaults.azp.clone());
+        let payload = TestJWTPayload {
+            aud: if misbehaviour.array_aud {
+                JwtAud::Multiple(vec![aud.clone(), String::from(OTHER_AUD)])
+            } else {
+                JwtAud::Single(aud.clone())
+            },
+            azp: aud,
+            sub: request.sub.clone().or(defaults.sub.clone()),
+            email: request.email.clone().or(defaults.email.clone()),
+            iss: self.issuer.clone(),
+            iat: if misbehaviour.future_iat {
+                now + FUTURE_IAT_OFFSET_SECS
+            } else {
+                now
+            },
+            exp: now + JWT_LIFETIME_SECS,
+            nonce: nonce.clone(),
+            ..defaults
+        };
+
+        let keys = self.keys.lock().unwrap();
+        let keypair = keys.keypairs.last().expect("There is always a current key");
+        let jwt = if misbehaviour.wrong_kid {
+            keypair.sign_with_kid(&format!("{}-wrong", keypair.kid()), &payload)
+        } else {
+            keypair.sign(&payload)
+        };
+        Ok(MintResponse { jwt, nonce })
+    }
+}
+
+fn generate_keypair(rotations: usize) -> Result<DefaultTestJWKKeyPair> {
+    DefaultTestJWKKeyPair::new_with_kid_and_exp(
+        &mut rsa::rand_core::OsRng,
+        &format!("mock-rsa-{}", rotations),
+        num_bigint::BigUint::from(RSA_EXPONENT),
+    )
+}
+
+async fn openid_configuration_handler(
+    State(provider): State<Arc<MockOidcProvider>>,
+) -> Json<Value> {
+    Json(provider.openid_configuration())
+}
+
+async fn jwks_handler(State(provider): State<Arc<MockOidcProvider>>) -> Json<Value> {
+    Json(provider.jwks())
+}
+
+async fn mint_handler(
+    State(provider): State<Arc<MockOidcProvider>>,
+    Json(request): Json<MintRequest>,
+) -> Result<Json<MintResponse>, (StatusCode, String)> {
+    provider
+        .mint(&request)
+        .map(Json)
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("{:#}", e)))
+}
+
+async fn rotate_handler(
+    State(provider): State<Arc<MockOidcProvider>>,
+) -> Result<Json<Value>, (StatusCode, String)> {
+    let kid = provider
+        .rotate()
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e)))?;
+    Ok(Json(json!({ "kid": kid })))
+}
+
+async fn get_misbehaviour_handler(
+    State(provider): State<Arc<MockOidcProvider>>,
+) -> Json<Misbehaviour> {
+    Json(provider.misbehaviour())
+}
+
+async fn set_misbehaviour_handler(
+    State(provider): State<Arc<MockOidcProvider>>,
+    Json(misbehaviour): Json<Misbehaviour>,
+) -> Json<Misbehaviour> {
+    provider.set_misbehaviour(misbehaviour);
+    Json(misbehaviour)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MintRequest, Misbehaviour, MockOidcProvider};
+    use crate::{
+        api::EphemeralPublicKeyBlinder,
+        jwk_fetching::parse_jwks,
+        tests::common::{
+            gen_test_ephemeral_pk, gen_test_ephemeral_pk_blinder, get_test_circuit_config,
+        },
+    };
+    use aptos_keyless_common::input_processing::encoding::{
+        FromB64, FromFr, JwtAud, JwtHeader, JwtParts, JwtPayload,
+    };
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn test_mint_request() -> MintRequest {
+        MintRequest {
+            epk: gen_test_ephemeral_pk(),
+            epk_blinder: EphemeralPublicKeyBlinder::from_fr(&gen_test_ephemeral_pk_blinder()),
+            exp_date_secs: 1900255944,
+            sub: Some(String::from("alice")),
+            email: None,
+            aud: Some(String::from("wallet")),
+        }
+    }
+
+    fn decode(jwt: &str) -> (JwtHeader, JwtPayload) {
+        let parts = JwtParts::from_b64(jwt).unwrap();
+        (
+            serde_json::from_str(&parts.header_decoded().unwrap()).unwrap(),
+            serde_json::from_str(&parts.payload_decoded().unwrap()).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_mint_and_rotate() {
+        let provider = MockOidcProvider::new(
+            "mock.oidc",
+            "http://localhost:4445/",
+            get_test_circuit_config(),
+        )
+        .unwrap();
+        assert_eq!(
+            provider.openid_configuration()["jwks_uri"],
+            "http://localhost:4445/jwks"
+        );
+
+        let response = provider.mint(&test_mint_request()).unwrap();
+        let (header, payload) = decode(&response.jwt);
+        assert_eq!(payload.nonce, response.nonce);
+        assert_eq!(payload.iss, "mock.oidc");
+        let jwks = parse_jwks(&provider.jwks()).unwrap();
+        let jwk = jwks.get(&header.kid).unwrap().clone();
+        assert!(jwk
+            .verify_signature_without_exp_check(&response.jwt)
+            .is_ok());
+
+        let kid = provider.rotate().unwrap();
+        let response = provider.mint(&test_mint_request()).unwrap();
+        assert_eq!(decode(&response.jwt).0.kid, kid);
+        // The previous key is still published.
+        let jwks = parse_jwks(&provider.jwks()).unwrap();
+        assert!(jwks.contains_key(&header.kid) && jwks.contains_key(&kid));
+    }
+
+    #[test]
+    fn test_misbehaviour() {
+        let provider = MockOidcProvider::new(
+            "mock.oidc",
+            "http://localhost:4445",
+            get_test_circuit_config(),
+        )
+        .unwrap();
+        provider.set_misbehaviour(Misbehaviour {
+            wrong_kid: true,
+            future_iat: true,
+            array_aud: true,
+        });
+
+        let response = provider.mint(&test_mint_request()).unwrap();
+        let (header, payload) = decode(&response.jwt);
+        assert!(!parse_jwks(&provider.jwks())
+            .unwrap()
+            .contains_key(&header.kid));
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        assert!(payload.iat > now);
+        assert!(matches!(payload.aud, Some(JwtAud::Multiple(_))));
+    }
+}
@@ -0,0 +1,256 @@
+// Copyright © Aptos Foundation
+
+//! Encodings of a proof besides the Aptos `Groth16Proof`, for clients that verify it elsewhere.
+//! They are all derived from the snarkjs JSON of the one proof the response carries.
+
+use crate::groth16_vk::{check_point, try_as_g1, try_as_g2};
+use anyhow::{anyhow, bail, Context, Result};
+use ark_bn254::{Bn254, Fq2, Fr, G1Affine, G2Affine};
+use ark_ff::{BigInteger, PrimeField};
+use ark_groth16::Proof;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// A proof encoding a client can ask for in `RequestInput::proof_encodings`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProofEncoding {
+    /// The proof as snarkjs and rapidsnark output it.
+    SnarkjsJson,
+    /// arkworks' `Proof<Bn254>`, serialized compressed, in hex.
+    ArkworksCompressed,
+    /// The arguments of `verifyProof` in a verifier exported by `snarkjs zkey export
+    /// solidityverifier`.
+    SolidityCalldata,
+}
+
+/// The encodings a client asked for. Those it didn't ask for are left out of the response.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProofEncodings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snarkjs_json: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arkworks_compressed: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub solidity_calldata: Option<SolidityCalldata>,
+}
+
+impl ProofEncodings {
+    /// Encodes the proof in `proof_json`, the snarkjs JSON output by the prover, for
+    /// `public_inputs`.
+    pub fn new(
+        encodings: &[ProofEncoding],
+        proof_json: &str,
+        public_inputs: &[Fr],
+    ) -> Result<Self> {
+        let mut result = Self::default();
+        if encodings.is_empty() {
+            return Ok(result);
+        }
+
+        let snarkjs_json: Value = serde_json::from_str(proof_json)?;
+        let proof = proof_from_snarkjs_json(&snarkjs_json)?;
+        for encoding in encodings {
+            match encoding {
+                ProofEncoding::SnarkjsJson => result.snarkjs_json = Some(snarkjs_json.clone()),
+                ProofEncoding::ArkworksCompressed => {
+                    result.arkworks_compressed = Some(to_arkworks_compressed(&proof)?)
+                }
+                ProofEncoding::SolidityCalldata => {
+                    result.solidity_calldata = Some(SolidityCalldata::new(&proof, public_inputs))
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+/// Decodes a proof in the snarkjs JSON format, with points in decimal projective coordinates.
+pub fn proof_from_snarkjs_json(json: &Value) -> Result<Proof<Bn254>> {
+    let point = |name: &str| {
+        json.get(name)
+            .cloned()
+            .ok_or_else(|| anyhow!("The proof has no {}", name))
+    };
+    Ok(Proof {
+        a: try_as_g1(&serde_json::from_value(point("pi_a")?)?).context("pi_a decoding error")?,
+        b: try_as_g2(&serde_json::from_value(point("pi_b")?)?).context("pi_b decoding error")?,
+        c: try_as_g1(&serde_json::from_value(point("pi_c")?)?).context("pi_c decoding error")?,
+    })
+}
+
+pub fn to_arkworks_compressed(proof: &Proof<Bn254>) -> Result<String> {
+    let mut buf = vec![];
+    proof.serialize_compressed(&mut buf)?;
+    Ok(hex::encode(buf))
+}
+
+pub fn from_arkworks_compressed(hex: &str) -> Result<Proof<Bn254>> {
+    Ok(Proof::deserialize_compressed(
+        hex::decode(hex.trim_start_matches("0x"))?.as_slice(),
+    )?)
+}
+
+/// The arguments of a snarkjs Solidity verifier's `verifyProof(a, b, c, input)`, as 0x-prefixed
+/// 32-byte big-endian hex, in the order `snarkjs zkey export soliditycalldata` prints them. The
+/// EVM's pairing precompile takes the coordinates of G2 points imaginary part first, so those of
+/// `b` are swapped.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SolidityCalldata {
+    pub a: [String; 2],
+    pub b: [[String; 2]; 2],
+    pub c: [String; 2],
+    pub public_inputs: Vec<String>,
+}
+
+impl SolidityCalldata {
+    pub fn new(proof: &Proof<Bn254>, public_inputs: &[Fr]) -> Self {
+        let (a, b, c) = (proof.a, proof.b, proof.c);
+        Self {
+            a: [uint256(&a.x), uint256(&a.y)],
+            b: [
+                [uint256(&b.x.c1), uint256(&b.x.c0)],
+                [uint256(&b.y.c1), uint256(&b.y.c0)],
+            ],
+            c: [uint256(&c.x), uint256(&c.y)],
+            public_inputs: public_inputs.iter().map(uint256).collect(),
+        }
+    }
+
+    /// Decodes the proof and the public inputs, checking that the points are valid.
+    pub fn decode(&self) -> Result<(Proof<Bn254>, Vec<Fr>)> {
+        let g1 = |[x, y]: &[String; 2]| -> Result<G1Affine> {
+            check_point(G1Affine::new_unchecked(from_uint256(x)?, from_uint256(y)?))
+        };
+        let fq2 = |[c1, c0]: &[String; 2]| -> Result<Fq2> {
+            Ok(Fq2::new(from_uint256(c0)?, from_uint256(c1)?))
+        };
+        let proof = Proof {
+            a: g1(&self.a).context("a decoding error")?,
+            b: check_point(G2Affine::new_unchecked(fq2(&self.b[0])?, fq2(&self.b[1])?))
+                .context("b decoding error")?,
+            c: g1(&self.c).context("c decoding error")?,
+        };
+        let public_inputs = self
+            .public_inputs
+            .iter()
+            .map(|input| from_uint256(input))
+            .collect::<Result<_>>()?;
+        Ok((proof, public_inputs))
+    }
+}
+
+fn uint256<F: PrimeField>(element: &F) -> String {
+    format!("0x{}", hex::encode(element.into_bigint().to_bytes_be()))
+}
+
+fn from_uint256<F: PrimeField>(repr: &str) -> Result<F> {
+    let bytes = hex::decode(repr.trim_start_matches("0x"))?;
+    if bytes.len() != 32 {
+        bail!("{} is not 32 bytes long", repr);
+    }
+    let element = F::from_be_bytes_mod_order(&bytes);
+    if element.into_bigint().to_bytes_be() != bytes {
+        bail!("{} is not a field element", repr);
+    }
+    Ok(element)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        from_arkworks_compressed, proof_from_snarkjs_json, ProofEncoding, ProofEncodings,
+        SolidityCalldata,
+    };
+    use crate::{
+        groth16_vk::Groth16VerificationKey,
+        prover_backend::{ArkworksBackend, BackendProof, ProverBackend},
+        zkey::read_witness,
+    };
+    use ark_bn254::{Bn254, Fr};
+    use ark_groth16::Groth16;
+    use std::{fs, io::Cursor};
+
+    fn toy_proof() -> (BackendProof, Vec<Fr>) {
+        let backend = ArkworksBackend::new("resources/toy_circuit/toy_1.zkey").unwrap();
+        let wtns = fs::read("resources/toy_circuit/toy.wtns").unwrap();
+        let public_inputs = read_witness(Cursor::new(&wtns)).unwrap()[1..2].to_vec();
+        (backend.prove(&wtns).unwrap(), public_inputs)
+    }
+
+    #[test]
+    fn test_proof_encodings_roundtrip() {
+        let (backend_proof, public_inputs) = toy_proof();
+        let vk = Groth16VerificationKey::from_file("resources/toy_circuit/toy_vk.json")
+            .unwrap()
+            .prepared_vk();
+        let encodings = ProofEncodings::new(
+            &[
+                ProofEncoding::SnarkjsJson,
+                ProofEncoding::ArkworksCompressed,
+                ProofEncoding::SolidityCalldata,
+            ],
+            &backend_proof.proof_json,
+            &public_inputs,
+        )
+        .unwrap();
+
+        let proof = proof_from_snarkjs_json(&encodings.snarkjs_json.unwrap()).unwrap();
+        assert!(Groth16::<Bn254>::verify_proof(&vk, &proof, &public_inputs).unwrap());
+        assert_eq!(
+            from_arkworks_compressed(&encodings.arkworks_compressed.unwrap()).unwrap(),
+            proof
+        );
+        let calldata = encodings.solidity_calldata.unwrap();
+        assert_eq!(SolidityCalldata::new(&proof, &public_inputs), calldata);
+        assert_eq!(calldata.decode().unwrap(), (proof, public_inputs));
+        assert!(calldata
+            .a
+            .iter()
+            .all(|x| x.len() == 66 && x.starts_with("0x")));
+    }
+
+    #[test]
+    fn test_no_proof_encodings() {
+        let (backend_proof, public_inputs) = toy_proof();
+
+        let encodings =
+            ProofEncodings::new(&[], &backend_proof.proof_json, &public_inputs).unwrap();
+        assert!(encodings.is_empty());
+        assert_eq!(serde_json::to_string(&encodings).unwrap(), "{}");
+
+        let encodings = ProofEncodings::new(
+            &[ProofEncoding::SolidityCalldata],
+            &backend_proof.proof_json,
+            &public_inputs,
+        )
+        .unwrap();
+        assert!(encodings.snarkjs_json.is_none() && encodings.arkworks_compressed.is_none());
+        assert!(ProofEncodings::new(&[ProofEncoding::SnarkjsJson], "{}", &public_inputs).is_err());
+    }
+
+    #[test]
+    fn test_invalid_solidity_calldata() {
+        let (backend_proof, public_inputs) = toy_proof();
+        let encodings = ProofEncodings::new(
+            &[ProofEncoding::SolidityCalldata],
+            &backend_proof.proof_json,
+            &public_inputs,
+        )
+        .unwrap();
+        let calldata = encodings.solidity_calldata.unwrap();
+
+        let mut not_on_curve = calldata.clone();
+        not_on_curve.a[1] = not_on_curve.a[0].clone();
+        assert!(not_on_curve.decode().is_err());
+
+        let mut too_short = calldata;
+        too_short.public_inputs[0] = String::from("0x01");
+        assert!(too_short.decode().is_err());
+    }
+}
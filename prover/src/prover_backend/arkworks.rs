@@ -0,0 +1,196 @@
+// Copyright © Aptos Foundation
+
+//! A pure-Rust Groth16 prover for snarkjs zkeys, built on `ark-groth16`. It holds the whole
+//! proving key in memory, so it suits small circuits, and cross-checking the rapidsnark backend.
+
+use super::{encode_proof, failed, BackendProof, ProverBackend, ProverBackendError};
+use crate::zkey::{read_witness, Zkey};
+use anyhow::{bail, Result};
+use ark_bn254::{Bn254, Fq, Fr};
+use ark_ff::{PrimeField, UniformRand};
+use ark_groth16::{
+    r1cs_to_qap::{evaluate_constraint, LibsnarkReduction, R1CSToQAP},
+    Groth16, Proof, ProvingKey,
+};
+use ark_poly::EvaluationDomain;
+use ark_relations::r1cs::{ConstraintMatrices, ConstraintSystemRef, SynthesisError};
+use ark_std::rand::rngs::OsRng;
+use num_bigint::BigUint;
+use serde_json::{json, Value};
+use std::{io::Cursor, time::Instant};
+
+pub struct ArkworksBackend {
+    proving_key: ProvingKey<Bn254>,
+    matrices: ConstraintMatrices<Fr>,
+}
+
+impl ArkworksBackend {
+    pub fn new(zkey_path: &str) -> Result<Self> {
+        let zkey = Zkey::read(zkey_path)?;
+        Ok(Self {
+            proving_key: zkey.proving_key,
+            matrices: zkey.matrices,
+        })
+    }
+
+    fn prove_witness(&self, witness: &[Fr]) -> Result<Proof<Bn254>> {
+        let num_inputs = self.matrices.num_instance_variables;
+        let expected_len = num_inputs + self.matrices.num_witness_variables;
+        if witness.len() != expected_len {
+            bail!(
+                "The witness has {} elements, but the zkey expects {}",
+                witness.len(),
+                expected_len
+            );
+        }
+
+        let r = Fr::rand(&mut OsRng);
+        let s = Fr::rand(&mut OsRng);
+        Ok(
+            Groth16::<Bn254, CircomReduction>::create_proof_with_reduction_and_matrices(
+                &self.proving_key,
+                r,
+                s,
+                &self.matrices,
+                num_inputs,
+                self.matrices.num_constraints,
+                witness,
+            )?,
+        )
+    }
+}
+
+impl ProverBackend for ArkworksBackend {
+    fn prove(&self, wtns: &[u8]) -> Result<BackendProof, ProverBackendError> {
+        let start_time = Instant::now();
+        let witness = read_witness(Cursor::new(wtns)).map_err(failed)?;
+        let proof = self.prove_witness(&witness).map_err(failed)?;
+        let prover_time = start_time.elapsed();
+
+        let proof_json = snarkjs_proof_json(&proof);
+        Ok(BackendProof {
+            proof: encode_proof(&serde_json::from_value(proof_json.clone()).map_err(failed)?)
+                .map_err(failed)?,
+            proof_json: proof_json.to_string(),
+            prover_time,
+        })
+    }
+}
+
+/// The proof as snarkjs and rapidsnark output it, with coordinates in decimal.
+pub(crate) fn snarkjs_proof_json(proof: &Proof<Bn254>) -> Value {
+    json!({
+        "pi_a": [decimal(proof.a.x), decimal(proof.a.y), "1"],
+        "pi_b": [
+            [decimal(proof.b.x.c0), decimal(proof.b.x.c1)],
+            [decimal(proof.b.y.c0), decimal(proof.b.y.c1)],
+            ["1", "0"],
+        ],
+        "pi_c": [decimal(proof.c.x), decimal(proof.c.y), "1"],
+        "protocol": "groth16",
+        "curve": "bn128",
+    })
+}
+
+fn decimal(fq: Fq) -> String {
+    BigUint::from(fq.into_bigint()).to_string()
+}
+
+/// The R1CS-to-QAP reduction of snarkjs, whose H query is evaluated over the odd powers of a root
+/// of unity of twice the domain's size, rather than libsnark's over the domain's coset.
+struct CircomReduction;
+
+impl R1CSToQAP for CircomReduction {
+    #[allow(clippy::type_complexity)]
+    fn instance_map_with_evaluation<F: PrimeField, D: EvaluationDomain<F>>(
+        cs: ConstraintSystemRef<F>,
+        t: &F,
+    ) -> Result<(Vec<F>, Vec<F>, Vec<F>, F, usize, usize), SynthesisError> {
+        LibsnarkReduction::instance_map_with_evaluation::<F, D>(cs, t)
+    }
+
+    fn witness_map_from_matrices<F: PrimeField, D: EvaluationDomain<F>>(
+        matrices: &ConstraintMatrices<F>,
+        num_inputs: usize,
+        num_constraints: usize,
+        full_assignment: &[F],
+    ) -> Result<Vec<F>, SynthesisError> {
+        let domain =
+            D::new(num_constraints + num_inputs).ok_or(SynthesisError::PolynomialDegreeTooLarge)?;
+        let domain_size = domain.size();
+        let zero = F::zero();
+
+        let mut a = vec![zero; domain_size];
+        let mut b = vec![zero; domain_size];
+        for (i, (a_i, b_i)) in matrices.a.iter().zip(&matrices.b).enumerate() {
+            a[i] = evaluate_constraint(a_i, full_assignment);
+            b[i] = evaluate_constraint(b_i, full_assignment);
+        }
+        // The constraints snarkjs appends for the public inputs.
+        a[num_constraints..num_constraints + num_inputs]
+            .clone_from_slice(&full_assignment[..num_inputs]);
+
+        let mut c = vec![zero; domain_size];
+        for ((c_i, a_i), b_i) in c.iter_mut().zip(&a).zip(&b).take(num_constraints) {
+            *c_i = *a_i * b_i;
+        }
+
+        let root_of_unity = D::new(2 * domain_size)
+            .ok_or(SynthesisError::PolynomialDegreeTooLarge)?
+            .element(1);
+        for evaluations in [&mut a, &mut b, &mut c] {
+            domain.ifft_in_place(evaluations);
+            D::distribute_powers_and_mul_by_const(evaluations, root_of_unity, F::one());
+            domain.fft_in_place(evaluations);
+        }
+
+        Ok(a.iter()
+            .zip(&b)
+            .zip(&c)
+            .map(|((a_i, b_i), c_i)| *a_i * b_i - c_i)
+            .collect())
+    }
+
+    fn h_query_scalars<F: PrimeField, D: EvaluationDomain<F>>(
+        max_power: usize,
+        t: F,
+        _zt: F,
+        delta_inverse: F,
+    ) -> Result<Vec<F>, SynthesisError> {
+        let mut scalars = (0..2 * max_power + 1)
+            .map(|i| delta_inverse * t.pow([i as u64]))
+            .collect::<Vec<_>>();
+        let domain = D::new(scalars.len()).ok_or(SynthesisError::PolynomialDegreeTooLarge)?;
+        domain.ifft_in_place(&mut scalars);
+        Ok(scalars.into_iter().skip(1).step_by(2).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ArkworksBackend;
+    use crate::{groth16_vk::Groth16VerificationKey, zkey::read_witness};
+    use ark_bn254::{Bn254, Fr};
+    use ark_groth16::Groth16;
+    use std::{fs, io::Cursor};
+
+    #[test]
+    fn test_prove_toy_circuit() {
+        let backend = ArkworksBackend::new("resources/toy_circuit/toy_1.zkey").unwrap();
+        let wtns = fs::read("resources/toy_circuit/toy.wtns").unwrap();
+        let witness = read_witness(Cursor::new(&wtns)).unwrap();
+        let vk = Groth16VerificationKey::from_file("resources/toy_circuit/toy_vk.json")
+            .unwrap()
+            .prepared_vk();
+
+        let proof = backend.prove_witness(&witness).unwrap();
+        assert!(Groth16::<Bn254>::verify_proof(&vk, &proof, &witness[1..2]).unwrap());
+
+        let mut wrong_witness = witness.clone();
+        wrong_witness[1] += Fr::from(1u64);
+        let proof = backend.prove_witness(&wrong_witness).unwrap();
+        assert!(!Groth16::<Bn254>::verify_proof(&vk, &proof, &wrong_witness[1..2]).unwrap());
+
+        assert!(backend.prove_witness(&witness[..2]).is_err());
+    }
+}
@@ -0,0 +1,177 @@
+// Copyright © Aptos Foundation
+
+//! The Groth16 provers a setup can prove witnesses with: rapidsnark, and a pure-Rust one built on
+//! arkworks.
+
+use crate::{
+    config::{ProverBackendKind, ProverServiceConfig},
+    groth16_vk::Groth16VerificationKey,
+    witness_gen::{write_witness_file, PathStr},
+    zkey::read_witness,
+};
+use anyhow::{anyhow, Result};
+use aptos_types::keyless::{G1Bytes, G2Bytes, Groth16Proof};
+use ark_bn254::Bn254;
+use ark_groth16::PreparedVerifyingKey;
+use rust_rapidsnark::{FullProver, ProverError};
+use serde::Deserialize;
+use std::{io::Cursor, time::Duration};
+use tracing::warn;
+
+pub mod arkworks;
+
+pub use self::arkworks::ArkworksBackend;
+
+/// A proof in the snarkjs JSON format that both backends output.
+#[derive(Deserialize)]
+pub struct RapidsnarkProofResponse {
+    pi_a: [String; 3],
+    pi_b: [[String; 2]; 3],
+    pi_c: [String; 3],
+}
+
+impl RapidsnarkProofResponse {
+    fn pi_b_str(&self) -> [[&str; 2]; 3] {
+        [
+            [&self.pi_b[0][0], &self.pi_b[0][1]],
+            [&self.pi_b[1][0], &self.pi_b[1][1]],
+            [&self.pi_b[2][0], &self.pi_b[2][1]],
+        ]
+    }
+}
+
+pub fn encode_proof(proof: &RapidsnarkProofResponse) -> Result<Groth16Proof> {
+    let new_pi_a = G1Bytes::new_unchecked(&proof.pi_a[0], &proof.pi_a[1])?;
+    let new_pi_b = G2Bytes::new_unchecked(proof.pi_b_str()[0], proof.pi_b_str()[1])?;
+    let new_pi_c = G1Bytes::new_unchecked(&proof.pi_c[0], &proof.pi_c[1])?;
+
+    Ok(Groth16Proof::new(new_pi_a, new_pi_b, new_pi_c))
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ProverBackendError {
+    #[error(transparent)]
+    Rapidsnark(#[from] ProverError),
+    #[error("Proving failed: {0:#}")]
+    Failed(anyhow::Error),
+}
+
+fn failed(error: impl Into<anyhow::Error>) -> ProverBackendError {
+    ProverBackendError::Failed(error.into())
+}
+
+pub struct BackendProof {
+    pub proof: Groth16Proof,
+    /// The proof as snarkjs outputs it, for logging.
+    pub proof_json: String,
+    pub prover_time: Duration,
+}
+
+/// Setups prove one witness at a time, behind a mutex, so backends need not be `Sync`.
+pub trait ProverBackend: Send {
+    /// Proves a witness in the `.wtns` format. The proof is not verified.
+    fn prove(&self, wtns: &[u8]) -> Result<BackendProof, ProverBackendError>;
+}
+
+/// Creates the backend selected by `config.prover_backend` for a setup, cross-checked by the
+/// arkworks one if `config.cross_check_prover_backend` is set.
+pub fn new_prover_backend(
+    config: &ProverServiceConfig,
+    use_new_setup: bool,
+) -> Result<Box<dyn ProverBackend>> {
+    let zkey_path = config.zkey_path(use_new_setup);
+    let backend: Box<dyn ProverBackend> = match config.prover_backend {
+        ProverBackendKind::Rapidsnark => Box::new(RapidsnarkBackend::new(&zkey_path)?),
+        ProverBackendKind::Arkworks => Box::new(ArkworksBackend::new(&zkey_path)?),
+    };
+    if !config.cross_check_prover_backend {
+        return Ok(backend);
+    }
+
+    let prepared_vk =
+        Groth16VerificationKey::from_file(config.verification_key_path(use_new_setup))?
+            .prepared_vk();
+    Ok(Box::new(CrossCheckedBackend {
+        backend,
+        reference: ArkworksBackend::new(&zkey_path)?,
+        prepared_vk,
+    }))
+}
+
+pub struct RapidsnarkBackend {
+    full_prover: FullProver,
+}
+
+impl RapidsnarkBackend {
+    pub fn new(zkey_path: &str) -> Result<Self> {
+        let full_prover = FullProver::new(zkey_path).map_err(|e| {
+            anyhow!(
+                "failed to initialize rapidsnark prover with {}: {:?}",
+                zkey_path,
+                e
+            )
+        })?;
+        Ok(Self { full_prover })
+    }
+}
+
+impl ProverBackend for RapidsnarkBackend {
+    fn prove(&self, wtns: &[u8]) -> Result<BackendProof, ProverBackendError> {
+        // rust-rapidsnark only reads witnesses from a file.
+        let witness_file = write_witness_file(wtns).map_err(failed)?;
+        let (proof_json, internal_metrics) = self
+            .full_prover
+            .prove(witness_file.path_str().map_err(failed)?)?;
+        let proof =
+            encode_proof(&serde_json::from_str(proof_json).map_err(failed)?).map_err(failed)?;
+
+        Ok(BackendProof {
+            proof,
+            proof_json: String::from(proof_json),
+            prover_time: Duration::from_secs_f64(f64::from(internal_metrics.prover_time) / 1000.0),
+        })
+    }
+}
+
+/// Proves with `backend`, and again with the arkworks `reference` to check it. When only one of
+/// the proofs verifies, the other backend is at fault rather than the witness.
+struct CrossCheckedBackend {
+    backend: Box<dyn ProverBackend>,
+    reference: ArkworksBackend,
+    prepared_vk: PreparedVerifyingKey<Bn254>,
+}
+
+impl ProverBackend for CrossCheckedBackend {
+    fn prove(&self, wtns: &[u8]) -> Result<BackendProof, ProverBackendError> {
+        let proof = self.backend.prove(wtns)?;
+
+        // The keyless circuit's only public input is the public inputs hash.
+        let witness = read_witness(Cursor::new(wtns)).map_err(failed)?;
+        let Some(public_inputs_hash) = witness.get(1) else {
+            return Ok(proof);
+        };
+        let verifies = |proof: &Groth16Proof| {
+            proof
+                .verify_proof(*public_inputs_hash, &self.prepared_vk)
+                .is_ok()
+        };
+
+        match self.reference.prove(wtns) {
+            Ok(reference_proof) => {
+                let backend_verifies = verifies(&proof.proof);
+                let reference_verifies = verifies(&reference_proof.proof);
+                if backend_verifies != reference_verifies {
+                    warn!(
+                        backend_verifies,
+                        reference_verifies,
+                        proof = %proof.proof_json,
+                        reference_proof = %reference_proof.proof_json,
+                        "The prover backends disagree"
+                    );
+                }
+            }
+            Err(e) => warn!("The reference prover backend failed: {:#}", e),
+        }
+        Ok(proof)
+    }
+}
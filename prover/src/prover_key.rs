@@ -8,8 +8,6 @@ use aptos_crypto::ed25519::{Ed25519PrivateKey, Ed25519PublicKey};
 use aptos_crypto::ValidCryptoMaterialStringExt;
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
-#[cfg(test)]
-use std::io::Write;
 use std::sync::{Arc, RwLock};
 
 pub async fn cached_prover_key(config: &ProverServiceConfig) -> String {
@@ -95,23 +93,3 @@ impl TrainingWheelsKeyPair {
 
 pub static ON_CHAIN_TW_PK: Lazy<Arc<RwLock<Option<OnChainKeylessConfiguration>>>> =
     Lazy::new(|| Arc::new(RwLock::new(None)));
-
-/// This is not a UT, but a tool to convert a .vkey to its on-chain representation and save in a file.
-#[test]
-fn tw_vk_rewriter() {
-    if let (Ok(path_in), Ok(path_out)) = (
-        std::env::var("LOCAL_TW_VK_IN"),
-        std::env::var("ONCHAIN_KEYLESS_CONFIG_OUT"),
-    ) {
-        let local_tw_sk_encoded = std::fs::read_to_string(path_in.as_str()).unwrap();
-        let local_tw_sk =
-            Ed25519PrivateKey::from_encoded_string(local_tw_sk_encoded.as_str()).unwrap();
-        let local_tw_pk = Ed25519PublicKey::from(&local_tw_sk);
-        let onchain_keyless_config = OnChainKeylessConfiguration::from_tw_pk(Some(local_tw_pk));
-        let json_out = serde_json::to_string_pretty(&onchain_keyless_config).unwrap();
-        std::fs::File::create(path_out)
-            .unwrap()
-            .write_all(json_out.as_bytes())
-            .unwrap();
-    }
-}
@@ -0,0 +1,197 @@
+// Copyright © Aptos Foundation
+
+use crate::{
+    config::ProverServiceConfig,
+    debug_capture::{DebugCapture, Stage},
+    input_processing::{derive_circuit_input_signals, preprocess},
+    prover_backend::BackendProof,
+    state::ProverSetup,
+    training_wheels,
+    witness_gen::generate_witness,
+};
+use anyhow::{anyhow, Result};
+use aptos_keyless_common::{input_processing::encoding::AsFr, PoseidonHash};
+use serde_json::Value;
+
+/// How a stage went when replayed.
+pub enum StageOutcome {
+    Passed,
+    /// The stage passed, but didn't compute what it computed when the request was captured.
+    Diverged(String),
+    Failed(anyhow::Error),
+}
+
+pub struct StageReport {
+    pub stage: Stage,
+    pub outcome: StageOutcome,
+}
+
+/// Reruns a captured request against the local setup it was captured with, stage by stage,
+/// stopping at the first stage that fails. The JWT's signature and dates aren't checked again,
+/// since it has most likely expired by the time it is replayed.
+pub async fn replay(capture: &DebugCapture, config: &ProverServiceConfig) -> Vec<StageReport> {
+    let mut reports = vec![];
+    let setup = ProverSetup::load(config, capture.use_new_setup);
+
+    let (circuit_input_json, public_inputs_hash) =
+        match replay_input_processing(capture, config, &setup) {
+            Ok((circuit_input_json, public_inputs_hash)) => {
+                let divergence = capture
+                    .circuit_input_signals
+                    .as_ref()
+                    .and_then(|captured| first_divergence(captured, &circuit_input_json));
+                reports.push(StageReport {
+                    stage: Stage::InputProcessing,
+                    outcome: divergence.map_or(StageOutcome::Passed, StageOutcome::Diverged),
+                });
+                (circuit_input_json, public_inputs_hash)
+            }
+            Err(e) => {
+                reports.push(StageReport {
+                    stage: Stage::InputProcessing,
+                    outcome: StageOutcome::Failed(e),
+                });
+                return reports;
+            }
+        };
+
+    let wtns = match generate_witness(setup.witness_generator.as_ref(), circuit_input_json).await {
+        Ok(wtns) => {
+            reports.push(StageReport {
+                stage: Stage::WitnessGeneration,
+                outcome: StageOutcome::Passed,
+            });
+            wtns
+        }
+        Err(e) => {
+            reports.push(StageReport {
+                stage: Stage::WitnessGeneration,
+                outcome: StageOutcome::Failed(e.into()),
+            });
+            return reports;
+        }
+    };
+
+    let proving = replay_proving(&setup, &wtns, public_inputs_hash);
+    reports.push(StageReport {
+        stage: Stage::Proving,
+        outcome: match proving.await {
+            Ok(()) => StageOutcome::Passed,
+            Err(e) => StageOutcome::Failed(e),
+        },
+    });
+    reports
+}
+
+fn replay_input_processing(
+    capture: &DebugCapture,
+    config: &ProverServiceConfig,
+    setup: &ProverSetup,
+) -> Result<(Value, PoseidonHash)> {
+    let jwk = capture
+        .jwk
+        .as_ref()
+        .ok_or_else(|| anyhow!("The request failed before its JWK was known"))?;
+    let input = preprocess::decode_and_add_jwk(capture.request.clone(), Some(jwk), config)?;
+    training_wheels::check_nonce_consistency(&input, &setup.circuit_config)?;
+    training_wheels::validate_jwt_payload_parsing(&input).map_err(|e| e.error)?;
+
+    let (circuit_input_signals, public_inputs_hash) =
+        derive_circuit_input_signals(input, &setup.circuit_config)?;
+    Ok((circuit_input_signals.to_json_value(), public_inputs_hash))
+}
+
+async fn replay_proving(
+    setup: &ProverSetup,
+    wtns: &[u8],
+    public_inputs_hash: PoseidonHash,
+) -> Result<()> {
+    let BackendProof { proof, .. } = setup.prover.lock().await.prove(wtns)?;
+    proof.verify_proof(public_inputs_hash.as_fr(), &setup.prepared_vk)
+}
+
+/// Describes the first circuit input signal whose value differs between the captured and the
+/// replayed signals. Signals are compared in alphabetical order of their names, which is the order
+/// `serde_json` keeps object keys in, not the order they were captured in.
+pub fn first_divergence(captured: &Value, replayed: &Value) -> Option<String> {
+    let (Some(captured), Some(replayed)) = (captured.as_object(), replayed.as_object()) else {
+        return (captured != replayed).then(|| String::from("The signals differ"));
+    };
+
+    for (name, captured_value) in captured {
+        let Some(replayed_value) = replayed.get(name) else {
+            return Some(format!("Signal {} is missing from the replay", name));
+        };
+        if captured_value == replayed_value {
+            continue;
+        }
+        return Some(
+            match (captured_value.as_array(), replayed_value.as_array()) {
+                (Some(captured_elements), Some(replayed_elements)) => {
+                    let index = captured_elements
+                        .iter()
+                        .zip(replayed_elements)
+                        .position(|(c, r)| c != r)
+                        .unwrap_or(captured_elements.len().min(replayed_elements.len()));
+                    format!(
+                        "Signal {} differs from index {} on (captured {} elements, replayed {})",
+                        name,
+                        index,
+                        captured_elements.len(),
+                        replayed_elements.len()
+                    )
+                }
+                _ => format!(
+                    "Signal {} differs: captured {}, replayed {}",
+                    name, captured_value, replayed_value
+                ),
+            },
+        );
+    }
+
+    replayed
+        .keys()
+        .find(|name| !captured.contains_key(*name))
+        .map(|name| format!("Signal {} wasn't captured", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::first_divergence;
+    use serde_json::json;
+
+    #[test]
+    fn test_first_divergence() {
+        let captured = json!({ "a": "1", "b": ["1", "2", "3"], "c": "3" });
+
+        assert_eq!(first_divergence(&captured, &captured), None);
+        assert_eq!(
+            first_divergence(
+                &captured,
+                &json!({ "a": "1", "b": ["1", "5", "6"], "c": "4" })
+            )
+            .unwrap(),
+            "Signal b differs from index 1 on (captured 3 elements, replayed 3)"
+        );
+        assert_eq!(
+            first_divergence(
+                &captured,
+                &json!({ "a": "2", "b": ["1", "2", "3"], "c": "3" })
+            )
+            .unwrap(),
+            r#"Signal a differs: captured "1", replayed "2""#
+        );
+        assert_eq!(
+            first_divergence(&captured, &json!({ "a": "1", "b": ["1", "2", "3"] })).unwrap(),
+            "Signal c is missing from the replay"
+        );
+        assert_eq!(
+            first_divergence(
+                &captured,
+                &json!({ "a": "1", "b": ["1", "2", "3"], "c": "3", "d": "4" })
+            )
+            .unwrap(),
+            "Signal d wasn't captured"
+        );
+    }
+}
@@ -1,19 +1,34 @@
-use std::fs;
+use std::{fs, path::Path};
 
 use aptos_crypto::ed25519::Ed25519PrivateKey;
-use aptos_keyless_common::input_processing::config::CircuitPaddingConfig;
+use aptos_keyless_common::input_processing::{
+    config::CircuitPaddingConfig,
+    signal_schema::{SignalSchema, KEYLESS_MAIN_PUBLIC_SIGNALS},
+    witness_calculator::WitnessCalculator,
+};
+use ark_bn254::Bn254;
+use ark_groth16::PreparedVerifyingKey;
 use figment::{
     providers::{Env, Format, Yaml},
     Figment,
 };
-use rust_rapidsnark::FullProver;
 use serde::{Deserialize, Serialize};
 
-use crate::config::{self, ProverServiceConfig};
-use crate::groth16_vk::{OnChainGroth16VerificationKey, SnarkJsGroth16VerificationKey};
+use crate::config::{self, ProverServiceConfig, WitnessGenMode};
+use crate::debug_capture::DebugCapturer;
+use crate::groth16_vk::{Groth16VerificationKey, OnChainGroth16VerificationKey};
+use crate::metrics;
+use crate::prover_backend::{new_prover_backend, ProverBackend};
 use crate::prover_key::TrainingWheelsKeyPair;
-use std::env;
+use crate::witness_gen::{new_witness_generator, WitnessGenerator};
+use crate::zkey::ZkeyHeader;
+use std::{
+    env,
+    sync::RwLock,
+    time::{Duration, Instant},
+};
 use tokio::sync::Mutex;
+use tracing::{error, info, warn};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ProverServiceSecrets {
@@ -23,28 +38,167 @@ pub struct ProverServiceSecrets {
     pub private_key_1: Option<Ed25519PrivateKey>,
 }
 
+/// What is needed to prove with one of the setups.
+pub struct ProverSetup {
+    // Ensures that only one circuit is being proven at a time
+    pub prover: Mutex<Box<dyn ProverBackend>>,
+    pub circuit_config: CircuitPaddingConfig,
+    pub witness_generator: Box<dyn WitnessGenerator>,
+    /// Proofs are checked against this before they are returned.
+    pub prepared_vk: PreparedVerifyingKey<Bn254>,
+    pub invalid_proof_breaker: InvalidProofBreaker,
+}
+
+impl ProverSetup {
+    pub fn load(config: &ProverServiceConfig, use_new_setup: bool) -> Self {
+        let circuit_config = load_circuit_config(config, use_new_setup);
+        check_circuit_config_matches_circuit(config, &circuit_config, use_new_setup);
+
+        let prover = new_prover_backend(config, use_new_setup)
+            .unwrap_or_else(|e| panic!("failed to initialize prover backend: {:?}", e));
+
+        let witness_generator = new_witness_generator(config, use_new_setup)
+            .unwrap_or_else(|e| panic!("failed to initialize witness generator: {:?}", e));
+
+        let prepared_vk =
+            Groth16VerificationKey::from_file(config.verification_key_path(use_new_setup))
+                .map(|vk| vk.prepared_vk())
+                .unwrap_or_else(|e| panic!("failed to load verification key: {:?}", e));
+
+        Self {
+            prover: Mutex::new(prover),
+            circuit_config,
+            witness_generator,
+            prepared_vk,
+            invalid_proof_breaker: InvalidProofBreaker::default(),
+        }
+    }
+}
+
+/// Stops a setup that keeps producing invalid proofs from taking requests. It trips after
+/// `invalid_proof_breaker_threshold` requests in a row without a valid proof. Once tripped, it lets
+/// one request through per `invalid_proof_breaker_cooldown_secs`, and the first valid proof closes
+/// it again.
+#[derive(Default)]
+pub struct InvalidProofBreaker {
+    state: std::sync::Mutex<BreakerState>,
+}
+
+#[derive(Default)]
+struct BreakerState {
+    /// How many requests in a row the setup only produced invalid proofs for.
+    streak: u32,
+    /// When the breaker tripped, or last let a request through since.
+    tripped_at: Option<Instant>,
+}
+
+impl InvalidProofBreaker {
+    /// Whether a request may be proven with the setup. While tripped, this is only the case once
+    /// `cooldown` passed since the breaker tripped or let the last request through.
+    pub fn admit(&self, cooldown: Duration) -> bool {
+        let mut state = self.state.lock().unwrap();
+        match state.tripped_at {
+            None => true,
+            Some(tripped_at) if tripped_at.elapsed() >= cooldown => {
+                state.tripped_at = Some(Instant::now());
+                true
+            }
+            Some(_) => false,
+        }
+    }
+
+    pub fn is_tripped(&self) -> bool {
+        self.state.lock().unwrap().tripped_at.is_some()
+    }
+
+    /// Records whether a request ended up with a valid proof, and returns the streak of requests
+    /// without one if this tripped the breaker. A `threshold` of 0 never trips it.
+    fn record(&self, valid: bool, threshold: u32) -> Option<u32> {
+        let mut state = self.state.lock().unwrap();
+        if valid {
+            *state = BreakerState::default();
+            return None;
+        }
+
+        state.streak += 1;
+        if threshold == 0 || state.streak < threshold {
+            return None;
+        }
+        let newly_tripped = state.tripped_at.is_none();
+        state.tripped_at = Some(Instant::now());
+        newly_tripped.then_some(state.streak)
+    }
+}
+
+/// Whether the service serves requests. It only does once the startup self-test passed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Readiness {
+    SelfTestRunning,
+    Ready,
+    SelfTestFailed(String),
+}
+
 pub struct ProverServiceState {
-    pub full_prover_default: Mutex<FullProver>,
-    pub full_prover_new: Option<Mutex<FullProver>>,
+    pub default_setup: ProverSetup,
+    pub new_setup: Option<ProverSetup>,
     pub new_groth16_vk: Option<OnChainGroth16VerificationKey>,
     pub tw_keypair_default: TrainingWheelsKeyPair,
     pub tw_keypair_new: Option<TrainingWheelsKeyPair>,
+    pub debug_capturer: Option<DebugCapturer>,
     pub config: ProverServiceConfig,
-    pub circuit_config: CircuitPaddingConfig,
-    // Ensures that only one circuit is being proven at a time
+    pub readiness: RwLock<Readiness>,
 }
 
 impl ProverServiceState {
-    pub fn init() -> Self {
-        let config_file_path = env::var(config::CONFIG_FILE_PATH_ENVVAR)
-            .unwrap_or(String::from(config::CONFIG_FILE_PATH));
+    pub fn setup(&self, use_new_setup: bool) -> &ProverSetup {
+        if use_new_setup {
+            self.new_setup
+                .as_ref()
+                .expect("The new setup is only selected if it is configured")
+        } else {
+            &self.default_setup
+        }
+    }
 
+    pub fn readiness(&self) -> Readiness {
+        self.readiness.read().unwrap().clone()
+    }
+
+    pub fn set_readiness(&self, readiness: Readiness) {
+        *self.readiness.write().unwrap() = readiness;
+    }
+
+    /// Records whether a setup ended up with a valid proof for a request, or gave up after
+    /// `max_proof_attempts` invalid ones. After `invalid_proof_breaker_threshold` requests in a row
+    /// without a valid proof, the setup is most likely broken, so its breaker trips rather than
+    /// letting every request spend its attempts on it.
+    pub fn record_proof_outcome(&self, use_new_setup: bool, valid: bool) {
+        let setup_label = if use_new_setup { "new" } else { "default" };
+        let breaker = &self.setup(use_new_setup).invalid_proof_breaker;
+        let was_tripped = breaker.is_tripped();
+
+        if let Some(failed_requests) =
+            breaker.record(valid, self.config.invalid_proof_breaker_threshold)
+        {
+            error!(
+                "The {} setup only produced invalid proofs for the last {} requests, no longer \
+                 serving requests with it for {}s",
+                setup_label, failed_requests, self.config.invalid_proof_breaker_cooldown_secs
+            );
+        } else if was_tripped && valid {
+            info!(
+                "The {} setup produced a valid proof again, serving requests with it",
+                setup_label
+            );
+        }
+        metrics::INVALID_PROOF_BREAKER_TRIPPED
+            .with_label_values(&[setup_label])
+            .set(breaker.is_tripped() as i64);
+    }
+
+    pub fn init() -> Self {
         // read config and secret key
-        let config: ProverServiceConfig = Figment::new()
-            .merge(Yaml::file(config_file_path))
-            .merge(Env::raw())
-            .extract()
-            .expect("Couldn't load config");
+        let config = load_config();
 
         let ProverServiceSecrets {
             private_key_0: private_key,
@@ -57,38 +211,168 @@ impl ProverServiceState {
         let tw_keypair_default = TrainingWheelsKeyPair::from_sk(private_key);
         let tw_keypair_new = private_key_new.map(TrainingWheelsKeyPair::from_sk);
 
-        let circuit_config: CircuitPaddingConfig = serde_yaml::from_str(
-            &fs::read_to_string("conversion_config.yml")
-                .expect("Unable to read circuit config file"),
-        )
-        .expect("Couldn't parse circuit config file");
-
         println!("using resources dir {}", config.resources_dir);
 
         // init state
-        let full_prover_default = FullProver::new(&config.zkey_path(false))
-            .expect("failed to initialize rapidsnark prover with old zkey");
-
-        let (full_prover_new, new_vk) = if config.new_setup_dir.is_some() {
-            let full_prover = FullProver::new(&config.zkey_path(true))
-                .expect("failed to initialize rapidsnark prover with new zkey");
-            let vk_json = fs::read_to_string(config.verification_key_path(true).as_str()).unwrap();
-            let local_vk: SnarkJsGroth16VerificationKey =
-                serde_json::from_str(vk_json.as_str()).unwrap();
-            let onchain_vk = local_vk.try_as_onchain_repr().unwrap();
-            (Some(full_prover), Some(onchain_vk))
+        let default_setup = ProverSetup::load(&config, false);
+
+        let (new_setup, new_vk) = if config.new_setup_dir.is_some() {
+            let new_setup = ProverSetup::load(&config, true);
+            let onchain_vk = Groth16VerificationKey::from_file(config.verification_key_path(true))
+                .and_then(|vk| vk.try_as_onchain_repr())
+                .unwrap_or_else(|e| panic!("failed to load verification key: {:?}", e));
+            (Some(new_setup), Some(onchain_vk))
         } else {
             (None, None)
         };
 
+        let debug_capturer = config.debug_capture.as_ref().map(|debug_capture_config| {
+            DebugCapturer::new(debug_capture_config)
+                .unwrap_or_else(|e| panic!("Couldn't set up debug captures: {:?}", e))
+        });
+
+        let readiness = if config.disable_self_test {
+            Readiness::Ready
+        } else {
+            Readiness::SelfTestRunning
+        };
+
         ProverServiceState {
-            full_prover_default: Mutex::new(full_prover_default),
-            full_prover_new: full_prover_new.map(Mutex::new),
+            default_setup,
+            new_setup,
             new_groth16_vk: new_vk,
             tw_keypair_default,
             tw_keypair_new,
+            debug_capturer,
             config,
-            circuit_config,
+            readiness: RwLock::new(readiness),
         }
     }
 }
+
+/// Loads the config from the file named by `CONFIG_FILE`, overridden by environment variables.
+pub fn load_config() -> ProverServiceConfig {
+    let config_file_path =
+        env::var(config::CONFIG_FILE_PATH_ENVVAR).unwrap_or(String::from(config::CONFIG_FILE_PATH));
+
+    let config: ProverServiceConfig = Figment::new()
+        .merge(Yaml::file(config_file_path))
+        .merge(Env::raw())
+        .extract()
+        .expect("Couldn't load config");
+    config.validate().expect("Invalid config");
+    config
+}
+
+/// Loads the padding config of a setup. In order of preference, it comes from the setup's own
+/// `conversion_config.yml`, from the setup's `main.circom`, or from the global
+/// `conversion_config.yml` for setups that ship neither.
+pub fn load_circuit_config(
+    config: &ProverServiceConfig,
+    use_new_setup: bool,
+) -> CircuitPaddingConfig {
+    let setup_circuit_config_path = config.circuit_config_path(use_new_setup);
+    let main_circom_path = config.main_circom_path(use_new_setup);
+
+    let circuit_config = if Path::new(&setup_circuit_config_path).exists() {
+        read_circuit_config(&setup_circuit_config_path)
+    } else if let Ok(main_circom) = fs::read_to_string(&main_circom_path) {
+        CircuitPaddingConfig::from_main_circom(&main_circom).unwrap_or_else(|e| {
+            panic!(
+                "Couldn't derive circuit config from {}: {}",
+                main_circom_path, e
+            )
+        })
+    } else {
+        warn!(
+            "{} not found, falling back to {}",
+            setup_circuit_config_path,
+            config::CIRCUIT_CONFIG_FILE_PATH
+        );
+        read_circuit_config(config::CIRCUIT_CONFIG_FILE_PATH)
+    };
+
+    check_circuit_config_matches_setup(config, &circuit_config, use_new_setup);
+    circuit_config
+}
+
+pub fn read_circuit_config(path: &str) -> CircuitPaddingConfig {
+    let circuit_config: CircuitPaddingConfig = serde_yaml::from_str(
+        &fs::read_to_string(path)
+            .unwrap_or_else(|e| panic!("Unable to read circuit config file {}: {}", path, e)),
+    )
+    .unwrap_or_else(|e| panic!("Couldn't parse circuit config file {}: {}", path, e));
+
+    circuit_config.with_signal_schema(SignalSchema::keyless_main())
+}
+
+/// Panics unless `circuit_config` fits the setup's circuit: the zkey's public inputs must be those
+/// of the main template, and the padded input signals must take up exactly as many field elements
+/// as the inputs of the setup's witness calculator. When witnesses are generated in a subprocess,
+/// the setup doesn't need a `main.wasm`, and without one the input size isn't checked.
+pub fn check_circuit_config_matches_circuit(
+    config: &ProverServiceConfig,
+    circuit_config: &CircuitPaddingConfig,
+    use_new_setup: bool,
+) {
+    let zkey_path = config.zkey_path(use_new_setup);
+    let header = ZkeyHeader::read(&zkey_path).unwrap_or_else(|e| panic!("{:?}", e));
+    if header.n_public as usize != KEYLESS_MAIN_PUBLIC_SIGNALS.len() {
+        panic!(
+            "{} has {} public inputs, but the circuit has {}",
+            zkey_path,
+            header.n_public,
+            KEYLESS_MAIN_PUBLIC_SIGNALS.len()
+        );
+    }
+
+    let wasm_path = config.witness_gen_wasm_path(use_new_setup);
+    if config.witness_gen_mode == WitnessGenMode::Subprocess && !Path::new(&wasm_path).exists() {
+        warn!(
+            "{} not found, can't check the circuit config against the input size of the circuit",
+            wasm_path
+        );
+        return;
+    }
+    let circuit_input_size = WitnessCalculator::from_file(&wasm_path)
+        .and_then(|witness_calculator| witness_calculator.input_size())
+        .unwrap_or_else(|e| panic!("{:?}", e));
+    let input_signal_count = circuit_config
+        .input_signal_count()
+        .expect("Circuit config should have a signal schema");
+    if input_signal_count != circuit_input_size {
+        panic!(
+            "The circuit config pads the input signals to {} field elements, but {} expects {}",
+            input_signal_count, wasm_path, circuit_input_size
+        );
+    }
+}
+
+/// Panics if the template arguments of the `main.circom` the setup was compiled from disagree with
+/// `circuit_config`, since that would produce witnesses the circuit rejects. Setups that don't
+/// ship their `main.circom` are only accepted with `allow_missing_main_circom`.
+pub fn check_circuit_config_matches_setup(
+    config: &ProverServiceConfig,
+    circuit_config: &CircuitPaddingConfig,
+    use_new_setup: bool,
+) {
+    let main_circom_path = config.main_circom_path(use_new_setup);
+    let Ok(main_circom) = fs::read_to_string(&main_circom_path) else {
+        if !config.allow_missing_main_circom {
+            panic!(
+                "{} not found, so the circuit config can't be checked against the setup. Set \
+                 allow_missing_main_circom to start anyway",
+                main_circom_path
+            );
+        }
+        warn!(
+            "{} not found, can't check the circuit config against the setup",
+            main_circom_path
+        );
+        return;
+    };
+
+    CircuitPaddingConfig::from_main_circom(&main_circom)
+        .and_then(|setup_circuit_config| circuit_config.check_matches(&setup_circuit_config))
+        .unwrap_or_else(|e| panic!("Circuit config doesn't match {}: {}", main_circom_path, e));
+}
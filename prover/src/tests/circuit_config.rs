@@ -0,0 +1,113 @@
+// Copyright © Aptos Foundation
+
+use crate::config::{ProverServiceConfig, WitnessGenMode};
+use crate::state::{check_circuit_config_matches_circuit, check_circuit_config_matches_setup};
+use crate::tests::common::{get_config, get_test_circuit_config};
+use aptos_keyless_common::input_processing::config::CircuitPaddingConfig;
+use std::{fs, path::Path};
+use tempfile::TempDir;
+
+#[test]
+fn test_conversion_config_matches_main_circom() {
+    let main_circom = fs::read_to_string("../circuit/templates/main.circom").unwrap();
+    let derived = CircuitPaddingConfig::from_main_circom(&main_circom).unwrap();
+
+    get_test_circuit_config().check_matches(&derived).unwrap();
+}
+
+#[test]
+fn test_shipped_main_circom_is_the_circuit_main_circom() {
+    assert_eq!(
+        fs::read_to_string("main.circom").unwrap(),
+        fs::read_to_string("../circuit/templates/main.circom").unwrap()
+    );
+}
+
+#[test]
+fn test_conversion_config_matches_setup_circuit() {
+    check_circuit_config_matches_circuit(&get_config(), &get_test_circuit_config(), false);
+}
+
+#[test]
+#[should_panic(expected = "The circuit config pads the input signals to")]
+fn test_mismatched_circuit_config_is_rejected() {
+    let iat_value_length = get_test_circuit_config()
+        .padded_length("iat_value")
+        .unwrap();
+    let circuit_config = get_test_circuit_config().max_length("iat_value", iat_value_length + 1);
+
+    check_circuit_config_matches_circuit(&get_config(), &circuit_config, false);
+}
+
+/// A config in `witness_gen_mode` whose default setup is in a temporary directory, with only the
+/// zkey of the test setup.
+fn config_with_zkey_only(witness_gen_mode: WitnessGenMode) -> (ProverServiceConfig, TempDir) {
+    let (config, resources_dir) = config_with_setup(None, false);
+    let zkey_path = config.zkey_path(false);
+    fs::create_dir_all(Path::new(&zkey_path).parent().unwrap()).unwrap();
+    std::os::unix::fs::symlink(get_config().zkey_path(false), zkey_path).unwrap();
+    (
+        ProverServiceConfig {
+            witness_gen_mode,
+            ..config
+        },
+        resources_dir,
+    )
+}
+
+#[test]
+fn test_missing_wasm_is_allowed_with_subprocess_witness_gen() {
+    let (config, _resources_dir) = config_with_zkey_only(WitnessGenMode::Subprocess);
+
+    check_circuit_config_matches_circuit(&config, &get_test_circuit_config(), false);
+}
+
+#[test]
+#[should_panic(expected = "main.wasm")]
+fn test_missing_wasm_is_rejected_with_in_process_witness_gen() {
+    let (config, _resources_dir) = config_with_zkey_only(WitnessGenMode::InProcess);
+
+    check_circuit_config_matches_circuit(&config, &get_test_circuit_config(), false);
+}
+
+/// A config whose default setup is in a temporary directory, with `main_circom` if it's given.
+fn config_with_setup(
+    main_circom: Option<&str>,
+    allow_missing_main_circom: bool,
+) -> (ProverServiceConfig, TempDir) {
+    let resources_dir = tempfile::tempdir().unwrap();
+    let config = ProverServiceConfig {
+        resources_dir: resources_dir.path().to_str().unwrap().to_string(),
+        allow_missing_main_circom,
+        ..get_config()
+    };
+    if let Some(main_circom) = main_circom {
+        let main_circom_path = config.main_circom_path(false);
+        fs::create_dir_all(Path::new(&main_circom_path).parent().unwrap()).unwrap();
+        fs::write(main_circom_path, main_circom).unwrap();
+    }
+    (config, resources_dir)
+}
+
+#[test]
+fn test_conversion_config_matches_setup_main_circom() {
+    let main_circom = fs::read_to_string("../circuit/templates/main.circom").unwrap();
+    let (config, _resources_dir) = config_with_setup(Some(&main_circom), false);
+
+    check_circuit_config_matches_setup(&config, &get_test_circuit_config(), false);
+}
+
+#[test]
+#[should_panic(expected = "allow_missing_main_circom")]
+fn test_setup_without_main_circom_is_rejected() {
+    let (config, _resources_dir) = config_with_setup(None, false);
+
+    check_circuit_config_matches_setup(&config, &get_test_circuit_config(), false);
+}
+
+#[test]
+fn test_setup_without_main_circom_can_be_allowed() {
+    let (config, _resources_dir) = config_with_setup(None, true);
+
+    check_circuit_config_matches_setup(&config, &get_test_circuit_config(), false);
+}
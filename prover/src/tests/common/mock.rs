@@ -0,0 +1,347 @@
+// Copyright © Aptos Foundation
+
+//! A prover backend and a witness generator that follow a script rather than a trusted setup, so
+//! that `prove_handler` can be tested without a zkey or a witness generation binary.
+
+use super::{
+    gen_test_jwk_keypair, gen_test_training_wheels_keypair, get_config, get_test_circuit_config,
+    types::{DefaultTestJWKKeyPair, ProofTestCase, TestJWKKeyPair, TestJWTPayload, WithNonce},
+};
+use crate::{
+    api::{ProverServiceResponse, RequestInput},
+    config::ProverServiceConfig,
+    debug_capture::DebugCapturer,
+    error::ErrorWithCode,
+    groth16_vk::{Groth16VerificationKey, ON_CHAIN_GROTH16_VK},
+    handlers::prove_handler,
+    jwk_fetching::{KeyID, DECODING_KEY_CACHE},
+    prover_backend::{
+        arkworks::snarkjs_proof_json, encode_proof, BackendProof, ProverBackend, ProverBackendError,
+    },
+    prover_key::{OnChainKeylessConfiguration, TrainingWheelsKeyPair, ON_CHAIN_TW_PK},
+    state::{InvalidProofBreaker, ProverServiceState, ProverSetup, Readiness},
+    witness_gen::{WitnessGenError, WitnessGenerator},
+};
+use anyhow::{anyhow, Result};
+use aptos_crypto::ed25519::Ed25519PublicKey;
+use aptos_types::jwks::rsa::RSA_JWK;
+use ark_bn254::{Bn254, Fr};
+use ark_ff::{BigInteger, One, PrimeField};
+use ark_groth16::{Groth16, ProvingKey};
+use ark_relations::{
+    lc,
+    r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError, Variable},
+};
+use ark_serialize::CanonicalSerialize;
+use ark_std::rand::rngs::OsRng;
+use axum::{extract::State, Json};
+use axum_extra::extract::WithRejection;
+use dashmap::DashMap;
+use futures::future::BoxFuture;
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    collections::VecDeque,
+    io::Cursor,
+    marker::PhantomData,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, RwLock,
+    },
+    time::Duration,
+};
+
+/// Outcomes for a mock to return, in order, and how often it was called.
+pub struct Script<T> {
+    outcomes: Mutex<VecDeque<T>>,
+    calls: AtomicUsize,
+}
+
+impl<T> Default for Script<T> {
+    fn default() -> Self {
+        Self {
+            outcomes: Mutex::new(VecDeque::new()),
+            calls: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl<T> Script<T> {
+    pub fn push(&self, outcome: T) {
+        self.outcomes.lock().unwrap().push_back(outcome);
+    }
+
+    pub fn calls(&self) -> usize {
+        self.calls.load(Ordering::SeqCst)
+    }
+
+    /// Records a call, and returns the next scripted outcome, if any is left.
+    fn next(&self) -> Option<T> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        self.outcomes.lock().unwrap().pop_front()
+    }
+}
+
+/// What the mock prover backend does with a witness. Once the script runs out, it proves validly.
+pub enum MockProof {
+    Valid,
+    /// A proof for another public inputs hash than the witness's.
+    Invalid,
+    Error(ProverBackendError),
+}
+
+/// A circuit whose only public input is the public inputs hash, as for the keyless circuit.
+#[derive(Clone, Copy)]
+struct PublicInputCircuit {
+    public_inputs_hash: Fr,
+}
+
+impl ConstraintSynthesizer<Fr> for PublicInputCircuit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+        let public_inputs_hash = cs.new_input_variable(|| Ok(self.public_inputs_hash))?;
+        cs.enforce_constraint(
+            lc!() + public_inputs_hash,
+            lc!() + Variable::One,
+            lc!() + public_inputs_hash,
+        )
+    }
+}
+
+/// Proves [`PublicInputCircuit`] for the public inputs hash of the witnesses it gets.
+pub struct MockProverBackend {
+    proving_key: ProvingKey<Bn254>,
+    script: Arc<Script<MockProof>>,
+}
+
+impl MockProverBackend {
+    fn prove_public_inputs_hash(&self, public_inputs_hash: Fr) -> Result<BackendProof> {
+        let proof = Groth16::<Bn254>::create_random_proof_with_reduction(
+            PublicInputCircuit { public_inputs_hash },
+            &self.proving_key,
+            &mut OsRng,
+        )?;
+        let proof_json = snarkjs_proof_json(&proof);
+        Ok(BackendProof {
+            proof: encode_proof(&serde_json::from_value(proof_json.clone())?)?,
+            proof_json: proof_json.to_string(),
+            prover_time: Duration::ZERO,
+        })
+    }
+}
+
+impl ProverBackend for MockProverBackend {
+    fn prove(&self, wtns: &[u8]) -> Result<BackendProof, ProverBackendError> {
+        let public_inputs_hash = read_mock_witness(wtns).map_err(ProverBackendError::Failed)?;
+        let public_inputs_hash = match self.script.next().unwrap_or(MockProof::Valid) {
+            MockProof::Valid => public_inputs_hash,
+            MockProof::Invalid => public_inputs_hash + Fr::one(),
+            MockProof::Error(e) => return Err(e),
+        };
+        self.prove_public_inputs_hash(public_inputs_hash)
+            .map_err(ProverBackendError::Failed)
+    }
+}
+
+/// Computes a witness of [`PublicInputCircuit`] from the circuit input signals, unless the script
+/// says to fail.
+pub struct MockWitnessGenerator {
+    script: Arc<Script<WitnessGenError>>,
+}
+
+impl WitnessGenerator for MockWitnessGenerator {
+    fn generate(&self, input: Value) -> BoxFuture<'_, Result<Vec<u8>, WitnessGenError>> {
+        Box::pin(async move {
+            if let Some(e) = self.script.next() {
+                return Err(e);
+            }
+            let public_inputs_hash = input["public_inputs_hash"]
+                .as_str()
+                .and_then(|s| Fr::from_str(s).ok())
+                .ok_or_else(|| {
+                    WitnessGenError::Failed(anyhow!("The input has no public_inputs_hash"))
+                })?;
+            Ok(write_mock_witness(&[Fr::one(), public_inputs_hash]))
+        })
+    }
+}
+
+/// Encodes a witness in the `.wtns` format.
+pub fn write_mock_witness(witness: &[Fr]) -> Vec<u8> {
+    let mut wtns = b"wtns".to_vec();
+    wtns.extend(2u32.to_le_bytes());
+    wtns.extend(2u32.to_le_bytes());
+
+    wtns.extend(1u32.to_le_bytes());
+    wtns.extend(40u64.to_le_bytes());
+    wtns.extend(32u32.to_le_bytes());
+    wtns.extend(Fr::MODULUS.to_bytes_le());
+    wtns.extend((witness.len() as u32).to_le_bytes());
+
+    wtns.extend(2u32.to_le_bytes());
+    wtns.extend((witness.len() as u64 * 32).to_le_bytes());
+    for element in witness {
+        wtns.extend(element.into_bigint().to_bytes_le());
+    }
+    wtns
+}
+
+fn read_mock_witness(wtns: &[u8]) -> Result<Fr> {
+    crate::zkey::read_witness(Cursor::new(wtns))?
+        .get(1)
+        .copied()
+        .ok_or_else(|| anyhow!("The witness has no public inputs hash"))
+}
+
+/// A setup of [`PublicInputCircuit`], with the scripts of its prover backend and witness
+/// generator.
+pub struct MockSetup {
+    pub vk: Groth16VerificationKey,
+    pub prover_script: Arc<Script<MockProof>>,
+    pub witness_gen_script: Arc<Script<WitnessGenError>>,
+    proving_key: ProvingKey<Bn254>,
+}
+
+impl MockSetup {
+    /// Runs a trusted setup of [`PublicInputCircuit`].
+    pub fn generate() -> Self {
+        let proving_key = Groth16::<Bn254>::generate_random_parameters_with_reduction(
+            PublicInputCircuit {
+                public_inputs_hash: Fr::one(),
+            },
+            &mut OsRng,
+        )
+        .unwrap();
+        let mut vk_bytes = vec![];
+        proving_key.vk.serialize_compressed(&mut vk_bytes).unwrap();
+
+        Self {
+            vk: Groth16VerificationKey::from_canonical_bytes(&vk_bytes).unwrap(),
+            prover_script: Arc::default(),
+            witness_gen_script: Arc::default(),
+            proving_key,
+        }
+    }
+
+    fn prover_setup(&self) -> ProverSetup {
+        ProverSetup {
+            prover: tokio::sync::Mutex::new(Box::new(MockProverBackend {
+                proving_key: self.proving_key.clone(),
+                script: self.prover_script.clone(),
+            })),
+            circuit_config: get_test_circuit_config(),
+            witness_generator: Box::new(MockWitnessGenerator {
+                script: self.witness_gen_script.clone(),
+            }),
+            prepared_vk: self.vk.prepared_vk(),
+            invalid_proof_breaker: InvalidProofBreaker::default(),
+        }
+    }
+}
+
+/// A prover service with a default and a new mock setup, and a default and a new training wheels
+/// key. The on-chain VK and training wheels key start out as neither of the new ones.
+pub struct MockProverService {
+    pub state: Arc<ProverServiceState>,
+    pub default_setup: MockSetup,
+    pub new_setup: MockSetup,
+    pub tw_pk_default: Ed25519PublicKey,
+    pub tw_pk_new: Ed25519PublicKey,
+    jwk_keypair: DefaultTestJWKKeyPair,
+}
+
+impl MockProverService {
+    pub fn init() -> Self {
+        Self::with_config(get_config())
+    }
+
+    /// Like `init`, with `config` rather than the test config. Its debug captures are set up, but
+    /// JWKs are never fetched.
+    pub fn with_config(config: ProverServiceConfig) -> Self {
+        let default_setup = MockSetup::generate();
+        let new_setup = MockSetup::generate();
+        let (tw_sk_default, tw_pk_default) = gen_test_training_wheels_keypair();
+        let (tw_sk_new, tw_pk_new) = gen_test_training_wheels_keypair();
+        let jwk_keypair = gen_test_jwk_keypair();
+
+        let dm: DashMap<KeyID, Arc<RSA_JWK>> =
+            DashMap::from_iter([("test-rsa".to_owned(), Arc::new(jwk_keypair.into_rsa_jwk()))]);
+        DECODING_KEY_CACHE.insert(String::from("test.oidc.provider"), dm);
+
+        let state = ProverServiceState {
+            default_setup: default_setup.prover_setup(),
+            new_setup: Some(new_setup.prover_setup()),
+            new_groth16_vk: Some(new_setup.vk.try_as_onchain_repr().unwrap()),
+            tw_keypair_default: TrainingWheelsKeyPair::from_sk(tw_sk_default),
+            tw_keypair_new: Some(TrainingWheelsKeyPair::from_sk(tw_sk_new)),
+            debug_capturer: config
+                .debug_capture
+                .as_ref()
+                .map(|debug_capture_config| DebugCapturer::new(debug_capture_config).unwrap()),
+            config: ProverServiceConfig {
+                enable_federated_jwks: false,
+                ..config
+            },
+            readiness: RwLock::new(Readiness::Ready),
+        };
+        let service = Self {
+            state: Arc::new(state),
+            default_setup,
+            new_setup,
+            tw_pk_default,
+            tw_pk_new,
+            jwk_keypair,
+        };
+        service.set_on_chain_groth16_vk(false);
+        service.set_on_chain_tw_pk(false);
+        service
+    }
+
+    /// Publishes the VK of the new setup on chain, or the VK of the default one.
+    pub fn set_on_chain_groth16_vk(&self, new: bool) {
+        let setup = if new {
+            &self.new_setup
+        } else {
+            &self.default_setup
+        };
+        *ON_CHAIN_GROTH16_VK.write().unwrap() = Some(setup.vk.try_as_onchain_repr().unwrap());
+    }
+
+    /// Publishes the new training wheels key on chain, or the default one.
+    pub fn set_on_chain_tw_pk(&self, new: bool) {
+        let tw_pk = if new {
+            &self.tw_pk_new
+        } else {
+            &self.tw_pk_default
+        };
+        *ON_CHAIN_TW_PK.write().unwrap() =
+            Some(OnChainKeylessConfiguration::from_tw_pk(Some(tw_pk.clone())));
+    }
+
+    /// A valid request for a JWT signed by the test OIDC provider.
+    pub fn request(&self) -> RequestInput {
+        self.request_with_payload(TestJWTPayload::default())
+    }
+
+    /// A request for a JWT with `payload`, signed by the test OIDC provider.
+    pub fn request_with_payload<T: Serialize + WithNonce + Clone>(
+        &self,
+        payload: T,
+    ) -> RequestInput {
+        ProofTestCase::default_with_payload(payload)
+            .compute_nonce(&get_test_circuit_config())
+            .convert_to_prover_request(&self.jwk_keypair)
+    }
+
+    pub async fn prove(
+        &self,
+        request: RequestInput,
+    ) -> Result<ProverServiceResponse, ErrorWithCode> {
+        prove_handler(
+            State(self.state.clone()),
+            WithRejection(Json(request), PhantomData),
+        )
+        .await
+        .map(|(_, Json(response))| response)
+    }
+}
@@ -1,7 +1,6 @@
 // Copyright © Aptos Foundation
 
 use self::types::{DefaultTestJWKKeyPair, TestJWKKeyPair, WithNonce};
-use crate::load_vk::prepared_vk;
 use crate::tests::common::types::ProofTestCase;
 use crate::training_wheels;
 use crate::{
@@ -9,7 +8,9 @@ use crate::{
     config::{self, ProverServiceConfig},
     handlers::prove_handler,
     jwk_fetching::{KeyID, DECODING_KEY_CACHE},
-    state::ProverServiceState,
+    prover_backend::{new_prover_backend, ProverBackend},
+    state::{read_circuit_config, InvalidProofBreaker, ProverServiceState, ProverSetup, Readiness},
+    witness_gen::new_witness_generator,
 };
 use aptos_crypto::{
     ed25519::{Ed25519PrivateKey, Ed25519PublicKey},
@@ -28,32 +29,34 @@ use figment::{
     Figment,
 };
 use rand::{rngs::ThreadRng, thread_rng};
-use rust_rapidsnark::FullProver;
 use serde::Serialize;
-use std::{fs, marker::PhantomData, str::FromStr, sync::Arc};
+use std::{
+    marker::PhantomData,
+    str::FromStr,
+    sync::{Arc, RwLock},
+};
 use tokio::sync::Mutex;
 
+pub mod mock;
 pub mod types;
 
-use crate::groth16_vk::{SnarkJsGroth16VerificationKey, ON_CHAIN_GROTH16_VK};
+use crate::groth16_vk::{Groth16VerificationKey, ON_CHAIN_GROTH16_VK};
 use crate::prover_key::{OnChainKeylessConfiguration, TrainingWheelsKeyPair, ON_CHAIN_TW_PK};
 
 const TEST_JWK_EXPONENT_STR: &str = "65537";
 
-pub fn init_test_full_prover(use_new_setup: bool) -> FullProver {
+pub fn init_test_prover_backend(use_new_setup: bool) -> Box<dyn ProverBackend> {
     let prover_server_config = Figment::new()
         .merge(Yaml::file(config::LOCAL_TESTING_CONFIG_FILE_PATH))
         .extract()
         .expect("Couldn't load config file");
     let config: ProverServiceConfig = prover_server_config;
 
-    FullProver::new(&config.zkey_path(use_new_setup))
-        .expect("failed to initialize rapidsnark prover")
+    new_prover_backend(&config, use_new_setup).expect("failed to initialize prover backend")
 }
 
 pub fn get_test_circuit_config() -> CircuitPaddingConfig {
-    serde_yaml::from_str(&fs::read_to_string("conversion_config.yml").expect("Unable to read file"))
-        .expect("should parse correctly")
+    read_circuit_config(config::CIRCUIT_CONFIG_FILE_PATH)
 }
 
 pub fn gen_test_ephemeral_pk() -> EphemeralPublicKey {
@@ -74,11 +77,11 @@ pub fn gen_test_ephemeral_pk_blinder() -> ark_bn254::Fr {
     ark_bn254::Fr::from_str("42").unwrap()
 }
 
-pub fn gen_test_jwk_keypair() -> impl TestJWKKeyPair {
+pub fn gen_test_jwk_keypair() -> DefaultTestJWKKeyPair {
     gen_test_jwk_keypair_with_kid_override("test-rsa")
 }
 
-pub fn gen_test_jwk_keypair_with_kid_override(kid: &str) -> impl TestJWKKeyPair {
+pub fn gen_test_jwk_keypair_with_kid_override(kid: &str) -> DefaultTestJWKKeyPair {
     let mut rng = rsa::rand_core::OsRng;
     DefaultTestJWKKeyPair::new_with_kid_and_exp(
         &mut rng,
@@ -110,8 +113,8 @@ pub fn get_config() -> ProverServiceConfig {
 pub async fn convert_prove_and_verify(
     testcase: &ProofTestCase<impl Serialize + WithNonce + Clone>,
 ) -> Result<(), anyhow::Error> {
-    let full_prover = init_test_full_prover(false);
-    let full_prover_2 = init_test_full_prover(true);
+    let prover = init_test_prover_backend(false);
+    let prover_2 = init_test_prover_backend(true);
     let circuit_config = get_test_circuit_config();
     let jwk_keypair = gen_test_jwk_keypair();
     let (tw_sk_default, _) = gen_test_training_wheels_keypair();
@@ -120,10 +123,13 @@ pub async fn convert_prove_and_verify(
     let tw_keypair_new = Some(TrainingWheelsKeyPair::from_sk(tw_sk_new));
     let prover_server_config = get_config();
 
+    let load_vk = |use_new_setup| {
+        Groth16VerificationKey::from_file(prover_server_config.verification_key_path(use_new_setup))
+            .unwrap()
+    };
+
     let new_vk = if prover_server_config.new_setup_dir.is_some() {
-        let vk_json = fs::read_to_string(prover_server_config.verification_key_path(true)).unwrap();
-        let local_vk: SnarkJsGroth16VerificationKey = serde_json::from_str(&vk_json).unwrap();
-        Some(local_vk.try_as_onchain_repr().unwrap())
+        Some(load_vk(true).try_as_onchain_repr().unwrap())
     } else {
         None
     };
@@ -140,13 +146,26 @@ pub async fn convert_prove_and_verify(
     DECODING_KEY_CACHE.insert(String::from("test.oidc.provider"), dm);
 
     let state = ProverServiceState {
-        full_prover_default: Mutex::new(full_prover),
-        full_prover_new: Some(Mutex::new(full_prover_2)),
+        default_setup: ProverSetup {
+            prover: Mutex::new(prover),
+            circuit_config: circuit_config.clone(),
+            witness_generator: new_witness_generator(&prover_server_config, false).unwrap(),
+            prepared_vk: load_vk(false).prepared_vk(),
+            invalid_proof_breaker: InvalidProofBreaker::default(),
+        },
+        new_setup: Some(ProverSetup {
+            prover: Mutex::new(prover_2),
+            circuit_config,
+            witness_generator: new_witness_generator(&prover_server_config, true).unwrap(),
+            prepared_vk: load_vk(true).prepared_vk(),
+            invalid_proof_breaker: InvalidProofBreaker::default(),
+        }),
         new_groth16_vk: new_vk,
         tw_keypair_default,
         tw_keypair_new,
+        debug_capturer: None,
         config: prover_server_config.clone(),
-        circuit_config: circuit_config.clone(),
+        readiness: RwLock::new(Readiness::Ready),
     };
 
     let prover_request_input = testcase.convert_to_prover_request(&jwk_keypair);
@@ -162,7 +181,7 @@ pub async fn convert_prove_and_verify(
     )
     .await;
     let response = match r {
-        Ok(Json(response)) => response,
+        Ok((_, Json(response))) => response,
         Err(e) => panic!("prove_handler returned an error: {:?}", e),
     };
 
@@ -172,7 +191,7 @@ pub async fn convert_prove_and_verify(
             public_inputs_hash,
             ..
         } => {
-            let g16vk = prepared_vk(&prover_server_config.verification_key_path(true));
+            let g16vk = load_vk(true).prepared_vk();
             proof.verify_proof(public_inputs_hash.as_fr(), &g16vk)?;
             training_wheels::verify(&response, &tw_pk_new)
         }
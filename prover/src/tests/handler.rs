@@ -0,0 +1,377 @@
+// Copyright © Aptos Foundation
+
+use crate::api::ProverServiceResponse;
+use crate::config::{DebugCaptureConfig, ProverServiceConfig};
+use crate::debug_capture::{InvalidProofCapture, INVALID_PROOF_CAPTURE_EXTENSION};
+use crate::metrics;
+use crate::proof_encoding::{from_arkworks_compressed, ProofEncoding};
+use crate::prover_backend::ProverBackendError;
+use crate::state::Readiness;
+use crate::tests::common::get_config;
+use crate::tests::common::mock::{write_mock_witness, MockProof, MockProverService, MockSetup};
+use crate::training_wheels;
+use crate::witness_gen::WitnessGenError;
+use anyhow::anyhow;
+use aptos_crypto::{ed25519::Ed25519PublicKey, x25519, Uniform};
+use aptos_keyless_common::input_processing::encoding::AsFr;
+use ark_bn254::{Bn254, Fr};
+use ark_ff::{One, PrimeField};
+use ark_groth16::Groth16;
+use axum::http::StatusCode;
+use rand::thread_rng;
+use rust_rapidsnark::ProverError;
+use serial_test::serial;
+use sha2::{Digest, Sha256};
+use std::{fs, time::Duration};
+
+/// Checks that `response` carries a proof that verifies against `setup`'s VK, signed with
+/// `tw_pk`.
+fn assert_proven_with(
+    response: &ProverServiceResponse,
+    setup: &MockSetup,
+    tw_pk: &Ed25519PublicKey,
+) {
+    let ProverServiceResponse::Success {
+        proof,
+        public_inputs_hash,
+        ..
+    } = response
+    else {
+        panic!("Expected a proof, got {:?}", response);
+    };
+    proof
+        .verify_proof(public_inputs_hash.as_fr(), &setup.vk.prepared_vk())
+        .unwrap();
+    training_wheels::verify(response, tw_pk).unwrap();
+}
+
+async fn prove_err(service: &MockProverService) -> StatusCode {
+    service.prove(service.request()).await.unwrap_err().code()
+}
+
+#[tokio::test]
+#[serial]
+async fn test_default_setup_and_tw_key_before_rotation() {
+    let service = MockProverService::init();
+
+    let response = service.prove(service.request()).await.unwrap();
+
+    assert_proven_with(&response, &service.default_setup, &service.tw_pk_default);
+    assert_eq!(service.default_setup.prover_script.calls(), 1);
+    assert_eq!(service.new_setup.prover_script.calls(), 0);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_new_setup_once_its_vk_is_on_chain() {
+    let service = MockProverService::init();
+    service.set_on_chain_groth16_vk(true);
+
+    let response = service.prove(service.request()).await.unwrap();
+
+    assert_proven_with(&response, &service.new_setup, &service.tw_pk_default);
+    assert_eq!(service.default_setup.prover_script.calls(), 0);
+    assert_eq!(service.new_setup.prover_script.calls(), 1);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_new_tw_key_once_it_is_on_chain() {
+    let service = MockProverService::init();
+    service.set_on_chain_tw_pk(true);
+
+    let response = service.prove(service.request()).await.unwrap();
+    assert_proven_with(&response, &service.default_setup, &service.tw_pk_new);
+
+    service.set_on_chain_groth16_vk(true);
+    let response = service.prove(service.request()).await.unwrap();
+    assert_proven_with(&response, &service.new_setup, &service.tw_pk_new);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_retries_invalid_proofs() {
+    let service = MockProverService::init();
+    service.default_setup.prover_script.push(MockProof::Invalid);
+    service.default_setup.prover_script.push(MockProof::Invalid);
+
+    let response = service.prove(service.request()).await.unwrap();
+
+    assert_proven_with(&response, &service.default_setup, &service.tw_pk_default);
+    assert_eq!(service.default_setup.prover_script.calls(), 3);
+    assert_eq!(service.default_setup.witness_gen_script.calls(), 1);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_gives_up_on_invalid_proofs() {
+    let service = MockProverService::init();
+    for _ in 0..3 {
+        service.default_setup.prover_script.push(MockProof::Invalid);
+    }
+
+    assert_eq!(prove_err(&service).await, StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(service.default_setup.prover_script.calls(), 3);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_max_proof_attempts() {
+    let service = MockProverService::with_config(ProverServiceConfig {
+        max_proof_attempts: 1,
+        ..get_config()
+    });
+    let invalid_proofs = metrics::INVALID_PROOFS.with_label_values(&["default"]);
+    let invalid_proofs_before = invalid_proofs.get();
+    service.default_setup.prover_script.push(MockProof::Invalid);
+
+    assert_eq!(prove_err(&service).await, StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(service.default_setup.prover_script.calls(), 1);
+    assert_eq!(invalid_proofs.get(), invalid_proofs_before + 1);
+}
+
+/// A service whose breaker trips after 2 requests without a valid proof, with one attempt each.
+fn service_with_breaker(cooldown_secs: u64) -> MockProverService {
+    MockProverService::with_config(ProverServiceConfig {
+        max_proof_attempts: 1,
+        invalid_proof_breaker_threshold: 2,
+        invalid_proof_breaker_cooldown_secs: cooldown_secs,
+        ..get_config()
+    })
+}
+
+#[tokio::test]
+#[serial]
+async fn test_breaker_trips_after_invalid_proofs_in_a_row() {
+    let service = service_with_breaker(60);
+    let setup = &service.default_setup;
+
+    // A valid proof resets the streak.
+    setup.prover_script.push(MockProof::Invalid);
+    assert_eq!(prove_err(&service).await, StatusCode::INTERNAL_SERVER_ERROR);
+    service.prove(service.request()).await.unwrap();
+    setup.prover_script.push(MockProof::Invalid);
+    assert_eq!(prove_err(&service).await, StatusCode::INTERNAL_SERVER_ERROR);
+    assert!(!service
+        .state
+        .default_setup
+        .invalid_proof_breaker
+        .is_tripped());
+
+    setup.prover_script.push(MockProof::Invalid);
+    assert_eq!(prove_err(&service).await, StatusCode::INTERNAL_SERVER_ERROR);
+    assert!(service
+        .state
+        .default_setup
+        .invalid_proof_breaker
+        .is_tripped());
+
+    assert_eq!(prove_err(&service).await, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(setup.prover_script.calls(), 4);
+    assert_eq!(service.state.readiness(), Readiness::Ready);
+
+    // The new setup still serves requests.
+    service.set_on_chain_groth16_vk(true);
+    let response = service.prove(service.request()).await.unwrap();
+    assert_proven_with(&response, &service.new_setup, &service.tw_pk_default);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_breaker_recovers_after_cooldown() {
+    let service = service_with_breaker(1);
+    let setup = &service.default_setup;
+    for _ in 0..2 {
+        setup.prover_script.push(MockProof::Invalid);
+        assert_eq!(prove_err(&service).await, StatusCode::INTERNAL_SERVER_ERROR);
+    }
+    assert_eq!(prove_err(&service).await, StatusCode::SERVICE_UNAVAILABLE);
+
+    // After the cooldown, one request is let through, and an invalid proof trips the breaker again.
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    setup.prover_script.push(MockProof::Invalid);
+    assert_eq!(prove_err(&service).await, StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(prove_err(&service).await, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(setup.prover_script.calls(), 3);
+
+    // A valid proof closes it.
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    service.prove(service.request()).await.unwrap();
+    assert!(!service
+        .state
+        .default_setup
+        .invalid_proof_breaker
+        .is_tripped());
+    service.prove(service.request()).await.unwrap();
+    assert_eq!(setup.prover_script.calls(), 5);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_captures_invalid_proofs() {
+    let dir = tempfile::tempdir().unwrap();
+    let private_key = x25519::PrivateKey::generate(&mut thread_rng());
+    let service = MockProverService::with_config(ProverServiceConfig {
+        debug_capture: Some(DebugCaptureConfig {
+            dir: dir.path().to_str().unwrap().to_owned(),
+            recipient_public_key: hex::encode(private_key.public_key().as_slice()),
+            max_captures: 10,
+            retention_secs: 3600,
+            only_failed_requests: true,
+            capture_invalid_proofs: true,
+        }),
+        ..get_config()
+    });
+    service.default_setup.prover_script.push(MockProof::Invalid);
+    service.default_setup.prover_script.push(MockProof::Invalid);
+
+    let response = service.prove(service.request()).await.unwrap();
+    let ProverServiceResponse::Success {
+        public_inputs_hash, ..
+    } = response
+    else {
+        panic!("Expected a proof, got {:?}", response);
+    };
+
+    // Only the first invalid proof of a request is captured, and the request itself succeeded.
+    let paths: Vec<_> = fs::read_dir(dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    assert_eq!(paths.len(), 1);
+    assert_eq!(
+        paths[0].extension(),
+        Some(INVALID_PROOF_CAPTURE_EXTENSION.as_ref())
+    );
+
+    let capture = InvalidProofCapture::read(&paths[0], &private_key).unwrap();
+    assert!(!capture.use_new_setup);
+    assert_eq!(capture.public_inputs_hash, public_inputs_hash);
+    // Only the public inputs and a digest of the witness are kept.
+    let public_inputs_hash = Fr::from_le_bytes_mod_order(&public_inputs_hash);
+    assert_eq!(
+        capture.witness_public_inputs,
+        vec![public_inputs_hash.to_string()]
+    );
+    assert_eq!(capture.witness_len, 2);
+    assert_eq!(
+        capture.wtns_sha256,
+        <[u8; 32]>::from(Sha256::digest(write_mock_witness(&[
+            Fr::one(),
+            public_inputs_hash
+        ])))
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn test_prover_backend_errors() {
+    let service = MockProverService::init();
+    let setup = &service.default_setup;
+
+    setup
+        .prover_script
+        .push(MockProof::Error(ProverBackendError::Rapidsnark(
+            ProverError::InvalidInput,
+        )));
+    assert_eq!(prove_err(&service).await, StatusCode::BAD_REQUEST);
+
+    setup
+        .prover_script
+        .push(MockProof::Error(ProverBackendError::Failed(anyhow!(
+            "out of memory"
+        ))));
+    assert_eq!(prove_err(&service).await, StatusCode::INTERNAL_SERVER_ERROR);
+
+    // Errors, unlike invalid proofs, aren't retried.
+    assert_eq!(setup.prover_script.calls(), 2);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_witness_gen_errors() {
+    let service = MockProverService::init();
+    let setup = &service.default_setup;
+
+    setup
+        .witness_gen_script
+        .push(WitnessGenError::Timeout(Duration::from_secs(10)));
+    assert_eq!(prove_err(&service).await, StatusCode::GATEWAY_TIMEOUT);
+
+    setup
+        .witness_gen_script
+        .push(WitnessGenError::ResourceLimit(anyhow!("out of memory")));
+    assert_eq!(prove_err(&service).await, StatusCode::SERVICE_UNAVAILABLE);
+
+    setup
+        .witness_gen_script
+        .push(WitnessGenError::Failed(anyhow!("assertion failed")));
+    assert_eq!(prove_err(&service).await, StatusCode::INTERNAL_SERVER_ERROR);
+
+    assert_eq!(setup.prover_script.calls(), 0);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_rejects_bad_requests_before_proving() {
+    let service = MockProverService::init();
+
+    // The JWT's nonce commits to another expiry date.
+    let mut request = service.request();
+    request.exp_date_secs += 1;
+    assert_eq!(
+        service.prove(request).await.unwrap_err().code(),
+        StatusCode::BAD_REQUEST
+    );
+
+    let mut request = service.request();
+    request.uid_key = String::from("unknown");
+    assert_eq!(
+        service.prove(request).await.unwrap_err().code(),
+        StatusCode::BAD_REQUEST
+    );
+
+    assert_eq!(service.default_setup.witness_gen_script.calls(), 0);
+    assert_eq!(service.default_setup.prover_script.calls(), 0);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_unavailable_until_ready() {
+    let service = MockProverService::init();
+    service.state.set_readiness(Readiness::SelfTestRunning);
+
+    assert_eq!(prove_err(&service).await, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(service.default_setup.witness_gen_script.calls(), 0);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_extra_proof_encodings() {
+    let service = MockProverService::init();
+    let mut request = service.request();
+    request.proof_encodings = vec![
+        ProofEncoding::ArkworksCompressed,
+        ProofEncoding::SolidityCalldata,
+    ];
+
+    let response = service.prove(request).await.unwrap();
+    assert_proven_with(&response, &service.default_setup, &service.tw_pk_default);
+    let ProverServiceResponse::Success {
+        public_inputs_hash,
+        proof_encodings,
+        ..
+    } = response
+    else {
+        unreachable!();
+    };
+
+    assert!(proof_encodings.snarkjs_json.is_none());
+    let proof = from_arkworks_compressed(&proof_encodings.arkworks_compressed.unwrap()).unwrap();
+    let vk = service.default_setup.vk.prepared_vk();
+    assert!(Groth16::<Bn254>::verify_proof(&vk, &proof, &[public_inputs_hash.as_fr()]).unwrap());
+    assert_eq!(
+        proof_encodings.solidity_calldata.unwrap().decode().unwrap(),
+        (proof, vec![public_inputs_hash.as_fr()])
+    );
+}
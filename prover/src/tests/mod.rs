@@ -1,5 +1,11 @@
+pub mod circuit_config;
 pub mod common;
+pub mod handler;
 pub mod jwk_fetching;
 pub mod playground;
+pub mod secret_hygiene;
+pub mod self_test;
 pub mod smoke;
 pub mod training_wheels;
+pub mod watcher;
+pub mod witness_gen;
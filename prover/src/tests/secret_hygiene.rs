@@ -0,0 +1,148 @@
+// Copyright © Aptos Foundation
+
+use crate::input_processing::{derive_circuit_input_signals, preprocess::decode_and_add_jwk};
+use crate::tests::common::mock::MockProverService;
+use crate::tests::common::types::{ProofTestCase, TestJWKKeyPair, TestJWTPayload, WithNonce};
+use crate::tests::common::{gen_test_jwk_keypair, get_config, get_test_circuit_config};
+use aptos_keyless_common::input_processing::encoding::{AsFr, FromB64, JwtParts};
+use aptos_types::keyless::Pepper;
+use axum::response::IntoResponse;
+use serde::Serialize;
+use serial_test::serial;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use tracing::{debug, Level};
+use tracing_subscriber::fmt::MakeWriter;
+
+#[derive(Clone, Default)]
+struct LogBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for LogBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for LogBuffer {
+    type Writer = Self;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+impl LogBuffer {
+    fn contents(&self) -> String {
+        String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+    }
+}
+
+/// A JWT payload with its `name` claim, which the test requests use as their extra field, twice.
+#[derive(Serialize, Clone)]
+struct DuplicateNamePayload {
+    #[serde(flatten)]
+    payload: TestJWTPayload,
+    #[serde(rename = "name")]
+    duplicate_name: String,
+}
+
+impl WithNonce for DuplicateNamePayload {
+    fn with_nonce(&self, nonce: &str) -> Self {
+        Self {
+            payload: self.payload.with_nonce(nonce),
+            ..self.clone()
+        }
+    }
+}
+
+#[test]
+fn test_no_secrets_in_logs() {
+    let pepper = Pepper::new([0x5e; 31]);
+    let testcase = ProofTestCase {
+        pepper: pepper.clone(),
+        ..ProofTestCase::default_with_payload(TestJWTPayload::default())
+    }
+    .compute_nonce(&get_test_circuit_config());
+    let jwk_keypair = gen_test_jwk_keypair();
+    let request = testcase.convert_to_prover_request(&jwk_keypair);
+
+    let jwt = request.jwt_b64.expose_secret().clone();
+    let secrets = [
+        jwt.clone(),
+        JwtParts::from_b64(&jwt).unwrap().payload_decoded().unwrap(),
+        hex::encode([0x5e; 31]),
+        pepper.as_fr().to_string(),
+        hex::encode(request.epk_blinder.expose_secret()),
+        testcase.epk_blinder_fr.to_string(),
+    ];
+
+    let logs = LogBuffer::default();
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(Level::TRACE)
+        .with_ansi(false)
+        .with_writer(logs.clone())
+        .finish();
+
+    tracing::subscriber::with_default(subscriber, || {
+        debug!("request: {:?}", request);
+        let input =
+            decode_and_add_jwk(request, Some(&jwk_keypair.into_rsa_jwk()), &get_config()).unwrap();
+        debug!("input: {:?}", input);
+        derive_circuit_input_signals(input, &get_test_circuit_config()).unwrap();
+    });
+
+    let logs = logs.contents();
+    // Makes sure that the logs were actually captured.
+    assert!(logs.contains("header decoded"));
+    assert!(logs.contains("[REDACTED]"));
+    for secret in secrets {
+        assert!(!logs.contains(&secret), "{} was logged", secret);
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn test_no_secrets_in_logs_or_responses_on_failure() {
+    let service = MockProverService::init();
+    let payload = TestJWTPayload::default();
+    let request = service.request_with_payload(DuplicateNamePayload {
+        duplicate_name: String::from("Mallory Duplicate"),
+        payload: payload.clone(),
+    });
+    let secrets = [
+        JwtParts::from_b64(request.jwt_b64.expose_secret())
+            .unwrap()
+            .payload_decoded()
+            .unwrap(),
+        payload.name,
+        String::from("Mallory Duplicate"),
+    ];
+
+    let logs = LogBuffer::default();
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(Level::TRACE)
+        .with_ansi(false)
+        .with_writer(logs.clone())
+        .finish();
+    let response = {
+        let _guard = tracing::subscriber::set_default(subscriber);
+        service.prove(request).await.unwrap_err().into_response()
+    };
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let body = String::from_utf8(body.to_vec()).unwrap();
+
+    let logs = logs.contents();
+    // Makes sure that the request was rejected by the field parser, and that it was logged.
+    assert!(body.contains("Duplicate key"), "{}", body);
+    assert!(logs.contains("Duplicate key"));
+    for secret in secrets {
+        assert!(!logs.contains(&secret), "{} was logged", secret);
+        assert!(!body.contains(&secret), "{} was in the response", secret);
+    }
+}
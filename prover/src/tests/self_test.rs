@@ -0,0 +1,45 @@
+// Copyright © Aptos Foundation
+
+use crate::config::EmailVerifiedPolicy;
+use crate::groth16_vk::Groth16VerificationKey;
+use crate::input_processing::preprocess::decode_with_jwk;
+use crate::self_test::{check_setup, fixture_request, Artifact};
+use crate::state::ProverSetup;
+use crate::tests::common::{get_config, get_test_circuit_config};
+use crate::training_wheels::{check_nonce_consistency, validate_jwt_sig_and_dates};
+use serial_test::serial;
+use std::sync::Arc;
+
+#[test]
+fn test_fixture_request_is_consistent() {
+    let (request, jwk) = fixture_request();
+    validate_jwt_sig_and_dates(&request, Some(&jwk), false).unwrap();
+
+    let input = decode_with_jwk(request, Arc::new(jwk), EmailVerifiedPolicy::NotRequired).unwrap();
+    check_nonce_consistency(&input, &get_test_circuit_config()).unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_self_test_passes() {
+    let config = get_config();
+    let setup = ProverSetup::load(&config, false);
+
+    check_setup(&config, &setup, false).await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_self_test_reports_mismatched_vk() {
+    let config = get_config();
+    let setup = ProverSetup {
+        prepared_vk: Groth16VerificationKey::from_file("./resources/toy_circuit/toy_vk.json")
+            .unwrap()
+            .prepared_vk(),
+        ..ProverSetup::load(&config, false)
+    };
+
+    let err = check_setup(&config, &setup, false).await.unwrap_err();
+    assert_eq!(err.setup, "default");
+    assert_eq!(err.artifact, Artifact::VerificationKey);
+}
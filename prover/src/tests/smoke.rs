@@ -1,7 +1,7 @@
 // Copyright © Aptos Foundation
 
-use crate::handlers::encode_proof;
-use crate::load_vk::prepared_vk;
+use crate::groth16_vk::Groth16VerificationKey;
+use crate::prover_backend::encode_proof;
 use crate::tests::common::get_test_circuit_config;
 use crate::tests::common::{
     convert_prove_and_verify,
@@ -204,12 +204,14 @@ async fn request_all_sub_lengths() {
 #[test]
 fn dummy_circuit_load_test() {
     let prover = FullProver::new("./resources/toy_circuit/toy_1.zkey").unwrap();
+    let g16vk = Groth16VerificationKey::from_file("./resources/toy_circuit/toy_vk.json")
+        .unwrap()
+        .prepared_vk();
 
     for _i in 0..1000 {
         let (proof_json, _) = prover.prove("./resources/toy_circuit/toy.wtns").unwrap();
 
         let proof = encode_proof(&serde_json::from_str(proof_json).unwrap()).unwrap();
-        let g16vk = prepared_vk("./resources/toy_circuit/toy_vk.json");
         proof.verify_proof(2.into(), &g16vk).unwrap();
     }
 }
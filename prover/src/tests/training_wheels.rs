@@ -1,8 +1,14 @@
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use crate::config::EmailVerifiedPolicy;
+use crate::input_processing::preprocess::decode_and_add_jwk;
 use crate::tests::common::types::{ProofTestCase, TestJWTPayload};
-use crate::tests::common::{gen_test_jwk_keypair, get_test_circuit_config, types::TestJWKKeyPair};
-use crate::training_wheels::validate_jwt_sig_and_dates;
+use crate::tests::common::{
+    gen_test_jwk_keypair, get_config, get_test_circuit_config, types::TestJWKKeyPair,
+};
+use crate::training_wheels::{validate_jwt_payload_parsing, validate_jwt_sig_and_dates};
+use aptos_keyless_common::input_processing::encoding::{AudSelectionError, JwtAud};
+use http::StatusCode;
 
 #[test]
 fn test_validate_jwt_sig_and_dates() {
@@ -105,3 +111,125 @@ fn test_validate_jwt_sig_and_dates_future_iat_can_be_disabled() {
     )
     .is_ok());
 }
+
+#[test]
+fn test_validate_jwt_payload_parsing_rejects_aud_array() {
+    let jwt_payload = TestJWTPayload {
+        aud: JwtAud::Multiple(vec![
+            String::from("407408718192.apps.googleusercontent.com"),
+            String::from("another.audience"),
+        ]),
+        ..TestJWTPayload::default()
+    };
+
+    let testcase = ProofTestCase {
+        aud_index: Some(1),
+        ..ProofTestCase::default_with_payload(jwt_payload)
+    }
+    .compute_nonce(&get_test_circuit_config());
+
+    let jwk_keypair = gen_test_jwk_keypair();
+    let prover_request_input = testcase.convert_to_prover_request(&jwk_keypair);
+    let input = decode_and_add_jwk(
+        prover_request_input,
+        Some(&jwk_keypair.into_rsa_jwk()),
+        &get_config(),
+    )
+    .unwrap();
+
+    let e = validate_jwt_payload_parsing(&input).unwrap_err();
+    assert_eq!(e.code(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        e.error.downcast_ref::<AudSelectionError>(),
+        Some(&AudSelectionError::ArrayNotSupportedByCircuit)
+    );
+}
+
+#[test]
+fn test_validate_jwt_payload_parsing_rejects_aud_index_on_single_aud() {
+    let testcase = ProofTestCase {
+        aud_index: Some(0),
+        ..ProofTestCase::default_with_payload(TestJWTPayload::default())
+    }
+    .compute_nonce(&get_test_circuit_config());
+
+    let jwk_keypair = gen_test_jwk_keypair();
+    let prover_request_input = testcase.convert_to_prover_request(&jwk_keypair);
+    let input = decode_and_add_jwk(
+        prover_request_input,
+        Some(&jwk_keypair.into_rsa_jwk()),
+        &get_config(),
+    )
+    .unwrap();
+
+    let e = validate_jwt_payload_parsing(&input).unwrap_err();
+    assert_eq!(
+        e.error.downcast_ref::<AudSelectionError>(),
+        Some(&AudSelectionError::IndexIntoSingleAud { index: 0 })
+    );
+}
+
+#[test]
+fn test_validate_jwt_payload_parsing_with_configured_uid_key() {
+    let testcase = ProofTestCase {
+        uid_key: String::from("name"),
+        ..ProofTestCase::default_with_payload(TestJWTPayload::default())
+    }
+    .compute_nonce(&get_test_circuit_config());
+
+    let jwk_keypair = gen_test_jwk_keypair();
+    let prover_request_input = testcase.convert_to_prover_request(&jwk_keypair);
+    let input = decode_and_add_jwk(
+        prover_request_input,
+        Some(&jwk_keypair.into_rsa_jwk()),
+        &get_config(),
+    )
+    .unwrap();
+
+    assert_eq!(input.email_verified_policy, EmailVerifiedPolicy::Required);
+    assert!(validate_jwt_payload_parsing(&input).is_ok());
+}
+
+#[test]
+fn test_validate_jwt_payload_parsing_requires_email_verified() {
+    let jwt_payload = TestJWTPayload {
+        email_verified: Some(false),
+        ..TestJWTPayload::default()
+    };
+
+    let testcase = ProofTestCase {
+        uid_key: String::from("name"),
+        ..ProofTestCase::default_with_payload(jwt_payload)
+    }
+    .compute_nonce(&get_test_circuit_config());
+
+    let jwk_keypair = gen_test_jwk_keypair();
+    let prover_request_input = testcase.convert_to_prover_request(&jwk_keypair);
+    let input = decode_and_add_jwk(
+        prover_request_input,
+        Some(&jwk_keypair.into_rsa_jwk()),
+        &get_config(),
+    )
+    .unwrap();
+
+    assert!(validate_jwt_payload_parsing(&input).is_err());
+}
+
+#[test]
+fn test_decode_and_add_jwk_rejects_unconfigured_uid_key() {
+    let testcase = ProofTestCase {
+        uid_key: String::from("locale"),
+        ..ProofTestCase::default_with_payload(TestJWTPayload::default())
+    }
+    .compute_nonce(&get_test_circuit_config());
+
+    let jwk_keypair = gen_test_jwk_keypair();
+    let prover_request_input = testcase.convert_to_prover_request(&jwk_keypair);
+
+    assert!(decode_and_add_jwk(
+        prover_request_input,
+        Some(&jwk_keypair.into_rsa_jwk()),
+        &get_config(),
+    )
+    .is_err());
+}
@@ -0,0 +1,199 @@
+// Copyright © Aptos Foundation
+
+use crate::metrics;
+use crate::watcher::{start_external_resource_refresh_loop, ExternalResource};
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    future::Future,
+    sync::{Arc, Mutex, RwLock},
+    time::Duration,
+};
+use tokio::{sync::watch, time::timeout};
+
+const REFRESH_INTERVAL: Duration = Duration::from_millis(20);
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+struct TestResource {
+    version: u64,
+}
+
+impl ExternalResource for TestResource {
+    fn resource_name() -> String {
+        "TestResource".to_string()
+    }
+}
+
+/// What the test server serves, and how it answered so far.
+#[derive(Default)]
+struct Served {
+    version: u64,
+    send_etag: bool,
+    failing: bool,
+    /// Never answers, rather than failing.
+    hanging: bool,
+    full_responses: usize,
+    not_modified_responses: usize,
+}
+
+async fn serve_resource(State(served): State<Arc<Mutex<Served>>>, headers: HeaderMap) -> Response {
+    let hanging = served.lock().unwrap().hanging;
+    if hanging {
+        return std::future::pending().await;
+    }
+    let mut served = served.lock().unwrap();
+    if served.failing {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    let etag = format!("\"{}\"", served.version);
+    if served.send_etag
+        && headers
+            .get(header::IF_NONE_MATCH)
+            .and_then(|v| v.to_str().ok())
+            == Some(&etag)
+    {
+        served.not_modified_responses += 1;
+        return StatusCode::NOT_MODIFIED.into_response();
+    }
+    served.full_responses += 1;
+    let body = Json(TestResource {
+        version: served.version,
+    });
+    if served.send_etag {
+        ([(header::ETAG, etag)], body).into_response()
+    } else {
+        body.into_response()
+    }
+}
+
+/// Serves `served` on a local port, and returns its URL.
+async fn start_server(served: Arc<Mutex<Served>>) -> String {
+    let app = Router::new()
+        .route("/resource", get(serve_resource))
+        .with_state(served);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{}/resource", addr)
+}
+
+async fn within_a_second<T>(future: impl Future<Output = T>) -> T {
+    timeout(Duration::from_secs(1), future)
+        .await
+        .expect("Timed out")
+}
+
+async fn next_version(receiver: &mut watch::Receiver<Option<TestResource>>) -> u64 {
+    within_a_second(receiver.changed()).await.unwrap();
+    let version = receiver.borrow_and_update().as_ref().unwrap().version;
+    version
+}
+
+#[tokio::test]
+async fn test_notifies_changes_and_sends_etag() {
+    let served = Arc::new(Mutex::new(Served {
+        version: 1,
+        send_etag: true,
+        ..Served::default()
+    }));
+    let url = start_server(served.clone()).await;
+    let cache = Arc::new(RwLock::new(None));
+
+    let mut receiver = start_external_resource_refresh_loop(&url, REFRESH_INTERVAL, cache.clone());
+    assert_eq!(next_version(&mut receiver).await, 1);
+
+    tokio::time::sleep(REFRESH_INTERVAL * 5).await;
+    assert!(!receiver.has_changed().unwrap());
+    {
+        let served = served.lock().unwrap();
+        assert_eq!(served.full_responses, 1);
+        assert!(served.not_modified_responses > 0);
+    }
+
+    served.lock().unwrap().version = 2;
+    assert_eq!(next_version(&mut receiver).await, 2);
+    assert_eq!(cache.read().unwrap().as_ref().unwrap().version, 2);
+}
+
+#[tokio::test]
+async fn test_only_notifies_actual_changes() {
+    let served = Arc::new(Mutex::new(Served {
+        version: 1,
+        ..Served::default()
+    }));
+    let url = start_server(served.clone()).await;
+
+    let mut receiver =
+        start_external_resource_refresh_loop(&url, REFRESH_INTERVAL, Arc::new(RwLock::new(None)));
+    assert_eq!(next_version(&mut receiver).await, 1);
+
+    // Without an ETag, the resource is downloaded every time, but it didn't change.
+    tokio::time::sleep(REFRESH_INTERVAL * 5).await;
+    assert!(served.lock().unwrap().full_responses > 1);
+    assert!(!receiver.has_changed().unwrap());
+}
+
+#[tokio::test]
+async fn test_counts_fetch_errors() {
+    let served = Arc::new(Mutex::new(Served {
+        failing: true,
+        ..Served::default()
+    }));
+    let url = start_server(served.clone()).await;
+    let fetch_errors = metrics::EXTERNAL_RESOURCE_FETCH_ERRORS
+        .with_label_values(&[&TestResource::resource_name()]);
+    let fetch_errors_before = fetch_errors.get();
+
+    let mut receiver = start_external_resource_refresh_loop::<TestResource>(
+        &url,
+        REFRESH_INTERVAL,
+        Arc::new(RwLock::new(None)),
+    );
+    within_a_second(async {
+        while fetch_errors.get() == fetch_errors_before {
+            tokio::time::sleep(REFRESH_INTERVAL).await;
+        }
+    })
+    .await;
+    assert!(receiver.borrow().is_none());
+
+    // The loop backs off, but recovers once the server does.
+    served.lock().unwrap().failing = false;
+    served.lock().unwrap().version = 1;
+    assert_eq!(next_version(&mut receiver).await, 1);
+}
+
+#[tokio::test]
+async fn test_gives_up_on_a_server_that_never_answers() {
+    let served = Arc::new(Mutex::new(Served {
+        hanging: true,
+        ..Served::default()
+    }));
+    let url = start_server(served.clone()).await;
+    let fetch_errors = metrics::EXTERNAL_RESOURCE_FETCH_ERRORS
+        .with_label_values(&[&TestResource::resource_name()]);
+    let fetch_errors_before = fetch_errors.get();
+
+    let mut receiver = start_external_resource_refresh_loop::<TestResource>(
+        &url,
+        REFRESH_INTERVAL,
+        Arc::new(RwLock::new(None)),
+    );
+    within_a_second(async {
+        while fetch_errors.get() == fetch_errors_before {
+            tokio::time::sleep(REFRESH_INTERVAL).await;
+        }
+    })
+    .await;
+
+    // The hung fetch doesn't keep the loop from fetching again.
+    served.lock().unwrap().hanging = false;
+    served.lock().unwrap().version = 1;
+    assert_eq!(next_version(&mut receiver).await, 1);
+}
@@ -0,0 +1,110 @@
+// Copyright © Aptos Foundation
+
+use crate::metrics;
+use crate::witness_gen::{
+    generate_witness, SubprocessWitnessGenerator, WitnessGenError, WitnessGenLimits,
+    WitnessGenerator,
+};
+use serde_json::json;
+use std::time::{Duration, Instant};
+
+fn test_limits() -> WitnessGenLimits {
+    WitnessGenLimits {
+        timeout: Duration::from_secs(10),
+        max_memory_bytes: None,
+        max_cpu_secs: None,
+    }
+}
+
+/// A witness generator running `script`, which gets the input and witness file paths as `$1`
+/// and `$2`.
+fn sh(script: &str, limits: WitnessGenLimits) -> SubprocessWitnessGenerator {
+    SubprocessWitnessGenerator::with_command(
+        String::from("sh"),
+        vec![String::from("-c"), String::from(script), String::from("sh")],
+        limits,
+    )
+}
+
+#[tokio::test]
+async fn test_subprocess_witness_gen() {
+    let input = json!({ "a": "1" });
+    let wtns = sh(r#"cp "$1" "$2""#, test_limits())
+        .generate(input.clone())
+        .await
+        .unwrap();
+
+    assert_eq!(wtns, serde_json::to_vec(&input).unwrap());
+}
+
+#[tokio::test]
+async fn test_subprocess_witness_gen_failure() {
+    let err = sh("echo 'Assert Failed.' >&2; exit 1", test_limits())
+        .generate(json!({}))
+        .await
+        .unwrap_err();
+
+    assert!(matches!(err, WitnessGenError::Failed(_)));
+    assert!(err.to_string().contains("Assert Failed."), "{}", err);
+    assert_eq!(err.status_code(), 500);
+}
+
+#[tokio::test]
+async fn test_subprocess_witness_gen_timeout() {
+    let start_time = Instant::now();
+    let limits = WitnessGenLimits {
+        timeout: Duration::from_millis(200),
+        ..test_limits()
+    };
+    let err = sh("sleep 5", limits).generate(json!({})).await.unwrap_err();
+
+    assert!(matches!(err, WitnessGenError::Timeout(_)));
+    assert_eq!(err.status_code(), 504);
+    assert!(start_time.elapsed() < Duration::from_secs(2));
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn test_subprocess_witness_gen_cpu_limit() {
+    let limits = WitnessGenLimits {
+        max_cpu_secs: Some(1),
+        ..test_limits()
+    };
+    let err = sh("while :; do :; done", limits)
+        .generate(json!({}))
+        .await
+        .unwrap_err();
+
+    assert!(matches!(err, WitnessGenError::ResourceLimit(_)), "{}", err);
+    assert_eq!(err.status_code(), 503);
+}
+
+#[tokio::test]
+async fn test_subprocess_witness_gen_cancelled() {
+    let dir = tempfile::tempdir().unwrap();
+    let marker = dir.path().join("done");
+    let generator = sh(
+        &format!("sleep 1; touch {}", marker.to_str().unwrap()),
+        test_limits(),
+    );
+    let cancelled_before = metrics::WITNESS_GEN_OUTCOMES
+        .with_label_values(&["cancelled"])
+        .get();
+
+    // Like a request whose client disconnects while its witness is being generated.
+    let result = tokio::time::timeout(
+        Duration::from_millis(200),
+        generate_witness(&generator, json!({})),
+    )
+    .await;
+    assert!(result.is_err());
+
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    assert!(!marker.exists(), "the subprocess should have been killed");
+    assert_eq!(
+        metrics::WITNESS_GEN_OUTCOMES
+            .with_label_values(&["cancelled"])
+            .get(),
+        cancelled_before + 1
+    );
+}
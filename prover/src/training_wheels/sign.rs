@@ -36,6 +36,7 @@ pub fn verify(
             proof,
             public_inputs_hash,
             training_wheels_signature,
+            ..
         } => {
             let ephem_tw_sig = EphemeralSignature::try_from(training_wheels_signature.as_slice())?;
             ephem_tw_sig.verify(
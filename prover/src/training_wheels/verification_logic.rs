@@ -6,7 +6,7 @@ use std::{
 use aptos_crypto::poseidon_bn254;
 use aptos_keyless_common::input_processing::{
     config::CircuitPaddingConfig,
-    encoding::{FromB64, JwtHeader, JwtParts, JwtPayload},
+    encoding::{FromB64, JwtAud, JwtHeader, JwtParts, JwtPayload},
 };
 use aptos_types::{
     jwks::rsa::RSA_JWK, keyless::Claims, transaction::authenticator::EphemeralPublicKey,
@@ -14,22 +14,24 @@ use aptos_types::{
 use ark_bn254::Fr;
 use http::StatusCode;
 use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use serde_json::Value;
 
 use crate::{
     api::RequestInput,
-    error::{ErrorWithCode, ThrowCodeOnError},
-    input_processing::{field_parser::FieldParser, types::Input},
+    config::EmailVerifiedPolicy,
+    error::{self, ErrorWithCode, ThrowCodeOnError},
+    input_processing::{field_check_input, field_parser::FieldParser, types::Input},
     jwk_fetching,
 };
 use anyhow::{bail, Context, Result};
 
 pub fn check_nonce_consistency(input: &Input, circuit_config: &CircuitPaddingConfig) -> Result<()> {
-    let payload_decoded = input.jwt_parts.payload_decoded()?;
+    let payload_decoded = input.jwt_parts.expose_secret().payload_decoded()?;
     let payload_struct: JwtPayload = serde_json::from_str(&payload_decoded)?;
     let computed_nonce = compute_nonce(
         input.exp_date_secs,
         &input.epk,
-        input.epk_blinder_fr,
+        *input.epk_blinder_fr.expose_secret(),
         circuit_config,
     )?;
 
@@ -45,7 +47,7 @@ pub fn validate_jwt_sig_and_dates(
     maybe_jwk: Option<&RSA_JWK>,
     disable_iat_in_past_check: bool,
 ) -> Result<(), ErrorWithCode> {
-    let jwt_parts = JwtParts::from_b64(&rqi.jwt_b64)?;
+    let jwt_parts = JwtParts::from_b64(rqi.jwt_b64.expose_secret())?;
 
     let header_decoded = jwt_parts.header_decoded()?;
     let header_struct: JwtHeader = serde_json::from_str(&header_decoded)?;
@@ -67,7 +69,7 @@ pub fn validate_jwt_sig_and_dates(
     validation.validate_exp = false;
     let key = &DecodingKey::from_rsa_components(&jwk.n, &jwk.e)?;
 
-    let _claims = jsonwebtoken::decode::<Claims>(&rqi.jwt_b64, key, &validation)?;
+    let _claims = jsonwebtoken::decode::<Claims>(rqi.jwt_b64.expose_secret(), key, &validation)?;
 
     let start = SystemTime::now();
     let since_the_epoch = start
@@ -84,30 +86,33 @@ pub fn validate_jwt_sig_and_dates(
 }
 
 pub fn validate_jwt_payload_parsing(input: &Input) -> Result<(), ErrorWithCode> {
-    let payload_decoded = input.jwt_parts.payload_decoded()?;
+    let payload_decoded = input.jwt_parts.expose_secret().payload_decoded()?;
     let payload_struct: JwtPayload = serde_json::from_str(&payload_decoded)?;
+    let payload_json: Value = serde_json::from_str(&payload_decoded)?;
     let uid_key = &input.uid_key;
 
     let parsed_uid = FieldParser::find_and_parse_field(&payload_decoded, uid_key)?;
+    if payload_json.get(uid_key).and_then(Value::as_str)
+        != Some(parsed_uid.unescaped_value.as_str())
+    {
+        crate::bail!("Circuit is parsing the \"{}\" field incorrectly", uid_key)
+    }
 
-    match uid_key.as_str() {
-        "email" => {
-            if Some(parsed_uid.value) != payload_struct.email {
-                crate::bail!("Circuit is parsing the \"email\" field incorrectly")
-            }
-        }
-        "sub" => {
-            if Some(parsed_uid.value) != payload_struct.sub {
-                crate::bail!("Circuit is parsing the \"sub\" field incorrectly")
-            }
-        }
-        _ => {
-            crate::bail!("unrecognized uid key")
+    if input.email_verified_policy == EmailVerifiedPolicy::Required {
+        match payload_json.get("email_verified") {
+            Some(Value::Bool(true)) => {}
+            Some(Value::String(s)) if s == "true" => {}
+            _ => crate::bail!(
+                "uid key \"{}\" requires the JWT to have a true email_verified claim",
+                uid_key
+            ),
         }
     }
 
+    // Rejects aud arrays, which the circuit can't parse yet, with a typed `AudSelectionError`.
+    field_check_input::jwt_aud_value(input).map_err(error::bad_request)?;
     let parsed_aud = FieldParser::find_and_parse_field(&payload_decoded, "aud")?;
-    if Some(parsed_aud.value) != payload_struct.aud {
+    if Some(JwtAud::Single(parsed_aud.unescaped_value)) != payload_struct.aud {
         crate::bail!("Circuit is parsing the \"aud\" field incorrectly")
     }
 
@@ -1,51 +1,188 @@
-use anyhow::Result;
+use crate::metrics;
+use anyhow::{bail, Result};
+use reqwest::{
+    header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+    Client, StatusCode,
+};
 use serde::de::DeserializeOwned;
 use std::sync::{Arc, RwLock};
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::watch;
 use tracing::{debug, info, warn};
 
-pub async fn fetch_and_cache_resource<T: DeserializeOwned>(
+/// How long the refresh loop waits at most between two attempts, however many failed in a row.
+pub const MAX_RETRY_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// How long a fetch may take at most. It is also given no longer than the refresh interval, so that
+/// a server that never answers doesn't hold up the refresh loop.
+pub const MAX_FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The validators of the last response, sent back so that the server can answer with a 304 if
+/// the resource hasn't changed since.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CacheValidators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FetchOutcome {
+    /// The resource differs from the cached one, which it replaced.
+    Changed,
+    /// The resource was downloaded again, but is the same as the cached one.
+    Unchanged,
+    /// The server answered that the resource hasn't changed, without sending it again.
+    NotModified,
+}
+
+/// Fetches the resource at `resource_url`, unless the server says it hasn't changed since the
+/// response `validators` came with. If it did change, it replaces the one in `resource_holder`,
+/// and is sent to the subscribers of `sender`.
+pub async fn fetch_and_cache_resource<T: DeserializeOwned + PartialEq + Clone>(
+    client: &Client,
     resource_url: &str,
+    validators: &mut CacheValidators,
     resource_holder: &RwLock<Option<T>>,
-) -> Result<()> {
-    let resource = reqwest::get(resource_url).await?.json::<T>().await?;
-    *resource_holder.write().unwrap() = Some(resource);
-    Ok(())
+    sender: &watch::Sender<Option<T>>,
+) -> Result<FetchOutcome> {
+    let mut request = client.get(resource_url);
+    if let Some(etag) = &validators.etag {
+        request = request.header(IF_NONE_MATCH, etag);
+    }
+    if let Some(last_modified) = &validators.last_modified {
+        request = request.header(IF_MODIFIED_SINCE, last_modified);
+    }
+
+    let response = request.send().await?;
+    if response.status() == StatusCode::NOT_MODIFIED {
+        return Ok(FetchOutcome::NotModified);
+    }
+    if !response.status().is_success() {
+        bail!("{} answered with {}", resource_url, response.status());
+    }
+
+    let header = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(String::from)
+    };
+    let new_validators = CacheValidators {
+        etag: header(ETAG),
+        last_modified: header(LAST_MODIFIED),
+    };
+    let resource = response.json::<T>().await?;
+    *validators = new_validators;
+
+    if sender.borrow().as_ref() == Some(&resource) {
+        return Ok(FetchOutcome::Unchanged);
+    }
+    *resource_holder.write().unwrap() = Some(resource.clone());
+    sender.send_replace(Some(resource));
+    Ok(FetchOutcome::Changed)
+}
+
+/// How long to wait before the next fetch, after `consecutive_failures` failed ones: the refresh
+/// interval, doubled for every failure, up to `MAX_RETRY_INTERVAL`.
+pub fn retry_interval(refresh_interval: Duration, consecutive_failures: u32) -> Duration {
+    if refresh_interval >= MAX_RETRY_INTERVAL {
+        return refresh_interval;
+    }
+    refresh_interval
+        .saturating_mul(2u32.saturating_pow(consecutive_failures))
+        .min(MAX_RETRY_INTERVAL)
 }
 
+/// Keeps `local_cache` up to date with the resource at `url`. The returned receiver is notified
+/// whenever the resource actually changes, not on every refresh.
 pub fn start_external_resource_refresh_loop<
-    T: DeserializeOwned + ExternalResource + Send + Sync + 'static,
+    T: DeserializeOwned + ExternalResource + PartialEq + Clone + Send + Sync + 'static,
 >(
     url: &str,
     refresh_interval: Duration,
     local_cache: Arc<RwLock<Option<T>>>,
-) {
+) -> watch::Receiver<Option<T>> {
     info!(
         "Starting external resource refresh loop for {}",
         T::resource_name()
     );
+    let (sender, receiver) = watch::channel(local_cache.read().unwrap().clone());
     let url = url.to_string();
     let _handle = tokio::spawn(async move {
+        let resource_name = T::resource_name();
+        let fetch_timeout = refresh_interval.min(MAX_FETCH_TIMEOUT);
+        let client = Client::builder()
+            .connect_timeout(fetch_timeout)
+            .timeout(fetch_timeout)
+            .build()
+            .expect("Couldn't build the HTTP client");
+        let mut validators = CacheValidators::default();
+        let mut consecutive_failures = 0;
         loop {
-            let result = fetch_and_cache_resource(&url, local_cache.as_ref()).await;
+            let result = fetch_and_cache_resource(
+                &client,
+                &url,
+                &mut validators,
+                local_cache.as_ref(),
+                &sender,
+            )
+            .await;
             match result {
-                Ok(_vk) => {
-                    debug!("fetch_and_cache_resource {} succeeded.", T::resource_name());
+                Ok(outcome) => {
+                    consecutive_failures = 0;
+                    metrics::EXTERNAL_RESOURCE_LAST_SUCCESS_SECS
+                        .with_label_values(&[&resource_name])
+                        .set(
+                            SystemTime::now()
+                                .duration_since(UNIX_EPOCH)
+                                .unwrap_or_default()
+                                .as_secs() as i64,
+                        );
+                    match outcome {
+                        FetchOutcome::Changed => info!("{} changed", resource_name),
+                        _ => debug!("fetch_and_cache_resource {} succeeded.", resource_name),
+                    }
                 }
                 Err(e) => {
+                    consecutive_failures += 1;
+                    metrics::EXTERNAL_RESOURCE_FETCH_ERRORS
+                        .with_label_values(&[&resource_name])
+                        .inc();
                     warn!(
-                        "fetch_and_cache_resource {} failed: {}",
-                        T::resource_name(),
-                        e
+                        "fetch_and_cache_resource {} failed {} times in a row: {}",
+                        resource_name, consecutive_failures, e
                     );
                 }
             }
-
-            tokio::time::sleep(refresh_interval).await;
+            tokio::time::sleep(retry_interval(refresh_interval, consecutive_failures)).await;
         }
     });
+    receiver
 }
 
 pub trait ExternalResource {
     fn resource_name() -> String;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{retry_interval, MAX_RETRY_INTERVAL};
+    use std::time::Duration;
+
+    #[test]
+    fn test_retry_interval() {
+        let refresh_interval = Duration::from_secs(10);
+        assert_eq!(retry_interval(refresh_interval, 0), refresh_interval);
+        assert_eq!(retry_interval(refresh_interval, 1), Duration::from_secs(20));
+        assert_eq!(retry_interval(refresh_interval, 3), Duration::from_secs(80));
+        assert_eq!(retry_interval(refresh_interval, 5), MAX_RETRY_INTERVAL);
+        assert_eq!(retry_interval(refresh_interval, 100), MAX_RETRY_INTERVAL);
+
+        let slow_refresh_interval = Duration::from_secs(3600);
+        assert_eq!(
+            retry_interval(slow_refresh_interval, 2),
+            slow_refresh_interval
+        );
+    }
+}
@@ -1,11 +1,24 @@
 // Copyright © Aptos Foundation
 
-use crate::config::ProverServiceConfig;
-use anyhow::{anyhow, bail, Result};
+use crate::config::{ProverServiceConfig, WitnessGenMode};
+use crate::metrics;
+use anyhow::{anyhow, Result};
+use aptos_keyless_common::input_processing::witness_calculator::{
+    CalculationLimits, Interruption, WitnessCalculator,
+};
+use axum::http::StatusCode;
+use futures::future::BoxFuture;
+use serde_json::Value;
 use std::fs;
-use std::process::Command;
+use std::process::Stdio;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use std::time::{Duration, Instant};
 use tempfile::NamedTempFile;
-use tracing::info_span;
+use tokio::process::Command;
+use tracing::{info_span, Instrument};
 
 pub trait PathStr {
     fn path_str(&self) -> Result<&str>;
@@ -17,68 +30,318 @@ impl PathStr for NamedTempFile {
     }
 }
 
-pub fn witness_gen(
+#[derive(Debug, thiserror::Error)]
+pub enum WitnessGenError {
+    #[error("Witness generation timed out after {0:?}")]
+    Timeout(Duration),
+    #[error("Witness generation exceeded its resource limits: {0}")]
+    ResourceLimit(anyhow::Error),
+    #[error("Witness generation was cancelled")]
+    Cancelled,
+    #[error("Witness generation failed: {0:#}")]
+    Failed(anyhow::Error),
+}
+
+impl WitnessGenError {
+    /// The `outcome` label of `metrics::WITNESS_GEN_OUTCOMES`.
+    pub fn outcome(&self) -> &'static str {
+        match self {
+            WitnessGenError::Timeout(_) => "timeout",
+            WitnessGenError::ResourceLimit(_) => "resource_limit",
+            WitnessGenError::Cancelled => "cancelled",
+            WitnessGenError::Failed(_) => "failed",
+        }
+    }
+
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            WitnessGenError::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            WitnessGenError::ResourceLimit(_) => StatusCode::SERVICE_UNAVAILABLE,
+            // Only happens once nobody is waiting for the response anymore. This is what nginx
+            // logs for requests the client closed.
+            WitnessGenError::Cancelled => StatusCode::from_u16(499).unwrap(),
+            WitnessGenError::Failed(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+fn failed(error: impl Into<anyhow::Error>) -> WitnessGenError {
+    WitnessGenError::Failed(error.into())
+}
+
+/// Bounds on a single witness generation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WitnessGenLimits {
+    pub timeout: Duration,
+    /// For subprocesses, this bounds their address space. In-process, it bounds the circuit's
+    /// linear memory.
+    pub max_memory_bytes: Option<u64>,
+    /// Only enforced for subprocesses. In-process, the timeout serves the same purpose.
+    pub max_cpu_secs: Option<u64>,
+}
+
+pub trait WitnessGenerator: Send + Sync {
+    /// Returns the witness for the circuit input signals in `input`, in the `.wtns` format.
+    /// Dropping the returned future stops the witness generation.
+    fn generate(&self, input: Value) -> BoxFuture<'_, Result<Vec<u8>, WitnessGenError>>;
+}
+
+/// Creates the witness generator selected by `config.witness_gen_mode` for a setup.
+pub fn new_witness_generator(
     config: &ProverServiceConfig,
     use_new_setup: bool,
-    body: &str,
-) -> Result<NamedTempFile> {
-    let span = info_span!("Generating witness");
-    let _enter = span.enter();
+) -> Result<Box<dyn WitnessGenerator>> {
+    let limits = config.witness_gen_limits();
+    Ok(match config.witness_gen_mode {
+        WitnessGenMode::InProcess => Box::new(InProcessWitnessGenerator::new(
+            &config.witness_gen_wasm_path(use_new_setup),
+            limits,
+        )?),
+        WitnessGenMode::Subprocess => Box::new(SubprocessWitnessGenerator::new(
+            config,
+            use_new_setup,
+            limits,
+        )),
+    })
+}
+
+/// Generates a witness with `generator`, recording how long it took and how it ended.
+pub async fn generate_witness(
+    generator: &dyn WitnessGenerator,
+    input: Value,
+) -> Result<Vec<u8>, WitnessGenError> {
+    // Counted as cancelled unless the generation finishes, since the future may be dropped at
+    // any await point.
+    let mut outcome = OutcomeRecorder("cancelled");
+    let start_time = Instant::now();
+
+    let result = generator.generate(input).await;
+
+    metrics::WITNESS_TIME_SECS.observe(start_time.elapsed().as_secs_f64());
+    outcome.0 = match &result {
+        Ok(_) => "success",
+        Err(e) => e.outcome(),
+    };
+    result
+}
+
+struct OutcomeRecorder(&'static str);
+
+impl Drop for OutcomeRecorder {
+    fn drop(&mut self) {
+        metrics::WITNESS_GEN_OUTCOMES
+            .with_label_values(&[self.0])
+            .inc();
+    }
+}
 
-    let input_file = NamedTempFile::new()?;
+/// rust-rapidsnark only reads witnesses from a file.
+pub fn write_witness_file(wtns: &[u8]) -> Result<NamedTempFile> {
     let witness_file = NamedTempFile::new()?;
+    fs::write(witness_file.path(), wtns)?;
+    Ok(witness_file)
+}
+
+/// Runs the setup's `main.wasm` witness calculator in-process, on Tokio's blocking thread pool.
+pub struct InProcessWitnessGenerator {
+    calculator: WitnessCalculator,
+    limits: WitnessGenLimits,
+}
+
+impl InProcessWitnessGenerator {
+    pub fn new(wasm_path: &str, limits: WitnessGenLimits) -> Result<Self> {
+        Ok(Self {
+            calculator: WitnessCalculator::from_file(wasm_path)?,
+            limits,
+        })
+    }
+}
+
+/// Sets the flag when dropped, so that a blocking task stops once its result isn't awaited
+/// anymore.
+struct CancelOnDrop(Arc<AtomicBool>);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+impl WitnessGenerator for InProcessWitnessGenerator {
+    fn generate(&self, input: Value) -> BoxFuture<'_, Result<Vec<u8>, WitnessGenError>> {
+        let span = info_span!("Generating witness");
+
+        Box::pin(
+            async move {
+                let calculator = self.calculator.clone();
+                let calculation_limits = CalculationLimits {
+                    timeout: Some(self.limits.timeout),
+                    max_memory_bytes: self.limits.max_memory_bytes.map(|bytes| bytes as usize),
+                    cancelled: Arc::new(AtomicBool::new(false)),
+                };
+                let _cancel_on_drop = CancelOnDrop(calculation_limits.cancelled.clone());
+
+                tokio::task::spawn_blocking(move || {
+                    calculator.calculate_wtns_with_limits(&input, &calculation_limits)
+                })
+                .await
+                .map_err(failed)?
+                .map_err(|e| match e.downcast_ref::<Interruption>() {
+                    Some(Interruption::Timeout) => WitnessGenError::Timeout(self.limits.timeout),
+                    Some(Interruption::Cancelled) => WitnessGenError::Cancelled,
+                    Some(Interruption::MemoryLimit) => WitnessGenError::ResourceLimit(e),
+                    None => WitnessGenError::Failed(e),
+                })
+            }
+            .instrument(span),
+        )
+    }
+}
+
+/// Runs the setup's witness generation binary, or `node generate_witness.js` where the binary
+/// isn't available, and reads back the `.wtns` file it writes. The input and output file paths
+/// are appended to `args`.
+pub struct SubprocessWitnessGenerator {
+    program: String,
+    args: Vec<String>,
+    limits: WitnessGenLimits,
+}
+
+impl SubprocessWitnessGenerator {
+    #[cfg(not(target_arch = "x86_64"))]
+    pub fn new(
+        config: &ProverServiceConfig,
+        use_new_setup: bool,
+        limits: WitnessGenLimits,
+    ) -> Self {
+        Self::with_command(
+            String::from("node"),
+            vec![
+                config.witness_gen_js_path(use_new_setup),
+                config.witness_gen_wasm_path(use_new_setup),
+            ],
+            limits,
+        )
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    pub fn new(
+        config: &ProverServiceConfig,
+        use_new_setup: bool,
+        limits: WitnessGenLimits,
+    ) -> Self {
+        Self::with_command(
+            config.witness_gen_binary_path(use_new_setup),
+            vec![],
+            limits,
+        )
+    }
+
+    pub fn with_command(program: String, args: Vec<String>, limits: WitnessGenLimits) -> Self {
+        Self {
+            program,
+            args,
+            limits,
+        }
+    }
+
+    async fn run(&self, input: Value) -> Result<Vec<u8>, WitnessGenError> {
+        let input_file = NamedTempFile::new().map_err(failed)?;
+        let witness_file = NamedTempFile::new().map_err(failed)?;
+
+        let input = serde_json::to_vec(&input).map_err(failed)?;
+        tokio::fs::write(input_file.path(), input)
+            .await
+            .map_err(failed)?;
+
+        let mut command = Command::new(&self.program);
+        command
+            .args(&self.args)
+            .arg(input_file.path())
+            .arg(witness_file.path())
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            // The child is killed when the future below is dropped, be it because of the timeout
+            // or because the request was cancelled.
+            .kill_on_drop(true);
+        set_rlimits(&mut command, &self.limits);
+
+        let child = command.spawn().map_err(failed)?;
+        let output = tokio::time::timeout(self.limits.timeout, child.wait_with_output())
+            .await
+            .map_err(|_| WitnessGenError::Timeout(self.limits.timeout))?
+            .map_err(failed)?;
+
+        if output.status.success() {
+            return tokio::fs::read(witness_file.path()).await.map_err(failed);
+        }
 
-    fs::write(input_file.path(), body.as_bytes())?;
-
-    let output = get_witness_command(
-        config,
-        use_new_setup,
-        input_file.path_str()?,
-        witness_file.path_str()?,
-    )
-    .output()?;
-
-    // Check if the command executed successfully
-    if output.status.success() {
-        // Convert the output bytes to a string
-        // let stdout = String::from_utf8_lossy(&output.stdout);
-
-        // Print the output
-        // This prints sensitive data. Do not uncomment in production.
-        //println!("Command output:\n{}", stdout);
-        Ok(witness_file)
-    } else {
-        // Print the error message if the command failed
         let stderr = String::from_utf8_lossy(&output.stderr);
-        bail!("Command failed:\n{}", stderr);
+        let error = anyhow!("Command failed with {}:\n{}", output.status, stderr);
+        if hit_rlimit(&output.status, &self.limits) {
+            Err(WitnessGenError::ResourceLimit(error))
+        } else {
+            Err(WitnessGenError::Failed(error))
+        }
     }
 }
 
-#[cfg(not(target_arch = "x86_64"))]
-fn get_witness_command(
-    config: &ProverServiceConfig,
-    use_new_setup: bool,
-    input_file_path: &str,
-    witness_file_path: &str,
-) -> Command {
-    let mut c = Command::new("node");
-    c.args(&[
-        config.witness_gen_js_path(use_new_setup),
-        config.witness_gen_wasm_path(use_new_setup),
-        String::from(input_file_path),
-        String::from(witness_file_path),
-    ]);
-    c
-}
-
-#[cfg(target_arch = "x86_64")]
-fn get_witness_command(
-    config: &ProverServiceConfig,
-    use_new_setup: bool,
-    input_file_path: &str,
-    witness_file_path: &str,
-) -> Command {
-    let mut c = Command::new(config.witness_gen_binary_path(use_new_setup));
-    c.args([input_file_path, witness_file_path]); // Example arguments
-    c
+impl WitnessGenerator for SubprocessWitnessGenerator {
+    fn generate(&self, input: Value) -> BoxFuture<'_, Result<Vec<u8>, WitnessGenError>> {
+        Box::pin(
+            self.run(input)
+                .instrument(info_span!("Generating witness with a subprocess")),
+        )
+    }
+}
+
+#[cfg(unix)]
+fn set_rlimits(command: &mut Command, limits: &WitnessGenLimits) {
+    let limits = *limits;
+    if limits.max_memory_bytes.is_none() && limits.max_cpu_secs.is_none() {
+        return;
+    }
+
+    // SAFETY: only calls setrlimit, which is async-signal-safe, between fork and exec.
+    unsafe {
+        command.pre_exec(move || {
+            let rlimit = |limit: u64| libc::rlimit {
+                rlim_cur: limit as libc::rlim_t,
+                rlim_max: limit as libc::rlim_t,
+            };
+            if let Some(max_memory_bytes) = limits.max_memory_bytes {
+                if libc::setrlimit(libc::RLIMIT_AS, &rlimit(max_memory_bytes)) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            if let Some(max_cpu_secs) = limits.max_cpu_secs {
+                if libc::setrlimit(libc::RLIMIT_CPU, &rlimit(max_cpu_secs)) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        });
+    }
+}
+
+#[cfg(not(unix))]
+fn set_rlimits(_command: &mut Command, _limits: &WitnessGenLimits) {}
+
+/// Whether the child was most likely stopped by one of its rlimits: exceeding the CPU limit
+/// raises SIGXCPU, and failing to allocate memory usually ends in an abort or a segfault.
+#[cfg(unix)]
+fn hit_rlimit(status: &std::process::ExitStatus, limits: &WitnessGenLimits) -> bool {
+    use std::os::unix::process::ExitStatusExt;
+
+    match status.signal() {
+        Some(libc::SIGXCPU) | Some(libc::SIGKILL) => limits.max_cpu_secs.is_some(),
+        Some(libc::SIGABRT) | Some(libc::SIGSEGV) => limits.max_memory_bytes.is_some(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn hit_rlimit(_status: &std::process::ExitStatus, _limits: &WitnessGenLimits) -> bool {
+    false
 }
@@ -0,0 +1,426 @@
+// Copyright © Aptos Foundation
+
+//! Readers for the binary files of snarkjs: `.zkey` proving keys and `.wtns` witnesses. Both are a
+//! magic, a version, and sections of a type and a size.
+
+use anyhow::{bail, Context, Result};
+use ark_bn254::{Bn254, Fq, Fq2, Fr, G1Affine, G2Affine};
+use ark_ff::{BigInt, Fp, MontBackend, MontConfig, PrimeField, Zero};
+use ark_groth16::{ProvingKey, VerifyingKey};
+use ark_relations::r1cs::ConstraintMatrices;
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufReader, Read, Seek, SeekFrom},
+};
+
+const ZKEY_MAGIC: &[u8; 4] = b"zkey";
+const WTNS_MAGIC: &[u8; 4] = b"wtns";
+const HEADER_SECTION: u32 = 1;
+const GROTH16_HEADER_SECTION: u32 = 2;
+const IC_SECTION: u32 = 3;
+const COEFFS_SECTION: u32 = 4;
+const A_SECTION: u32 = 5;
+const B1_SECTION: u32 = 6;
+const B2_SECTION: u32 = 7;
+const C_SECTION: u32 = 8;
+const H_SECTION: u32 = 9;
+const WITNESS_SECTION: u32 = 2;
+const GROTH16_PROTOCOL_ID: u32 = 1;
+/// The size of the BN254 base and scalar field elements.
+const N8: u32 = 32;
+
+/// The sizes recorded in the Groth16 header section of a snarkjs `.zkey` file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ZkeyHeader {
+    /// The number of witness elements, including the leading constant 1.
+    pub n_vars: u32,
+    /// The number of public inputs and outputs.
+    pub n_public: u32,
+    pub domain_size: u32,
+}
+
+impl ZkeyHeader {
+    /// Reads the header without loading the rest of the (typically very large) file.
+    pub fn read(path: &str) -> Result<Self> {
+        let file = File::open(path).with_context(|| format!("Couldn't open zkey {}", path))?;
+        Self::read_from(BufReader::new(file)).with_context(|| format!("Invalid zkey {}", path))
+    }
+
+    pub fn read_from(mut reader: impl Read + Seek) -> Result<Self> {
+        let sections = read_sections(&mut reader, ZKEY_MAGIC)?;
+        Ok(read_groth16_header(&mut reader, &sections)?.0)
+    }
+}
+
+/// A snarkjs Groth16 zkey, in the form the arkworks prover takes it.
+pub struct Zkey {
+    pub header: ZkeyHeader,
+    pub proving_key: ProvingKey<Bn254>,
+    /// The A and B matrices of the constraints. snarkjs appends a constraint per public input,
+    /// which are left out since arkworks adds them back when proving.
+    pub matrices: ConstraintMatrices<Fr>,
+}
+
+impl Zkey {
+    /// Reads the whole zkey into memory.
+    pub fn read(path: &str) -> Result<Self> {
+        let file = File::open(path).with_context(|| format!("Couldn't open zkey {}", path))?;
+        Self::read_from(BufReader::new(file)).with_context(|| format!("Invalid zkey {}", path))
+    }
+
+    pub fn read_from(mut reader: impl Read + Seek) -> Result<Self> {
+        let sections = read_sections(&mut reader, ZKEY_MAGIC)?;
+        let (header, points) = read_groth16_header(&mut reader, &sections)?;
+        let n_vars = header.n_vars as usize;
+        let n_public = header.n_public as usize;
+
+        seek_section(&mut reader, &sections, IC_SECTION)?;
+        let gamma_abc_g1 = read_g1s(&mut reader, n_public + 1).context("IC section")?;
+        seek_section(&mut reader, &sections, A_SECTION)?;
+        let a_query = read_g1s(&mut reader, n_vars).context("A section")?;
+        seek_section(&mut reader, &sections, B1_SECTION)?;
+        let b_g1_query = read_g1s(&mut reader, n_vars).context("B1 section")?;
+        seek_section(&mut reader, &sections, B2_SECTION)?;
+        let b_g2_query = read_g2s(&mut reader, n_vars).context("B2 section")?;
+        seek_section(&mut reader, &sections, C_SECTION)?;
+        let l_query = read_g1s(&mut reader, n_vars - n_public - 1).context("C section")?;
+        seek_section(&mut reader, &sections, H_SECTION)?;
+        let h_query = read_g1s(&mut reader, header.domain_size as usize).context("H section")?;
+
+        seek_section(&mut reader, &sections, COEFFS_SECTION)?;
+        let matrices = read_matrices(&mut reader, &header).context("coefficients section")?;
+
+        let proving_key = ProvingKey {
+            vk: VerifyingKey {
+                alpha_g1: points.alpha_g1,
+                beta_g2: points.beta_g2,
+                gamma_g2: points.gamma_g2,
+                delta_g2: points.delta_g2,
+                gamma_abc_g1,
+            },
+            beta_g1: points.beta_g1,
+            delta_g1: points.delta_g1,
+            a_query,
+            b_g1_query,
+            b_g2_query,
+            h_query,
+            l_query,
+        };
+        Ok(Self {
+            header,
+            proving_key,
+            matrices,
+        })
+    }
+}
+
+/// Reads the number of witness elements from a witness in the `.wtns` format. A witness fits a
+/// zkey if this is the zkey's `n_vars`.
+pub fn read_witness_len(mut reader: impl Read + Seek) -> Result<u32> {
+    let sections = read_sections(&mut reader, WTNS_MAGIC)?;
+    read_wtns_header(&mut reader, &sections)
+}
+
+/// Reads the elements of a witness in the `.wtns` format.
+pub fn read_witness(mut reader: impl Read + Seek) -> Result<Vec<Fr>> {
+    let sections = read_sections(&mut reader, WTNS_MAGIC)?;
+    let n_witness = read_wtns_header(&mut reader, &sections)?;
+    read_witness_elements(&mut reader, &sections, n_witness)
+}
+
+/// Reads the public inputs of a witness in the `.wtns` format, which has `n_public` of them. They
+/// come right after the constant 1 that starts every witness.
+pub fn read_witness_public_inputs(mut reader: impl Read + Seek, n_public: u32) -> Result<Vec<Fr>> {
+    let sections = read_sections(&mut reader, WTNS_MAGIC)?;
+    let n_witness = read_wtns_header(&mut reader, &sections)?;
+    if n_witness <= n_public {
+        bail!(
+            "The witness has {} elements, which is too few for {} public inputs",
+            n_witness,
+            n_public
+        );
+    }
+    let mut elements = read_witness_elements(&mut reader, &sections, n_public + 1)?;
+    elements.remove(0);
+    Ok(elements)
+}
+
+/// Reads the first `n` elements of the witness section.
+fn read_witness_elements(
+    reader: &mut (impl Read + Seek),
+    sections: &HashMap<u32, u64>,
+    n: u32,
+) -> Result<Vec<Fr>> {
+    seek_section(reader, sections, WITNESS_SECTION)?;
+    (0..n)
+        .map(|i| {
+            Fr::from_bigint(read_bigint(&mut *reader)?)
+                .with_context(|| format!("Witness element {} is not a scalar", i))
+        })
+        .collect()
+}
+
+fn read_wtns_header(reader: &mut (impl Read + Seek), sections: &HashMap<u32, u64>) -> Result<u32> {
+    seek_section(reader, sections, HEADER_SECTION)?;
+    let n8 = read_u32(reader)?;
+    reader.seek(SeekFrom::Current(n8 as i64))?;
+    read_u32(reader)
+}
+
+/// The points of the Groth16 header section, other than the sizes.
+struct HeaderPoints {
+    alpha_g1: G1Affine,
+    beta_g1: G1Affine,
+    beta_g2: G2Affine,
+    gamma_g2: G2Affine,
+    delta_g1: G1Affine,
+    delta_g2: G2Affine,
+}
+
+fn read_groth16_header(
+    reader: &mut (impl Read + Seek),
+    sections: &HashMap<u32, u64>,
+) -> Result<(ZkeyHeader, HeaderPoints)> {
+    seek_section(reader, sections, HEADER_SECTION)?;
+    if read_u32(reader)? != GROTH16_PROTOCOL_ID {
+        bail!("Not a Groth16 zkey");
+    }
+
+    seek_section(reader, sections, GROTH16_HEADER_SECTION)?;
+    let n8q = read_u32(reader)?;
+    reader.seek(SeekFrom::Current(n8q as i64))?;
+    let n8r = read_u32(reader)?;
+    reader.seek(SeekFrom::Current(n8r as i64))?;
+    let header = ZkeyHeader {
+        n_vars: read_u32(reader)?,
+        n_public: read_u32(reader)?,
+        domain_size: read_u32(reader)?,
+    };
+    if n8q != N8 || n8r != N8 {
+        bail!("Not a BN254 zkey");
+    }
+    if header.n_vars <= header.n_public {
+        bail!(
+            "The zkey has {} public inputs, but only {} witness elements",
+            header.n_public,
+            header.n_vars
+        );
+    }
+
+    let points = HeaderPoints {
+        alpha_g1: read_g1(reader)?,
+        beta_g1: read_g1(reader)?,
+        beta_g2: read_g2(reader)?,
+        gamma_g2: read_g2(reader)?,
+        delta_g1: read_g1(reader)?,
+        delta_g2: read_g2(reader)?,
+    };
+    Ok((header, points))
+}
+
+/// Each coefficient is a matrix (0 for A, 1 for B), a constraint, a signal and a value.
+fn read_matrices(reader: &mut impl Read, header: &ZkeyHeader) -> Result<ConstraintMatrices<Fr>> {
+    let n_coeffs = read_u32(reader)?;
+    let mut matrices = vec![vec![vec![]; header.domain_size as usize]; 2];
+    let mut n_constraints = 0;
+    for _ in 0..n_coeffs {
+        let matrix = read_u32(reader)? as usize;
+        let constraint = read_u32(reader)? as usize;
+        let signal = read_u32(reader)? as usize;
+        // Coefficients are multiplied by the Montgomery constant twice.
+        let value: Fr = read_montgomery(reader)?;
+        let value = Fr::new_unchecked(value.into_bigint());
+
+        if matrix > 1 || constraint >= header.domain_size as usize {
+            bail!("Coefficient out of bounds: {} {}", matrix, constraint);
+        }
+        if signal >= header.n_vars as usize {
+            bail!("Coefficient of an unknown signal {}", signal);
+        }
+        n_constraints = n_constraints.max(constraint + 1);
+        matrices[matrix][constraint].push((value, signal));
+    }
+
+    // The last constraints are those appended for the public inputs, including the constant 1.
+    let n_public = header.n_public as usize;
+    let num_constraints = n_constraints.checked_sub(n_public + 1).with_context(|| {
+        format!(
+            "There are fewer constraints than the {} public inputs",
+            n_public
+        )
+    })?;
+    for matrix in matrices.iter_mut() {
+        matrix.truncate(num_constraints);
+    }
+    let b = matrices.pop().expect("There are two matrices");
+    let a = matrices.pop().expect("There are two matrices");
+
+    Ok(ConstraintMatrices {
+        num_instance_variables: n_public + 1,
+        num_witness_variables: header.n_vars as usize - n_public - 1,
+        num_constraints,
+        a_num_non_zero: a.iter().map(Vec::len).sum(),
+        b_num_non_zero: b.iter().map(Vec::len).sum(),
+        c_num_non_zero: 0,
+        a,
+        b,
+        c: vec![],
+    })
+}
+
+/// Indexes the offsets of the sections of a file by their type.
+fn read_sections(reader: &mut (impl Read + Seek), magic: &[u8; 4]) -> Result<HashMap<u32, u64>> {
+    let mut file_magic = [0u8; 4];
+    reader.read_exact(&mut file_magic)?;
+    if &file_magic != magic {
+        bail!("Not a {} file", String::from_utf8_lossy(magic));
+    }
+    let _version = read_u32(reader)?;
+    let n_sections = read_u32(reader)?;
+
+    let mut sections = HashMap::new();
+    for _ in 0..n_sections {
+        let section_type = read_u32(reader)?;
+        let section_size = read_u64(reader)?;
+        let offset = reader.stream_position()?;
+        sections.entry(section_type).or_insert(offset);
+        reader.seek(SeekFrom::Current(section_size as i64))?;
+    }
+    Ok(sections)
+}
+
+fn seek_section(
+    reader: &mut impl Seek,
+    sections: &HashMap<u32, u64>,
+    section_type: u32,
+) -> Result<()> {
+    let offset = sections
+        .get(&section_type)
+        .with_context(|| format!("Missing section {}", section_type))?;
+    reader.seek(SeekFrom::Start(*offset))?;
+    Ok(())
+}
+
+fn read_bigint(reader: &mut impl Read) -> Result<BigInt<4>> {
+    let mut limbs = [0u64; 4];
+    for limb in limbs.iter_mut() {
+        *limb = read_u64(reader)?;
+    }
+    Ok(BigInt::new(limbs))
+}
+
+/// Field elements of points and coefficients are in Montgomery form.
+fn read_montgomery<P: MontConfig<4>>(reader: &mut impl Read) -> Result<Fp<MontBackend<P, 4>, 4>> {
+    let bigint = read_bigint(reader)?;
+    if bigint >= P::MODULUS {
+        bail!("{} is not a field element", bigint);
+    }
+    Ok(Fp::new_unchecked(bigint))
+}
+
+/// Points at infinity are all zeros.
+fn read_g1(reader: &mut impl Read) -> Result<G1Affine> {
+    let x: Fq = read_montgomery(reader)?;
+    let y: Fq = read_montgomery(reader)?;
+    let point = if x.is_zero() && y.is_zero() {
+        G1Affine::identity()
+    } else {
+        G1Affine::new_unchecked(x, y)
+    };
+    if !point.is_on_curve() {
+        bail!("G1 point not on the curve");
+    }
+    Ok(point)
+}
+
+fn read_g2(reader: &mut impl Read) -> Result<G2Affine> {
+    let x = Fq2::new(read_montgomery(reader)?, read_montgomery(reader)?);
+    let y = Fq2::new(read_montgomery(reader)?, read_montgomery(reader)?);
+    let point = if x.is_zero() && y.is_zero() {
+        G2Affine::identity()
+    } else {
+        G2Affine::new_unchecked(x, y)
+    };
+    if !point.is_on_curve() {
+        bail!("G2 point not on the curve");
+    }
+    Ok(point)
+}
+
+fn read_g1s(reader: &mut impl Read, n: usize) -> Result<Vec<G1Affine>> {
+    (0..n).map(|_| read_g1(reader)).collect()
+}
+
+fn read_g2s(reader: &mut impl Read, n: usize) -> Result<Vec<G2Affine>> {
+    (0..n).map(|_| read_g2(reader)).collect()
+}
+
+fn read_u32(reader: &mut impl Read) -> Result<u32> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64(reader: &mut impl Read) -> Result<u64> {
+    let mut buf = [0u8; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_witness, read_witness_len, Zkey, ZkeyHeader};
+    use ark_bn254::Fr;
+    use std::{fs, io::Cursor};
+
+    #[test]
+    fn test_read_toy_zkey_header() {
+        let header = ZkeyHeader::read("resources/toy_circuit/toy_1.zkey").unwrap();
+
+        assert_eq!(
+            header,
+            ZkeyHeader {
+                n_vars: 3,
+                n_public: 1,
+                domain_size: 4,
+            }
+        );
+    }
+
+    #[test]
+    fn test_read_toy_zkey() {
+        let zkey = Zkey::read("resources/toy_circuit/toy_1.zkey").unwrap();
+
+        assert_eq!(
+            zkey.header,
+            ZkeyHeader::read("resources/toy_circuit/toy_1.zkey").unwrap()
+        );
+        assert_eq!(zkey.proving_key.vk.gamma_abc_g1.len(), 2);
+        assert_eq!(zkey.proving_key.a_query.len(), 3);
+        assert_eq!(zkey.proving_key.l_query.len(), 1);
+        assert_eq!(zkey.proving_key.h_query.len(), 4);
+        assert_eq!(zkey.matrices.num_instance_variables, 2);
+        assert_eq!(zkey.matrices.num_witness_variables, 1);
+    }
+
+    #[test]
+    fn test_read_toy_witness() {
+        let wtns = fs::read("resources/toy_circuit/toy.wtns").unwrap();
+        let header = ZkeyHeader::read("resources/toy_circuit/toy_1.zkey").unwrap();
+
+        assert_eq!(read_witness_len(Cursor::new(&wtns)).unwrap(), header.n_vars);
+        let witness = read_witness(Cursor::new(&wtns)).unwrap();
+        assert_eq!(witness.len(), 3);
+        assert_eq!(witness[0], Fr::from(1u64));
+        assert!(read_witness_len(Cursor::new(b"zkey\x01\x00\x00\x00")).is_err());
+    }
+
+    #[test]
+    fn test_read_invalid_zkey() {
+        assert!(ZkeyHeader::read_from(Cursor::new(b"wtns\x02\x00\x00\x00")).is_err());
+        assert!(
+            ZkeyHeader::read_from(Cursor::new(b"zkey\x01\x00\x00\x00\x00\x00\x00\x00")).is_err()
+        );
+    }
+}